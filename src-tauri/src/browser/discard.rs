@@ -0,0 +1,36 @@
+// Tab Discarding
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// When background tabs give up their engine to save memory.
+///
+/// A discarded tab keeps its `TabInfo` and navigation history and gets a
+/// fresh engine the next time it is activated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TabDiscardPolicy {
+    /// Discard tabs that have been in the background this long
+    pub idle_timeout_secs: Option<u64>,
+    /// Keep at most this many engines alive, discarding the least recently
+    /// used background tabs first
+    pub max_live_engines: Option<usize>,
+    pub exempt_pinned: bool,
+    pub exempt_audible: bool,
+}
+
+impl TabDiscardPolicy {
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout_secs.map(Duration::from_secs)
+    }
+}
+
+impl Default for TabDiscardPolicy {
+    fn default() -> Self {
+        Self {
+            idle_timeout_secs: Some(30 * 60),
+            max_live_engines: Some(20),
+            exempt_pinned: true,
+            exempt_audible: true,
+        }
+    }
+}
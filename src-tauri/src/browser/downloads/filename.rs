@@ -0,0 +1,316 @@
+// Download Filename Resolution
+use percent_encoding::percent_decode_str;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+const FALLBACK_FILENAME: &str = "download";
+// Most filesystems cap names at 255 bytes
+const MAX_FILENAME_BYTES: usize = 255;
+// Extensions that should stay together when numbering duplicates
+const COMPOUND_EXTENSIONS: &[&str] = &["tar.gz", "tar.bz2", "tar.xz", "tar.zst"];
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Picks a safe filename for a download: the `Content-Disposition` name if
+/// the server sent one, otherwise the last URL path segment, with an
+/// extension inferred from the MIME type when the name has none.
+pub fn resolve_filename(url: &str, content_disposition: Option<&str>, mime_type: Option<&str>) -> String {
+    let name = content_disposition
+        .and_then(filename_from_content_disposition)
+        .or_else(|| filename_from_url(url))
+        .unwrap_or_else(|| FALLBACK_FILENAME.to_string());
+    let mut filename = sanitize_filename(&name);
+
+    if !has_extension(&filename) {
+        if let Some(extension) = mime_type.and_then(extension_for_mime) {
+            filename = format!("{}.{}", filename, extension);
+        }
+    }
+    filename
+}
+
+/// Extracts the filename from a `Content-Disposition` header, preferring
+/// the RFC 5987 `filename*` parameter over plain `filename`.
+pub fn filename_from_content_disposition(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+
+    for (name, value) in parse_parameters(value) {
+        match name.to_ascii_lowercase().as_str() {
+            "filename*" => extended = extended.or_else(|| decode_extended_value(&value)),
+            "filename" => plain = plain.or(Some(value)),
+            _ => {}
+        }
+    }
+    extended.or(plain).filter(|name| !name.trim().is_empty())
+}
+
+/// The percent-decoded last path segment of a URL, ignoring the query
+/// and fragment
+pub fn filename_from_url(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let segment = url.path_segments()?.rev().find(|segment| !segment.is_empty())?;
+    let name = percent_decode_str(segment).decode_utf8_lossy().to_string();
+    Some(name).filter(|name| !name.trim().is_empty())
+}
+
+pub fn extension_for_mime(mime_type: &str) -> Option<&'static str> {
+    let essence = mime_type.split(';').next()?.trim().to_ascii_lowercase();
+    let extension = match essence.as_str() {
+        "application/pdf" => "pdf",
+        "application/zip" => "zip",
+        "application/gzip" | "application/x-gzip" => "gz",
+        "application/x-tar" => "tar",
+        "application/x-7z-compressed" => "7z",
+        "application/x-iso9660-image" => "iso",
+        "application/json" => "json",
+        "application/xml" | "text/xml" => "xml",
+        "application/javascript" | "text/javascript" => "js",
+        "application/wasm" => "wasm",
+        "application/vnd.debian.binary-package" => "deb",
+        "application/x-rpm" => "rpm",
+        "application/x-msdownload" | "application/vnd.microsoft.portable-executable" => "exe",
+        "text/plain" => "txt",
+        "text/html" => "html",
+        "text/css" => "css",
+        "text/csv" => "csv",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "audio/mpeg" => "mp3",
+        "audio/ogg" => "ogg",
+        "audio/wav" => "wav",
+        "video/mp4" => "mp4",
+        "video/webm" => "webm",
+        _ => return None,
+    };
+    Some(extension)
+}
+
+/// Makes a name safe on Windows, macOS and Linux: strips path separators,
+/// characters Windows rejects and control characters, trims leading and
+/// trailing dots and spaces, avoids reserved device names and caps the
+/// length while keeping the extension.
+pub fn sanitize_filename(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let mut filename = replaced.trim_matches(|c: char| c == '.' || c.is_whitespace()).to_string();
+    if filename.is_empty() || filename.chars().all(|c| c == '_') {
+        return FALLBACK_FILENAME.to_string();
+    }
+
+    let (stem, extension) = split_extension(&filename);
+    if WINDOWS_RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem)) {
+        filename = format!("{}_{}", stem, extension);
+    }
+
+    truncate_filename(&filename)
+}
+
+/// Returns `directory/filename`, or the first free `name (n).ext` variant
+/// if that path is taken on disk or by `reserved`.
+pub fn unique_path(directory: &Path, filename: &str, reserved: impl Fn(&Path) -> bool) -> PathBuf {
+    let is_free = |path: &Path| !path.exists() && !reserved(path);
+
+    let path = directory.join(filename);
+    if is_free(&path) {
+        return path;
+    }
+
+    let (stem, extension) = split_extension(filename);
+    (1..)
+        .map(|n| directory.join(truncate_filename(&format!("{} ({}){}", stem, n, extension))))
+        .find(|path| is_free(path))
+        .unwrap()
+}
+
+fn has_extension(filename: &str) -> bool {
+    !split_extension(filename).1.is_empty()
+}
+
+// Splits "name.tar.gz" into ("name", ".tar.gz"). Dotfiles have no extension.
+fn split_extension(filename: &str) -> (&str, &str) {
+    let lowercase = filename.to_ascii_lowercase();
+    for compound in COMPOUND_EXTENSIONS {
+        let suffix_len = compound.len() + 1;
+        if lowercase.len() > suffix_len && lowercase.ends_with(&format!(".{}", compound)) {
+            return filename.split_at(filename.len() - suffix_len);
+        }
+    }
+    match filename.rfind('.') {
+        Some(index) if index > 0 && index + 1 < filename.len() => filename.split_at(index),
+        _ => (filename, ""),
+    }
+}
+
+fn truncate_filename(filename: &str) -> String {
+    if filename.len() <= MAX_FILENAME_BYTES {
+        return filename.to_string();
+    }
+    let (stem, extension) = split_extension(filename);
+    let extension = if extension.len() < MAX_FILENAME_BYTES / 2 { extension } else { "" };
+    let mut end = MAX_FILENAME_BYTES - extension.len();
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &stem[..end], extension)
+}
+
+// Splits `attachment; name=value; name="quoted value"` into its parameters
+fn parse_parameters(header: &str) -> Vec<(String, String)> {
+    let mut parameters = Vec::new();
+    let mut rest = match header.split_once(';') {
+        Some((_, rest)) => rest,
+        None => return parameters,
+    };
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ';' || c.is_whitespace());
+        let Some((name, after_name)) = rest.split_once('=') else {
+            break;
+        };
+        let after_name = after_name.trim_start();
+
+        let value;
+        if let Some(quoted) = after_name.strip_prefix('"') {
+            // Quoted string with backslash escapes
+            let mut unescaped = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = quoted.len();
+            while let Some((index, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some((_, escaped)) = chars.next() {
+                            unescaped.push(escaped);
+                        }
+                    }
+                    '"' => {
+                        end = index + 1;
+                        break;
+                    }
+                    c => unescaped.push(c),
+                }
+            }
+            value = unescaped;
+            rest = &quoted[end.min(quoted.len())..];
+        } else {
+            let end = after_name.find(';').unwrap_or(after_name.len());
+            value = after_name[..end].trim().to_string();
+            rest = &after_name[end..];
+        }
+
+        parameters.push((name.trim().to_string(), value));
+    }
+    parameters
+}
+
+// Decodes an RFC 5987 `charset'language'percent-encoded` value
+fn decode_extended_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?.trim();
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+
+    let bytes: Vec<u8> = percent_decode_str(encoded).collect();
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_names_drop_query_and_decode_percent_escapes() {
+        assert_eq!(
+            filename_from_url("https://example.com/files/My%20Report.pdf?token=abc#page=2"),
+            Some("My Report.pdf".to_string())
+        );
+        assert_eq!(filename_from_url("https://example.com/releases/"), Some("releases".to_string()));
+        assert_eq!(filename_from_url("https://example.com/"), None);
+    }
+
+    #[test]
+    fn content_disposition_prefers_extended_filename() {
+        assert_eq!(
+            filename_from_content_disposition(
+                "attachment; filename=\"fallback.txt\"; filename*=UTF-8''%E2%82%AC%20rates.txt"
+            ),
+            Some("€ rates.txt".to_string())
+        );
+        assert_eq!(
+            filename_from_content_disposition("attachment; filename=\"quoted \\\"name\\\".zip\""),
+            Some("quoted \"name\".zip".to_string())
+        );
+        assert_eq!(
+            filename_from_content_disposition("attachment; filename=plain.tar.gz; size=10"),
+            Some("plain.tar.gz".to_string())
+        );
+        assert_eq!(
+            filename_from_content_disposition("attachment; filename*=iso-8859-1'en'%A3%20rates.txt"),
+            Some("£ rates.txt".to_string())
+        );
+        assert_eq!(filename_from_content_disposition("inline"), None);
+    }
+
+    #[test]
+    fn sanitizing_blocks_traversal_and_illegal_characters() {
+        assert_eq!(sanitize_filename("../../etc/passwd"), "_.._etc_passwd");
+        assert_eq!(sanitize_filename(".."), "download");
+        assert_eq!(sanitize_filename("a<b>c:d\"e|f?g*h.txt"), "a_b_c_d_e_f_g_h.txt");
+        assert_eq!(sanitize_filename("  .hidden. "), "hidden");
+        assert_eq!(sanitize_filename("con.txt"), "con_.txt");
+        assert_eq!(sanitize_filename("tab\there"), "tab_here");
+
+        let long_name = format!("{}.iso", "a".repeat(300));
+        let truncated = sanitize_filename(&long_name);
+        assert_eq!(truncated.len(), 255);
+        assert!(truncated.ends_with(".iso"));
+    }
+
+    #[test]
+    fn missing_extension_comes_from_mime_type() {
+        assert_eq!(
+            resolve_filename("https://example.com/export?id=4", None, Some("application/pdf; charset=binary")),
+            "export.pdf"
+        );
+        assert_eq!(resolve_filename("https://example.com/", None, None), "download");
+        assert_eq!(
+            resolve_filename("https://example.com/a.bin", Some("attachment; filename=\"report.csv\""), None),
+            "report.csv"
+        );
+    }
+
+    #[test]
+    fn duplicates_are_numbered_before_the_extension() {
+        let directory = std::env::temp_dir().join(format!("vishwakarma-names-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("archive.tar.gz"), b"").unwrap();
+        std::fs::write(directory.join("archive (1).tar.gz"), b"").unwrap();
+
+        assert_eq!(
+            unique_path(&directory, "archive.tar.gz", |_| false),
+            directory.join("archive (2).tar.gz")
+        );
+        let reserved = directory.join("notes.txt");
+        assert_eq!(
+            unique_path(&directory, "notes.txt", |path| path == reserved),
+            directory.join("notes (1).txt")
+        );
+    }
+}
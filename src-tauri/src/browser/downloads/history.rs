@@ -0,0 +1,150 @@
+// Download History Persistence
+use super::{DownloadItem, DownloadSchedule, DownloadStatus};
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const DOWNLOAD_HISTORY_VERSION: u32 = 1;
+
+const HISTORY_FILE_NAME: &str = "downloads.json";
+const TEMP_FILE_NAME: &str = "downloads.json.tmp";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DownloadHistory {
+    version: u32,
+    saved_at: u64,
+    /// Oldest first
+    downloads: Vec<DownloadItem>,
+    #[serde(default)]
+    settings: DownloadSettings,
+}
+
+/// Manager-wide settings saved along with the downloads list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadSettings {
+    /// Combined speed of every download, in bytes per second
+    pub global_rate_limit: Option<u64>,
+    pub schedule: Option<DownloadSchedule>,
+}
+
+/// Reads and writes the downloads list inside a directory. Writes go
+/// through a temporary file so a crash never leaves a truncated history.
+pub struct DownloadHistoryStore {
+    directory: PathBuf,
+}
+
+impl DownloadHistoryStore {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    pub fn history_path(&self) -> PathBuf {
+        self.directory.join(HISTORY_FILE_NAME)
+    }
+
+    pub fn save(&self, downloads: &[&DownloadItem], settings: &DownloadSettings) -> Result<()> {
+        fs::create_dir_all(&self.directory)
+            .with_context(|| format!("Failed to create download history directory {:?}", self.directory))?;
+
+        let history = DownloadHistory {
+            version: DOWNLOAD_HISTORY_VERSION,
+            saved_at: now_secs(),
+            downloads: downloads.iter().map(|download| (*download).clone()).collect(),
+            settings: *settings,
+        };
+        let temp_path = self.directory.join(TEMP_FILE_NAME);
+        {
+            let mut file = fs::File::create(&temp_path)?;
+            file.write_all(&serde_json::to_vec_pretty(&history)?)?;
+            file.sync_all()?;
+        }
+        fs::rename(&temp_path, self.history_path())?;
+        Ok(())
+    }
+
+    pub fn load(&self) -> Result<(Vec<DownloadItem>, DownloadSettings)> {
+        let path = self.history_path();
+        if !path.exists() {
+            return Ok((Vec::new(), DownloadSettings::default()));
+        }
+        let history = Self::read_history(&path)?;
+        info!("Loaded {} downloads from {:?}", history.downloads.len(), path);
+        Ok((history.downloads, history.settings))
+    }
+
+    fn read_history(path: &Path) -> Result<DownloadHistory> {
+        let history: DownloadHistory = serde_json::from_slice(&fs::read(path)?)?;
+        if history.version > DOWNLOAD_HISTORY_VERSION {
+            anyhow::bail!("Unsupported download history version {}", history.version);
+        }
+        Ok(history)
+    }
+}
+
+/// How long finished downloads stay in the list. Paused and running
+/// downloads are never dropped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadRetention {
+    /// Drop finished downloads that ended longer ago than this
+    pub max_age_days: Option<u64>,
+    /// Keep at most this many finished downloads, dropping the oldest
+    pub max_finished_entries: Option<usize>,
+}
+
+impl Default for DownloadRetention {
+    fn default() -> Self {
+        Self {
+            max_age_days: Some(90),
+            max_finished_entries: Some(500),
+        }
+    }
+}
+
+impl DownloadRetention {
+    /// Returns the ids of the downloads that should be dropped
+    pub fn expired(&self, downloads: &[&DownloadItem], now: u64) -> Vec<String> {
+        let mut finished: Vec<&DownloadItem> = downloads
+            .iter()
+            .copied()
+            .filter(|download| {
+                matches!(
+                    download.status,
+                    DownloadStatus::Completed
+                        | DownloadStatus::Failed
+                        | DownloadStatus::Cancelled
+                        | DownloadStatus::Blocked
+                )
+            })
+            .collect();
+        // Newest first, so the tail is what gets dropped
+        finished.sort_by_key(|download| std::cmp::Reverse(finished_at(download)));
+
+        let max_age = self.max_age_days.map(|days| days * 24 * 60 * 60);
+        finished
+            .iter()
+            .enumerate()
+            .filter(|(index, download)| {
+                let too_old = max_age.is_some_and(|max_age| now.saturating_sub(finished_at(download)) > max_age);
+                let over_limit = self.max_finished_entries.is_some_and(|limit| *index >= limit);
+                too_old || over_limit
+            })
+            .map(|(_, download)| download.id.clone())
+            .collect()
+    }
+}
+
+fn finished_at(download: &DownloadItem) -> u64 {
+    download.end_time.unwrap_or(download.start_time)
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
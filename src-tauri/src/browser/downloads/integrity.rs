@@ -0,0 +1,218 @@
+// Download Integrity Checks
+use anyhow::Result;
+use log::info;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+use url::Url;
+
+const SIDECAR_EXTENSION: &str = "sha256";
+// Checksum files are a line per file; anything bigger is not one
+const MAX_SIDECAR_BYTES: usize = 64 * 1024;
+const READ_BUFFER_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Sha512,
+    Md5,
+}
+
+impl ChecksumAlgorithm {
+    fn hex_length(self) -> usize {
+        match self {
+            ChecksumAlgorithm::Sha256 => 64,
+            ChecksumAlgorithm::Sha512 => 128,
+            ChecksumAlgorithm::Md5 => 32,
+        }
+    }
+}
+
+/// A digest as lowercase hex
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checksum {
+    pub algorithm: ChecksumAlgorithm,
+    pub value: String,
+}
+
+impl Checksum {
+    pub fn new(algorithm: ChecksumAlgorithm, value: &str) -> Result<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        if value.len() != algorithm.hex_length() || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("Not a valid {:?} checksum: {}", algorithm, value);
+        }
+        Ok(Self { algorithm, value })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityStatus {
+    /// No expected checksum, or the download has not finished
+    #[default]
+    Unverified,
+    Verified,
+    Mismatch,
+}
+
+impl IntegrityStatus {
+    pub fn check(expected: Option<&Checksum>, actual: Option<&Checksum>) -> Self {
+        match (expected, actual) {
+            (Some(expected), Some(actual)) if expected.algorithm == actual.algorithm => {
+                if expected.value == actual.value {
+                    IntegrityStatus::Verified
+                } else {
+                    IntegrityStatus::Mismatch
+                }
+            }
+            _ => IntegrityStatus::Unverified,
+        }
+    }
+}
+
+/// Incremental digest for any supported algorithm
+pub enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Md5(Md5),
+}
+
+impl Hasher {
+    pub fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            ChecksumAlgorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+            ChecksumAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+            Hasher::Md5(hasher) => hasher.update(data),
+        }
+    }
+
+    pub fn finish(self) -> Checksum {
+        let (algorithm, digest) = match self {
+            Hasher::Sha256(hasher) => (ChecksumAlgorithm::Sha256, hasher.finalize().to_vec()),
+            Hasher::Sha512(hasher) => (ChecksumAlgorithm::Sha512, hasher.finalize().to_vec()),
+            Hasher::Md5(hasher) => (ChecksumAlgorithm::Md5, hasher.finalize().to_vec()),
+        };
+        Checksum {
+            algorithm,
+            value: digest.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        }
+    }
+}
+
+/// Hashes a file without blocking the runtime
+pub async fn hash_file(path: &Path, algorithm: ChecksumAlgorithm) -> Result<Checksum> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0u8; READ_BUFFER_BYTES];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finish())
+}
+
+/// `https://host/file.iso` becomes `https://host/file.iso.sha256`
+pub fn sidecar_url(url: &str) -> Option<String> {
+    let mut url = Url::parse(url).ok()?;
+    if !matches!(url.scheme(), "http" | "https") || url.path().ends_with('/') {
+        return None;
+    }
+    let path = format!("{}.{}", url.path(), SIDECAR_EXTENSION);
+    url.set_path(&path);
+    url.set_fragment(None);
+    Some(url.to_string())
+}
+
+/// Reads a `sha256sum`-style checksum file: either a bare digest or
+/// `<digest>  <name>` lines, of which the one naming `filename` wins.
+pub fn parse_sidecar(text: &str, filename: Option<&str>) -> Option<Checksum> {
+    let entries: Vec<(&str, Option<&str>)> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| match line.split_once(char::is_whitespace) {
+            Some((digest, name)) => (digest, Some(name.trim().trim_start_matches('*'))),
+            None => (line, None),
+        })
+        .collect();
+
+    let digest = match entries.as_slice() {
+        [(digest, _)] => *digest,
+        _ => entries
+            .iter()
+            .find(|(_, name)| name.is_some() && *name == filename)
+            .map(|(digest, _)| *digest)?,
+    };
+    Checksum::new(ChecksumAlgorithm::Sha256, digest).ok()
+}
+
+/// Looks for a published SHA-256 next to the download. Any failure just
+/// means there is nothing to check against.
+pub async fn fetch_sidecar(client: &reqwest::Client, sidecar_url: &str, filename: Option<&str>) -> Option<Checksum> {
+    let response = client.get(sidecar_url).send().await.ok()?.error_for_status().ok()?;
+    if response.content_length().is_some_and(|length| length as usize > MAX_SIDECAR_BYTES) {
+        return None;
+    }
+    let body = response.bytes().await.ok()?;
+    if body.len() > MAX_SIDECAR_BYTES {
+        return None;
+    }
+    let checksum = parse_sidecar(&String::from_utf8_lossy(&body), filename)?;
+    info!("Found checksum sidecar {}", sidecar_url);
+    Some(checksum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digests_match_known_values() {
+        let mut hasher = Hasher::new(ChecksumAlgorithm::Sha256);
+        hasher.update(b"ab");
+        hasher.update(b"c");
+        assert_eq!(
+            hasher.finish().value,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let mut hasher = Hasher::new(ChecksumAlgorithm::Md5);
+        hasher.update(b"abc");
+        assert_eq!(hasher.finish().value, "900150983cd24fb0d6963f7d28e17f72");
+
+        assert!(Checksum::new(ChecksumAlgorithm::Md5, "900150983CD24FB0D6963F7D28E17F72").is_ok());
+        assert!(Checksum::new(ChecksumAlgorithm::Sha256, "900150983cd24fb0d6963f7d28e17f72").is_err());
+    }
+
+    #[test]
+    fn sidecars_pick_the_matching_line() {
+        let first = "a".repeat(64);
+        let second = "b".repeat(64);
+
+        assert_eq!(
+            sidecar_url("https://example.com/releases/os.iso?mirror=2#top").as_deref(),
+            Some("https://example.com/releases/os.iso.sha256?mirror=2")
+        );
+        assert_eq!(sidecar_url("https://example.com/releases/"), None);
+
+        assert_eq!(parse_sidecar(&format!("{}\n", first), Some("os.iso")).unwrap().value, first);
+        let listing = format!("# SHA256SUMS\n{}  other.iso\n{} *os.iso\n", first, second);
+        assert_eq!(parse_sidecar(&listing, Some("os.iso")).unwrap().value, second);
+        assert_eq!(parse_sidecar(&listing, Some("missing.iso")), None);
+        assert_eq!(parse_sidecar("<html>Not Found</html>", None), None);
+    }
+}
@@ -0,0 +1,2258 @@
+// Download Manager
+use anyhow::Result;
+use log::{info, error, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use progress::TransferRate;
+use throttle::Throttle;
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+use uuid::Uuid;
+
+pub mod filename;
+pub mod history;
+pub mod integrity;
+pub mod progress;
+pub mod queue;
+pub mod retry;
+pub mod safety;
+pub mod schedule;
+pub mod segments;
+pub mod throttle;
+pub mod transfer;
+#[cfg(test)]
+mod test_server;
+
+pub use history::{DownloadHistoryStore, DownloadRetention, DownloadSettings};
+pub use integrity::{Checksum, ChecksumAlgorithm, IntegrityStatus};
+pub use progress::{DownloadProgress, ProgressThrottle};
+pub use queue::{DownloadPriority, DownloadQueueState};
+pub use retry::RetryPolicy;
+pub use safety::{DownloadSafetyPolicy, SafetyAction, SafetyCondition, SafetyRule, SafetyVerdict};
+pub use schedule::DownloadSchedule;
+pub use segments::{DownloadSegment, SegmentSettings};
+pub use throttle::RateLimiter;
+pub use transfer::{TransferMessage, TransferRequest, TransferUpdate};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DownloadStatus {
+    Pending,
+    InProgress,
+    Completed,
+    Failed,
+    Cancelled,
+    Paused,
+    /// Refused by the safety policy; nothing was kept
+    Blocked,
+    /// Finished but held as a partial file until the user keeps or
+    /// discards it
+    AwaitingConfirmation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadItem {
+    pub id: String,
+    pub url: String,
+    pub filename: String,
+    pub file_path: PathBuf,
+    pub total_bytes: Option<u64>,
+    pub downloaded_bytes: u64,
+    pub status: DownloadStatus,
+    pub mime_type: Option<String>,
+    pub start_time: u64,
+    /// Order the download was added in, which tells apart downloads
+    /// started within the same second
+    #[serde(default)]
+    pub sequence: u64,
+    pub end_time: Option<u64>,
+    pub error_message: Option<String>,
+    // Validators of the server copy, used to resume with `If-Range`
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    #[serde(default)]
+    pub accepts_ranges: bool,
+    /// The caller chose the filename, so the server's suggestion is ignored
+    #[serde(default)]
+    pub custom_filename: bool,
+    #[serde(default)]
+    pub priority: DownloadPriority,
+    /// Per-connection progress while the download is split into segments
+    #[serde(default)]
+    pub segments: Vec<DownloadSegment>,
+    /// Digest the finished file must match, from the caller or a sidecar
+    #[serde(default)]
+    pub expected_checksum: Option<Checksum>,
+    #[serde(default)]
+    pub actual_checksum: Option<Checksum>,
+    #[serde(default)]
+    pub integrity: IntegrityStatus,
+    /// Bytes per second, on top of the manager-wide limit
+    #[serde(default)]
+    pub rate_limit: Option<u64>,
+    /// Failed transfer attempts since the download was last started by hand
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// Unix time in milliseconds of the next automatic retry
+    #[serde(default)]
+    pub next_retry_at: Option<u64>,
+    /// Recent transfer speed, 0 unless the download is running
+    #[serde(default)]
+    pub bytes_per_second: u64,
+    /// Seconds left at the recent speed, if the size is known
+    #[serde(default)]
+    pub eta_secs: Option<u64>,
+    /// Time spent transferring, summed over every run
+    #[serde(default)]
+    pub elapsed_ms: u64,
+    /// Why the safety policy blocked the download or wants confirmation
+    #[serde(default)]
+    pub danger_reason: Option<String>,
+}
+
+impl DownloadItem {
+    pub fn new(url: String, filename: String, file_path: PathBuf) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            url,
+            filename,
+            file_path,
+            total_bytes: None,
+            downloaded_bytes: 0,
+            status: DownloadStatus::Pending,
+            mime_type: None,
+            start_time: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            sequence: 0,
+            end_time: None,
+            error_message: None,
+            etag: None,
+            last_modified: None,
+            accepts_ranges: false,
+            custom_filename: false,
+            priority: DownloadPriority::default(),
+            segments: Vec::new(),
+            expected_checksum: None,
+            actual_checksum: None,
+            integrity: IntegrityStatus::default(),
+            rate_limit: None,
+            attempts: 0,
+            last_error: None,
+            next_retry_at: None,
+            bytes_per_second: 0,
+            eta_secs: None,
+            elapsed_ms: 0,
+            danger_reason: None,
+        }
+    }
+
+    /// None while the size is unknown
+    pub fn progress_percentage(&self) -> Option<f64> {
+        if self.is_complete() {
+            return Some(100.0);
+        }
+        match self.total_bytes {
+            Some(0) => Some(0.0),
+            Some(total) => Some((self.downloaded_bytes as f64 / total as f64 * 100.0).min(100.0)),
+            None => None,
+        }
+    }
+
+    pub fn is_indeterminate(&self) -> bool {
+        self.progress_percentage().is_none()
+    }
+
+    pub fn update_eta(&mut self) {
+        self.eta_secs = self
+            .total_bytes
+            .filter(|_| self.bytes_per_second > 0)
+            .map(|total| total.saturating_sub(self.downloaded_bytes).div_ceil(self.bytes_per_second));
+    }
+
+    pub fn is_complete(&self) -> bool {
+        matches!(self.status, DownloadStatus::Completed)
+    }
+
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, DownloadStatus::InProgress | DownloadStatus::Pending)
+    }
+
+    /// Whether a partial file can be continued instead of starting over.
+    /// Weak ETags cannot be used with `If-Range`.
+    pub fn resume_validator(&self) -> Option<&str> {
+        if !self.accepts_ranges {
+            return None;
+        }
+        self.etag
+            .as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+
+    pub fn can_resume(&self) -> bool {
+        self.resume_validator().is_some() && self.downloaded_bytes > 0
+    }
+
+    /// Where bytes are written until the download completes. It does not
+    /// depend on the filename, which may change once the server responds.
+    pub fn part_path(&self) -> PathBuf {
+        self.file_path.with_file_name(format!("{}.part", self.id))
+    }
+}
+
+// A running worker and the limiter it draws from, so limits can change
+// mid-transfer
+struct ActiveTransfer {
+    handle: AbortHandle,
+    generation: u64,
+    rate_limiter: RateLimiter,
+}
+
+pub type SharedDownloadManager = Arc<Mutex<DownloadManager>>;
+
+// What setting an expected checksum still needs
+enum ChecksumCheck {
+    Done(IntegrityStatus),
+    /// The finished file has to be hashed first
+    Hash { path: PathBuf, algorithm: ChecksumAlgorithm },
+}
+
+/// Sets or clears the digest a download must match. A finished file is
+/// checked straight away, hashing it without holding the manager's lock.
+pub async fn set_download_checksum(
+    downloads: &SharedDownloadManager,
+    download_id: &str,
+    expected_checksum: Option<Checksum>,
+) -> Result<IntegrityStatus> {
+    let check = downloads.lock().unwrap().expect_checksum(download_id, expected_checksum)?;
+    let (path, algorithm) = match check {
+        ChecksumCheck::Done(integrity) => return Ok(integrity),
+        ChecksumCheck::Hash { path, algorithm } => (path, algorithm),
+    };
+    let checksum = integrity::hash_file(&path, algorithm).await?;
+    downloads.lock().unwrap().record_file_checksum(download_id, &path, checksum)
+}
+
+pub struct DownloadManager {
+    downloads: HashMap<String, DownloadItem>,
+    download_directory: PathBuf,
+    max_concurrent_downloads: usize,
+    max_downloads_per_host: Option<usize>,
+    active_downloads: usize,
+    // Pending download ids, oldest first
+    queue: VecDeque<String>,
+    client: reqwest::Client,
+    // Running transfer workers by download id
+    transfers: HashMap<String, ActiveTransfer>,
+    // Sequence number for the next download added
+    next_sequence: u64,
+    // Numbers each started transfer, so messages from stopped ones are ignored
+    next_generation: u64,
+    update_sender: mpsc::UnboundedSender<TransferMessage>,
+    update_receiver: mpsc::UnboundedReceiver<TransferMessage>,
+    history: Option<DownloadHistoryStore>,
+    retention: DownloadRetention,
+    segment_settings: SegmentSettings,
+    detect_checksum_sidecars: bool,
+    global_rate_limiter: RateLimiter,
+    schedule: Option<DownloadSchedule>,
+    schedule_open: bool,
+    retry_policy: RetryPolicy,
+    // Downloads waiting out a backoff delay, and when to queue them again
+    retries: HashMap<String, Instant>,
+    rates: HashMap<String, TransferRate>,
+    safety_policy: DownloadSafetyPolicy,
+}
+
+impl DownloadManager {
+    pub fn new(download_directory: PathBuf) -> Self {
+        let (update_sender, update_receiver) = mpsc::unbounded_channel();
+        Self {
+            downloads: HashMap::new(),
+            download_directory,
+            max_concurrent_downloads: 3,
+            max_downloads_per_host: None,
+            active_downloads: 0,
+            queue: VecDeque::new(),
+            client: reqwest::Client::new(),
+            transfers: HashMap::new(),
+            next_sequence: 0,
+            next_generation: 0,
+            update_sender,
+            update_receiver,
+            history: None,
+            retention: DownloadRetention::default(),
+            segment_settings: SegmentSettings::default(),
+            detect_checksum_sidecars: true,
+            global_rate_limiter: RateLimiter::default(),
+            schedule: None,
+            schedule_open: true,
+            retry_policy: RetryPolicy::default(),
+            retries: HashMap::new(),
+            rates: HashMap::new(),
+            safety_policy: DownloadSafetyPolicy::default(),
+        }
+    }
+
+    /// Loads downloads and settings saved by a previous run and keeps
+    /// `store` up to date from now on. Downloads that were interrupted come
+    /// back paused.
+    pub fn open_history(&mut self, store: DownloadHistoryStore) -> Result<()> {
+        let (downloads, settings) = store.load()?;
+        for download in downloads {
+            self.restore_download(download);
+        }
+        self.global_rate_limiter.set_limit(settings.global_rate_limit);
+        self.schedule = settings.schedule;
+        self.history = Some(store);
+        self.apply_retention();
+        Ok(())
+    }
+
+    pub fn retention(&self) -> &DownloadRetention {
+        &self.retention
+    }
+
+    pub fn set_retention(&mut self, retention: DownloadRetention) {
+        self.retention = retention;
+        self.apply_retention();
+    }
+
+    pub fn segment_settings(&self) -> &SegmentSettings {
+        &self.segment_settings
+    }
+
+    /// Applies to downloads started or resumed from now on
+    pub fn set_segment_settings(&mut self, settings: SegmentSettings) {
+        info!("Splitting downloads into up to {} segments", settings.max_segments);
+        self.segment_settings = settings;
+    }
+
+    pub fn detect_checksum_sidecars(&self) -> bool {
+        self.detect_checksum_sidecars
+    }
+
+    /// Whether downloads without an expected checksum look for a
+    /// `<url>.sha256` file to verify against
+    pub fn set_detect_checksum_sidecars(&mut self, enabled: bool) {
+        self.detect_checksum_sidecars = enabled;
+    }
+
+    pub fn safety_policy(&self) -> &DownloadSafetyPolicy {
+        &self.safety_policy
+    }
+
+    /// Applies to downloads that start or finish from now on
+    pub fn set_safety_policy(&mut self, policy: DownloadSafetyPolicy) {
+        info!("Download safety policy updated with {} rules", policy.rules.len());
+        self.safety_policy = policy;
+    }
+
+    /// Drops finished downloads the retention rules no longer keep, along
+    /// with any partial files they left behind. Returns the dropped ids.
+    pub fn apply_retention(&mut self) -> Vec<String> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let downloads: Vec<&DownloadItem> = self.downloads.values().collect();
+        let expired = self.retention.expired(&downloads, now);
+
+        for download_id in &expired {
+            if let Some(download) = self.downloads.remove(download_id) {
+                remove_partial_file(&download.part_path());
+            }
+        }
+        if !expired.is_empty() {
+            info!("Dropped {} old downloads from history", expired.len());
+        }
+        self.save_history();
+        expired
+    }
+
+    /// Writes the downloads list now; it is otherwise saved whenever a
+    /// download changes state
+    pub fn save_history(&self) {
+        let Some(store) = &self.history else {
+            return;
+        };
+        let mut downloads: Vec<&DownloadItem> = self.downloads.values().collect();
+        downloads.sort_by(|a, b| started_before(a, b));
+        if let Err(e) = store.save(&downloads, &self.settings()) {
+            error!("Failed to save download history: {}", e);
+        }
+    }
+
+    pub fn start_download(&mut self, url: String, filename: Option<String>) -> Result<String> {
+        self.start_download_with_checksum(url, filename, None)
+    }
+
+    /// Starts a download that is verified against `expected_checksum` once
+    /// it finishes
+    pub fn start_download_with_checksum(
+        &mut self,
+        url: String,
+        filename: Option<String>,
+        expected_checksum: Option<Checksum>,
+    ) -> Result<String> {
+        info!("Starting download: {}", url);
+
+        // Use the URL until the server suggests a better name
+        let custom_filename = filename.is_some();
+        let filename = match filename {
+            Some(filename) => filename::sanitize_filename(&filename),
+            None => filename::resolve_filename(&url, None, None),
+        };
+
+        // Create file path, never overwriting an existing file
+        let directory = self.download_directory.clone();
+        let file_path = self.unique_file_path(&directory, &filename, None);
+
+        // Create download item and queue it; it starts right away if a
+        // slot is free
+        let mut download = DownloadItem::new(url, filename, file_path);
+        download.custom_filename = custom_filename;
+        download.expected_checksum = expected_checksum;
+        download.sequence = self.next_sequence;
+        self.next_sequence += 1;
+        let download_id = download.id.clone();
+
+        // Some downloads can be refused from the URL alone
+        let verdict = self.safety_policy.classify(&download.url, &download.filename, None);
+        if verdict.action == SafetyAction::Block {
+            warn!("Blocked download {}: {:?}", download_id, verdict.reason);
+            download.status = DownloadStatus::Blocked;
+            download.danger_reason = verdict.reason;
+            download.end_time = Some(unix_millis() / 1000);
+            self.downloads.insert(download_id.clone(), download);
+            self.save_history();
+            return Ok(download_id);
+        }
+
+        self.downloads.insert(download_id.clone(), download);
+        self.queue.push_back(download_id.clone());
+        info!("Download {} queued", download_id);
+
+        self.start_queued_downloads();
+        self.save_history();
+        Ok(download_id)
+    }
+
+    pub fn pause_download(&mut self, download_id: &str) -> Result<()> {
+        if let Some(download) = self.downloads.get_mut(download_id) {
+            let was_running = matches!(download.status, DownloadStatus::InProgress);
+            if download.is_active() {
+                download.status = DownloadStatus::Paused;
+                self.dequeue(download_id);
+                if was_running {
+                    self.active_downloads = self.active_downloads.saturating_sub(1);
+                    self.stop_transfer(download_id);
+                }
+                info!("Download {} paused", download_id);
+                
+                // Start next queued download if any
+                self.start_queued_downloads();
+                self.save_history();
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts a paused download back in the queue.
+    pub fn resume_download(&mut self, download_id: &str) -> Result<()> {
+        if let Some(download) = self.downloads.get_mut(download_id) {
+            if matches!(download.status, DownloadStatus::Paused) {
+                download.status = DownloadStatus::Pending;
+                download.attempts = 0;
+                self.queue.push_back(download_id.to_string());
+                info!("Download {} queued for resume", download_id);
+
+                self.start_queued_downloads();
+                self.save_history();
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues a failed download again with a fresh set of attempts,
+    /// continuing from its partial file where possible
+    pub fn retry_download(&mut self, download_id: &str) -> Result<()> {
+        let download = self
+            .downloads
+            .get_mut(download_id)
+            .ok_or_else(|| anyhow::anyhow!("Download not found: {}", download_id))?;
+        if !matches!(download.status, DownloadStatus::Failed) {
+            anyhow::bail!("Only failed downloads can be retried");
+        }
+        download.status = DownloadStatus::Pending;
+        download.error_message = None;
+        download.end_time = None;
+        download.attempts = 0;
+        self.queue.push_back(download_id.to_string());
+        info!("Download {} queued for retry", download_id);
+
+        self.start_queued_downloads();
+        self.save_history();
+        Ok(())
+    }
+
+    pub fn cancel_download(&mut self, download_id: &str) -> Result<()> {
+        if let Some(download) = self.downloads.get_mut(download_id) {
+            // Queued downloads never took a slot
+            if matches!(download.status, DownloadStatus::InProgress) {
+                self.active_downloads = self.active_downloads.saturating_sub(1);
+            }
+            
+            download.status = DownloadStatus::Cancelled;
+            download.end_time = Some(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+            );
+            let part_path = download.part_path();
+
+            self.dequeue(download_id);
+            self.stop_transfer(download_id);
+            remove_partial_file(&part_path);
+            info!("Download {} cancelled", download_id);
+            
+            // Start next queued download if any
+            self.start_queued_downloads();
+            self.save_history();
+        }
+        Ok(())
+    }
+
+    /// Keeps a download the safety policy held back
+    pub fn confirm_download(&mut self, download_id: &str) -> Result<()> {
+        let download = self
+            .downloads
+            .get(download_id)
+            .ok_or_else(|| anyhow::anyhow!("Download not found: {}", download_id))?;
+        if download.status != DownloadStatus::AwaitingConfirmation {
+            anyhow::bail!("Download is not awaiting confirmation");
+        }
+        info!("Keeping dangerous download {}", download_id);
+        self.keep_download(download_id);
+        Ok(())
+    }
+
+    /// Deletes a download the safety policy held back or blocked, along
+    /// with its entry
+    pub fn discard_download(&mut self, download_id: &str) -> Result<()> {
+        let download = self
+            .downloads
+            .get(download_id)
+            .ok_or_else(|| anyhow::anyhow!("Download not found: {}", download_id))?;
+        if !matches!(
+            download.status,
+            DownloadStatus::AwaitingConfirmation | DownloadStatus::Blocked
+        ) {
+            anyhow::bail!("Only held back or blocked downloads can be discarded");
+        }
+        info!("Discarding dangerous download {}", download_id);
+        self.remove_download(download_id)
+    }
+
+    pub fn remove_download(&mut self, download_id: &str) -> Result<()> {
+        if let Some(download) = self.downloads.remove(download_id) {
+            self.dequeue(download_id);
+            self.stop_transfer(download_id);
+            if !download.is_complete() {
+                remove_partial_file(&download.part_path());
+            }
+            if matches!(download.status, DownloadStatus::InProgress) {
+                self.active_downloads = self.active_downloads.saturating_sub(1);
+                // Start next queued download if any
+                self.start_queued_downloads();
+            }
+            
+            info!("Download {} removed", download_id);
+            self.save_history();
+        }
+        Ok(())
+    }
+
+    /// Adopts a download from a previous run. Downloads that were running
+    /// are paused so they can be resumed from their partial file.
+    pub fn restore_download(&mut self, mut download: DownloadItem) {
+        if download.is_active() {
+            download.status = DownloadStatus::Paused;
+            download.next_retry_at = None;
+            download.bytes_per_second = 0;
+            download.eta_secs = None;
+            // Segmented part files have their full size from the start
+            download.downloaded_bytes = if download.segments.is_empty() {
+                std::fs::metadata(download.part_path())
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0)
+            } else {
+                segments::downloaded_bytes(&download.segments)
+            };
+        }
+        self.next_sequence = self.next_sequence.max(download.sequence + 1);
+        self.downloads.insert(download.id.clone(), download);
+    }
+
+    pub fn get_download(&self, download_id: &str) -> Option<&DownloadItem> {
+        self.downloads.get(download_id)
+    }
+
+    /// Every download, oldest first
+    pub fn get_all_downloads(&self) -> Vec<&DownloadItem> {
+        let mut downloads: Vec<&DownloadItem> = self.downloads.values().collect();
+        downloads.sort_by(|a, b| started_before(a, b));
+        downloads
+    }
+
+    /// Snapshot with the current speed and time left
+    pub fn download_progress(&self, download_id: &str) -> Option<DownloadProgress> {
+        self.downloads.get(download_id).map(DownloadProgress::new)
+    }
+
+    pub fn get_active_downloads(&self) -> Vec<&DownloadItem> {
+        self.downloads
+            .values()
+            .filter(|download| download.is_active())
+            .collect()
+    }
+
+    pub fn get_completed_downloads(&self) -> Vec<&DownloadItem> {
+        self.downloads
+            .values()
+            .filter(|download| download.is_complete())
+            .collect()
+    }
+
+    pub fn clear_completed(&mut self) {
+        self.downloads.retain(|_, download| !download.is_complete());
+        info!("Cleared completed downloads");
+        self.save_history();
+    }
+
+    pub fn set_download_directory(&mut self, path: PathBuf) -> Result<()> {
+        if !path.exists() {
+            std::fs::create_dir_all(&path)?;
+        }
+        
+        self.download_directory = path;
+        info!("Download directory updated to: {:?}", self.download_directory);
+        Ok(())
+    }
+
+    pub fn get_download_directory(&self) -> &PathBuf {
+        &self.download_directory
+    }
+
+    // Sets or clears the digest a download must match. A running transfer
+    // restarts from its partial file so the new digest covers every byte. A
+    // finished file without a digest of the right kind is left to the caller
+    // to hash, so the manager is not locked while reading it.
+    fn expect_checksum(&mut self, download_id: &str, expected_checksum: Option<Checksum>) -> Result<ChecksumCheck> {
+        let download = self
+            .downloads
+            .get_mut(download_id)
+            .ok_or_else(|| anyhow::anyhow!("Download not found: {}", download_id))?;
+        download.expected_checksum = expected_checksum;
+        download.integrity = IntegrityStatus::Unverified;
+
+        match download.status {
+            DownloadStatus::Completed => {
+                let needs_hash = match (&download.expected_checksum, &download.actual_checksum) {
+                    (Some(expected), Some(actual)) => expected.algorithm != actual.algorithm,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                };
+                if let Some(expected) = download.expected_checksum.as_ref().filter(|_| needs_hash) {
+                    return Ok(ChecksumCheck::Hash {
+                        path: download.file_path.clone(),
+                        algorithm: expected.algorithm,
+                    });
+                }
+                download.integrity =
+                    IntegrityStatus::check(download.expected_checksum.as_ref(), download.actual_checksum.as_ref());
+            }
+            DownloadStatus::InProgress => {
+                self.stop_transfer(download_id);
+                self.start_transfer(download_id);
+            }
+            _ => {}
+        }
+
+        let integrity = self.downloads[download_id].integrity;
+        info!("Download {} integrity: {:?}", download_id, integrity);
+        self.save_history();
+        Ok(ChecksumCheck::Done(integrity))
+    }
+
+    // Stores the digest of a finished file hashed by `set_download_checksum`,
+    // unless the file or the expected digest changed in the meantime
+    fn record_file_checksum(&mut self, download_id: &str, path: &Path, checksum: Checksum) -> Result<IntegrityStatus> {
+        let download = self
+            .downloads
+            .get_mut(download_id)
+            .ok_or_else(|| anyhow::anyhow!("Download not found: {}", download_id))?;
+        let still_wanted = download.status == DownloadStatus::Completed
+            && download.file_path == path
+            && download
+                .expected_checksum
+                .as_ref()
+                .is_some_and(|expected| expected.algorithm == checksum.algorithm);
+        if !still_wanted {
+            return Ok(download.integrity);
+        }
+
+        download.actual_checksum = Some(checksum);
+        download.integrity =
+            IntegrityStatus::check(download.expected_checksum.as_ref(), download.actual_checksum.as_ref());
+        let integrity = download.integrity;
+        info!("Download {} integrity: {:?}", download_id, integrity);
+        self.save_history();
+        Ok(integrity)
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        info!("Retrying failed downloads up to {} times", retry_policy.max_attempts);
+        self.retry_policy = retry_policy;
+    }
+
+    /// The rate limit and schedule, as saved with the history
+    pub fn settings(&self) -> DownloadSettings {
+        DownloadSettings {
+            global_rate_limit: self.global_rate_limit(),
+            schedule: self.schedule,
+        }
+    }
+
+    pub fn global_rate_limit(&self) -> Option<u64> {
+        self.global_rate_limiter.limit()
+    }
+
+    /// Caps the combined speed of every download, in bytes per second.
+    /// Running transfers pick up the change immediately.
+    pub fn set_global_rate_limit(&mut self, bytes_per_second: Option<u64>) {
+        self.global_rate_limiter.set_limit(bytes_per_second);
+        info!("Global download rate limit set to {:?}", self.global_rate_limiter.limit());
+        self.save_history();
+    }
+
+    pub fn set_download_rate_limit(&mut self, download_id: &str, bytes_per_second: Option<u64>) -> Result<()> {
+        let download = self
+            .downloads
+            .get_mut(download_id)
+            .ok_or_else(|| anyhow::anyhow!("Download not found: {}", download_id))?;
+        download.rate_limit = bytes_per_second.filter(|&limit| limit > 0);
+        if let Some(transfer) = self.transfers.get(download_id) {
+            transfer.rate_limiter.set_limit(download.rate_limit);
+        }
+        info!("Download {} rate limit set to {:?}", download_id, download.rate_limit);
+
+        self.save_history();
+        Ok(())
+    }
+
+    pub fn schedule(&self) -> Option<DownloadSchedule> {
+        self.schedule
+    }
+
+    /// Restricts queued downloads to a daily time window, or lifts the
+    /// restriction with `None`
+    pub fn set_schedule(&mut self, schedule: Option<DownloadSchedule>) -> Vec<String> {
+        self.schedule = schedule;
+        info!("Download schedule set to {:?}", schedule);
+        let changed = self.apply_schedule();
+        self.save_history();
+        changed
+    }
+
+    /// Holds or releases the queue depending on the schedule and the local
+    /// time. Meant to be called periodically; returns the ids of downloads
+    /// that were suspended or started.
+    pub fn apply_schedule(&mut self) -> Vec<String> {
+        let open = self.schedule.as_ref().map_or(true, DownloadSchedule::is_open_now);
+        self.set_schedule_open(open)
+    }
+
+    fn set_schedule_open(&mut self, open: bool) -> Vec<String> {
+        if open == self.schedule_open {
+            return Vec::new();
+        }
+        self.schedule_open = open;
+
+        if open {
+            info!("Download window opened, starting queued downloads");
+            let queued: Vec<String> = self.queue.iter().cloned().collect();
+            self.start_queued_downloads();
+            self.save_history();
+            return queued
+                .into_iter()
+                .filter(|id| !self.queue.contains(id))
+                .collect();
+        }
+
+        // Put running downloads back at the front of the queue, in the
+        // order they started, so they continue first when the window opens
+        let mut running: Vec<&DownloadItem> = self
+            .downloads
+            .values()
+            .filter(|download| matches!(download.status, DownloadStatus::InProgress))
+            .collect();
+        running.sort_by(|a, b| started_before(a, b));
+        let running: Vec<String> = running.into_iter().map(|download| download.id.clone()).collect();
+        for download_id in running.iter().rev() {
+            self.stop_transfer(download_id);
+            if let Some(download) = self.downloads.get_mut(download_id) {
+                download.status = DownloadStatus::Pending;
+            }
+            self.active_downloads = self.active_downloads.saturating_sub(1);
+            self.queue.push_front(download_id.clone());
+        }
+        info!("Download window closed, suspended {} downloads", running.len());
+        self.save_history();
+        running
+    }
+
+    pub fn max_concurrent_downloads(&self) -> usize {
+        self.max_concurrent_downloads
+    }
+
+    pub fn set_max_concurrent_downloads(&mut self, max_concurrent_downloads: usize) {
+        self.max_concurrent_downloads = max_concurrent_downloads.max(1);
+        info!("Running at most {} downloads at once", self.max_concurrent_downloads);
+        self.start_queued_downloads();
+    }
+
+    pub fn max_downloads_per_host(&self) -> Option<usize> {
+        self.max_downloads_per_host
+    }
+
+    /// Limits how many downloads from the same host run at once. `None`
+    /// only applies the global limit.
+    pub fn set_max_downloads_per_host(&mut self, max_downloads_per_host: Option<usize>) {
+        self.max_downloads_per_host = max_downloads_per_host.map(|limit| limit.max(1));
+        info!("Per-host download limit set to {:?}", self.max_downloads_per_host);
+        self.start_queued_downloads();
+    }
+
+    pub fn set_download_priority(&mut self, download_id: &str, priority: DownloadPriority) -> Result<()> {
+        let download = self
+            .downloads
+            .get_mut(download_id)
+            .ok_or_else(|| anyhow::anyhow!("Download not found: {}", download_id))?;
+        download.priority = priority;
+        info!("Download {} priority set to {:?}", download_id, priority);
+
+        self.start_queued_downloads();
+        self.save_history();
+        Ok(())
+    }
+
+    /// Makes a queued download the next one to start, raising its priority
+    /// to match the most urgent queued download if needed.
+    pub fn move_download_to_top(&mut self, download_id: &str) -> Result<()> {
+        let position = self
+            .queue
+            .iter()
+            .position(|id| id == download_id)
+            .ok_or_else(|| anyhow::anyhow!("Download is not queued: {}", download_id))?;
+        let highest_priority = self
+            .queue
+            .iter()
+            .filter_map(|id| self.downloads.get(id))
+            .map(|download| download.priority)
+            .max()
+            .unwrap_or_default();
+
+        self.queue.remove(position);
+        self.queue.push_front(download_id.to_string());
+        if let Some(download) = self.downloads.get_mut(download_id) {
+            download.priority = download.priority.max(highest_priority);
+        }
+        info!("Moved download {} to the top of the queue", download_id);
+
+        self.save_history();
+        Ok(())
+    }
+
+    /// Queued downloads in the order they will start: highest priority
+    /// first, then first come first served
+    pub fn get_queued_downloads(&self) -> Vec<&DownloadItem> {
+        let mut queued: Vec<&DownloadItem> = self.queue.iter().filter_map(|id| self.downloads.get(id)).collect();
+        queued.sort_by_key(|download| std::cmp::Reverse(download.priority));
+        queued
+    }
+
+    pub fn queue_state(&self) -> DownloadQueueState {
+        let mut running: Vec<&DownloadItem> = self
+            .downloads
+            .values()
+            .filter(|download| matches!(download.status, DownloadStatus::InProgress))
+            .collect();
+        running.sort_by(|a, b| started_before(a, b));
+
+        DownloadQueueState {
+            max_concurrent_downloads: self.max_concurrent_downloads,
+            max_downloads_per_host: self.max_downloads_per_host,
+            running: running.into_iter().map(|download| download.id.clone()).collect(),
+            queued: self
+                .get_queued_downloads()
+                .into_iter()
+                .map(|download| download.id.clone())
+                .collect(),
+            schedule: self.schedule,
+            schedule_open: self.schedule_open,
+            global_rate_limit: self.global_rate_limiter.limit(),
+        }
+    }
+
+    // Starts queued downloads while global and per-host slots are free.
+    // Downloads whose host is at its limit wait without blocking others.
+    fn start_queued_downloads(&mut self) {
+        if !self.schedule_open {
+            return;
+        }
+        while self.active_downloads < self.max_concurrent_downloads {
+            let next_download_id = self
+                .get_queued_downloads()
+                .into_iter()
+                .find(|download| self.host_has_free_slot(&download.url))
+                .map(|download| download.id.clone());
+            let Some(download_id) = next_download_id else {
+                break;
+            };
+
+            self.queue.retain(|id| *id != download_id);
+            if let Some(download) = self.downloads.get_mut(&download_id) {
+                download.status = DownloadStatus::InProgress;
+                self.active_downloads += 1;
+                info!("Started queued download: {}", download_id);
+                self.start_transfer(&download_id);
+            }
+        }
+    }
+
+    fn host_has_free_slot(&self, url: &str) -> bool {
+        let Some(limit) = self.max_downloads_per_host else {
+            return true;
+        };
+        let host = queue::host_of(url);
+        let running = self
+            .downloads
+            .values()
+            .filter(|download| matches!(download.status, DownloadStatus::InProgress))
+            .filter(|download| queue::host_of(&download.url) == host)
+            .count();
+        running < limit
+    }
+
+    fn start_transfer(&mut self, download_id: &str) {
+        let Some(download) = self.downloads.get(download_id) else {
+            return;
+        };
+        // Continue from whatever made it to disk, even across restarts
+        let part_path = download.part_path();
+        let if_range = download.resume_validator().map(str::to_string);
+        let resume_from = match if_range {
+            Some(_) if download.segments.is_empty() => {
+                std::fs::metadata(&part_path).map(|metadata| metadata.len()).unwrap_or(0)
+            }
+            _ => 0,
+        };
+        // Segment progress is only good while the part file it describes is
+        let segmented_length = download.segments.iter().map(|segment| segment.end).max();
+        let part_length = std::fs::metadata(&part_path).map(|metadata| metadata.len()).ok();
+        let segments = match if_range {
+            Some(_) if part_length.is_some() && part_length == segmented_length => download.segments.clone(),
+            _ => Vec::new(),
+        };
+        let rate_limiter = RateLimiter::new(download.rate_limit);
+
+        // Without an expected digest, hash for a sidecar checksum to match
+        let sidecar_url = match download.expected_checksum {
+            None if self.detect_checksum_sidecars => integrity::sidecar_url(&download.url),
+            _ => None,
+        };
+        let checksum_algorithm = match &download.expected_checksum {
+            Some(expected) => Some(expected.algorithm),
+            None => sidecar_url.as_ref().map(|_| ChecksumAlgorithm::Sha256),
+        };
+        self.next_generation += 1;
+        let generation = self.next_generation;
+        let request = TransferRequest {
+            download_id: download_id.to_string(),
+            generation,
+            url: download.url.clone(),
+            part_path,
+            resume_from,
+            if_range,
+            segment_settings: self.segment_settings.clone(),
+            segments,
+            checksum_algorithm,
+            sidecar_url,
+            throttle: Throttle {
+                global: self.global_rate_limiter.clone(),
+                download: rate_limiter.clone(),
+            },
+        };
+
+        match transfer::spawn_transfer(self.client.clone(), request, self.update_sender.clone()) {
+            Ok(handle) => {
+                let transfer = ActiveTransfer {
+                    handle,
+                    generation,
+                    rate_limiter,
+                };
+                self.transfers.insert(download_id.to_string(), transfer);
+            }
+            Err(e) => self.fail_download(download_id, e.to_string()),
+        }
+    }
+
+    fn stop_transfer(&mut self, download_id: &str) {
+        if let Some(transfer) = self.transfers.remove(download_id) {
+            transfer.handle.abort();
+        }
+        self.stop_rate(download_id);
+    }
+
+    // Copies speed, time left and elapsed time onto the download
+    fn update_rate(&mut self, download_id: &str, now: Instant) {
+        let (Some(rate), Some(download)) = (self.rates.get(download_id), self.downloads.get_mut(download_id)) else {
+            return;
+        };
+        download.bytes_per_second = rate.bytes_per_second(now).unwrap_or(0);
+        download.elapsed_ms = rate.elapsed(now).as_millis() as u64;
+        download.update_eta();
+    }
+
+    fn stop_rate(&mut self, download_id: &str) {
+        self.update_rate(download_id, Instant::now());
+        self.rates.remove(download_id);
+        if let Some(download) = self.downloads.get_mut(download_id) {
+            download.bytes_per_second = 0;
+            download.eta_secs = None;
+        }
+    }
+
+    // Switches to the filename the server suggested, unless the caller chose one
+    fn apply_server_filename(&mut self, download_id: &str, content_disposition: Option<&str>) {
+        let Some(download) = self.downloads.get(download_id) else {
+            return;
+        };
+        if download.custom_filename {
+            return;
+        }
+        let resolved = filename::resolve_filename(&download.url, content_disposition, download.mime_type.as_deref());
+        if resolved == download.filename {
+            return;
+        }
+
+        let directory = download.file_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let file_path = self.unique_file_path(&directory, &resolved, Some(download_id));
+        if let Some(download) = self.downloads.get_mut(download_id) {
+            info!("Download {} will be saved as {:?}", download_id, file_path);
+            download.filename = file_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or(resolved);
+            download.file_path = file_path;
+        }
+    }
+
+    // Renames the finished partial file to its final name, picking a new
+    // name if something else took the old one meanwhile
+    fn move_into_place(&mut self, download_id: &str) -> Result<()> {
+        let download = self
+            .downloads
+            .get(download_id)
+            .ok_or_else(|| anyhow::anyhow!("Download not found: {}", download_id))?;
+        let part_path = download.part_path();
+        let directory = download.file_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let filename = download.filename.clone();
+
+        let file_path = self.unique_file_path(&directory, &filename, Some(download_id));
+        std::fs::rename(&part_path, &file_path)
+            .map_err(|e| anyhow::anyhow!("Failed to move download into {:?}: {}", file_path, e))?;
+
+        if let Some(download) = self.downloads.get_mut(download_id) {
+            if let Some(name) = file_path.file_name() {
+                download.filename = name.to_string_lossy().to_string();
+            }
+            download.file_path = file_path;
+        }
+        Ok(())
+    }
+
+    // A free path for `filename` in `directory`, also avoiding paths that
+    // other unfinished downloads are going to use
+    fn unique_file_path(&self, directory: &Path, filename: &str, download_id: Option<&str>) -> PathBuf {
+        filename::unique_path(directory, filename, |path| {
+            self.downloads.values().any(|download| {
+                Some(download.id.as_str()) != download_id
+                    && matches!(
+                        download.status,
+                        DownloadStatus::Pending
+                            | DownloadStatus::InProgress
+                            | DownloadStatus::Paused
+                            | DownloadStatus::AwaitingConfirmation
+                    )
+                    && download.file_path == path
+            })
+        })
+    }
+
+    /// Applies progress reported by transfer workers since the last call.
+    /// Returns the ids of downloads that changed.
+    pub fn process_transfer_updates(&mut self) -> Vec<String> {
+        let mut changed: Vec<String> = Vec::new();
+        while let Ok(message) = self.update_receiver.try_recv() {
+            let download_id = message.download_id;
+
+            // Updates from a worker that was paused, cancelled or restarted
+            // meanwhile
+            let current = self
+                .transfers
+                .get(&download_id)
+                .is_some_and(|transfer| transfer.generation == message.generation);
+            let in_progress = self
+                .downloads
+                .get(&download_id)
+                .is_some_and(|download| matches!(download.status, DownloadStatus::InProgress));
+            if !current || !in_progress {
+                continue;
+            }
+
+            match message.update {
+                TransferUpdate::Started {
+                    total_bytes,
+                    mime_type,
+                    content_disposition,
+                    resumed_from,
+                    etag,
+                    last_modified,
+                    accepts_ranges,
+                } => {
+                    if let Some(download) = self.downloads.get_mut(&download_id) {
+                        download.total_bytes = total_bytes;
+                        download.downloaded_bytes = resumed_from;
+                        download.mime_type = mime_type;
+                        download.etag = etag;
+                        download.last_modified = last_modified;
+                        download.accepts_ranges = accepts_ranges;
+                        if resumed_from == 0 {
+                            download.segments.clear();
+                        }
+                    }
+                    let elapsed_before = self
+                        .downloads
+                        .get(&download_id)
+                        .map(|download| Duration::from_millis(download.elapsed_ms))
+                        .unwrap_or_default();
+                    self.rates.insert(
+                        download_id.clone(),
+                        TransferRate::new(resumed_from, Instant::now(), elapsed_before),
+                    );
+                    self.apply_server_filename(&download_id, content_disposition.as_deref());
+                    self.save_history();
+                }
+                TransferUpdate::Progress {
+                    downloaded_bytes,
+                    segments,
+                } => {
+                    self.update_download_progress(&download_id, downloaded_bytes, None);
+                    if let Some(rate) = self.rates.get_mut(&download_id) {
+                        rate.record(downloaded_bytes, Instant::now());
+                    }
+                    if let Some(download) = self.downloads.get_mut(&download_id) {
+                        if !segments.is_empty() {
+                            download.segments = segments;
+                        }
+                    }
+                }
+                TransferUpdate::Finished {
+                    checksum,
+                    sidecar_checksum,
+                } => {
+                    self.transfers.remove(&download_id);
+                    self.stop_rate(&download_id);
+                    self.record_checksum(&download_id, checksum, sidecar_checksum);
+                    self.finish_download(&download_id);
+                }
+                TransferUpdate::Failed { error, transient } => {
+                    self.transfers.remove(&download_id);
+                    self.stop_rate(&download_id);
+                    self.handle_transfer_failure(&download_id, error, transient);
+                }
+            }
+
+            if !changed.contains(&download_id) {
+                changed.push(download_id);
+            }
+        }
+
+        // Keep speeds current even while no bytes arrive
+        let now = Instant::now();
+        let running: Vec<String> = self.rates.keys().cloned().collect();
+        for download_id in running {
+            self.update_rate(&download_id, now);
+        }
+
+        for download_id in self.queue_due_retries() {
+            if !changed.contains(&download_id) {
+                changed.push(download_id);
+            }
+        }
+        changed
+    }
+
+    // Transient failures wait out a backoff delay and go back in the queue
+    // until the attempts run out; anything else fails for good
+    fn handle_transfer_failure(&mut self, download_id: &str, error: String, transient: bool) {
+        let Some(download) = self.downloads.get_mut(download_id) else {
+            return;
+        };
+        download.attempts += 1;
+        download.last_error = Some(error.clone());
+        if !transient || download.attempts >= self.retry_policy.max_attempts {
+            self.fail_download(download_id, error);
+            return;
+        }
+
+        let delay = self.retry_policy.delay_after(download.attempts);
+        download.status = DownloadStatus::Pending;
+        download.next_retry_at = Some(unix_millis() + delay.as_millis() as u64);
+        self.retries.insert(download_id.to_string(), Instant::now() + delay);
+        self.active_downloads = self.active_downloads.saturating_sub(1);
+        info!(
+            "Download {} failed ({}), retry {} in {:?}",
+            download_id, error, download.attempts, delay
+        );
+
+        self.start_queued_downloads();
+        self.save_history();
+    }
+
+    // Moves downloads whose backoff is over to the front of the queue
+    fn queue_due_retries(&mut self) -> Vec<String> {
+        let now = Instant::now();
+        let due: Vec<String> = self
+            .retries
+            .iter()
+            .filter(|(_, retry_at)| **retry_at <= now)
+            .map(|(download_id, _)| download_id.clone())
+            .collect();
+        if due.is_empty() {
+            return due;
+        }
+
+        for download_id in &due {
+            self.retries.remove(download_id);
+            if let Some(download) = self.downloads.get_mut(download_id) {
+                download.next_retry_at = None;
+                if matches!(download.status, DownloadStatus::Pending) {
+                    self.queue.push_front(download_id.clone());
+                }
+            }
+        }
+        self.start_queued_downloads();
+        self.save_history();
+        due
+    }
+
+    // Takes a download out of the queue and cancels any pending retry
+    fn dequeue(&mut self, download_id: &str) {
+        self.queue.retain(|id| id != download_id);
+        self.retries.remove(download_id);
+        if let Some(download) = self.downloads.get_mut(download_id) {
+            download.next_retry_at = None;
+        }
+    }
+
+    fn record_checksum(&mut self, download_id: &str, checksum: Option<Checksum>, sidecar_checksum: Option<Checksum>) {
+        let Some(download) = self.downloads.get_mut(download_id) else {
+            return;
+        };
+        if download.expected_checksum.is_none() {
+            download.expected_checksum = sidecar_checksum;
+        }
+        download.actual_checksum = checksum;
+        download.integrity = IntegrityStatus::check(download.expected_checksum.as_ref(), download.actual_checksum.as_ref());
+        if download.integrity == IntegrityStatus::Mismatch {
+            warn!(
+                "Download {} does not match its expected checksum {:?}",
+                download_id, download.expected_checksum
+            );
+        }
+    }
+
+    pub fn update_download_progress(&mut self, download_id: &str, downloaded_bytes: u64, total_bytes: Option<u64>) {
+        if let Some(download) = self.downloads.get_mut(download_id) {
+            download.downloaded_bytes = downloaded_bytes;
+            if let Some(total) = total_bytes {
+                download.total_bytes = Some(total);
+            }
+        }
+    }
+
+    // Checks the finished file against the safety policy now that its
+    // final name and type are known, then keeps, holds back or drops it
+    fn finish_download(&mut self, download_id: &str) {
+        let Some(download) = self.downloads.get(download_id) else {
+            return;
+        };
+        let verdict = self
+            .safety_policy
+            .classify(&download.url, &download.filename, download.mime_type.as_deref());
+        let status = match verdict.action {
+            SafetyAction::Allow => return self.keep_download(download_id),
+            SafetyAction::Confirm => DownloadStatus::AwaitingConfirmation,
+            SafetyAction::Block => DownloadStatus::Blocked,
+        };
+        self.quarantine_download(download_id, status, verdict.reason);
+    }
+
+    fn keep_download(&mut self, download_id: &str) {
+        match self.move_into_place(download_id) {
+            Ok(()) => self.complete_download(download_id),
+            Err(e) => self.fail_download(download_id, e.to_string()),
+        }
+    }
+
+    // Frees the download's slot without moving the file into place. A
+    // blocked download loses its partial file too.
+    fn quarantine_download(&mut self, download_id: &str, status: DownloadStatus, reason: Option<String>) {
+        let Some(download) = self.downloads.get_mut(download_id) else {
+            return;
+        };
+        warn!("Download {} is {:?}: {}", download_id, status, reason.as_deref().unwrap_or_default());
+        if status == DownloadStatus::Blocked {
+            remove_partial_file(&download.part_path());
+            download.segments.clear();
+        }
+        download.status = status;
+        download.danger_reason = reason;
+        download.end_time = Some(unix_millis() / 1000);
+
+        self.active_downloads = self.active_downloads.saturating_sub(1);
+        self.start_queued_downloads();
+        self.save_history();
+    }
+
+    pub fn complete_download(&mut self, download_id: &str) {
+        if let Some(download) = self.downloads.get_mut(download_id) {
+            // Confirmed downloads gave up their slot when they were held back
+            let was_running = matches!(download.status, DownloadStatus::InProgress);
+            download.status = DownloadStatus::Completed;
+            download.segments.clear();
+            download.end_time = Some(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+            );
+            safety::record_origin(&download.file_path, &download.url);
+
+            if was_running {
+                self.active_downloads = self.active_downloads.saturating_sub(1);
+            }
+            info!("Download {} completed", download_id);
+            
+            // Start next queued download if any
+            self.start_queued_downloads();
+            self.save_history();
+        }
+    }
+
+    pub fn fail_download(&mut self, download_id: &str, error_message: String) {
+        if let Some(download) = self.downloads.get_mut(download_id) {
+            let was_running = matches!(download.status, DownloadStatus::InProgress);
+            download.status = DownloadStatus::Failed;
+            download.error_message = Some(error_message);
+            download.end_time = Some(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+            );
+
+            if was_running {
+                self.active_downloads = self.active_downloads.saturating_sub(1);
+            }
+            error!("Download {} failed", download_id);
+            
+            // Start next queued download if any
+            self.start_queued_downloads();
+            self.save_history();
+        }
+    }
+}
+
+// Oldest first, in the order downloads were added when they started within
+// the same second
+fn started_before(a: &DownloadItem, b: &DownloadItem) -> std::cmp::Ordering {
+    (a.start_time, a.sequence, &a.id).cmp(&(b.start_time, b.sequence, &b.id))
+}
+
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_millis() as u64
+}
+
+fn remove_partial_file(part_path: &PathBuf) {
+    if let Err(e) = std::fs::remove_file(part_path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!("Failed to remove partial download {:?}: {}", part_path, e);
+        }
+    }
+}
+
+impl Default for DownloadManager {
+    fn default() -> Self {
+        let download_dir = dirs::download_dir()
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+        Self::new(download_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_server::{TestResponse, TestServer};
+    use super::*;
+    use std::time::Duration;
+
+    fn test_directory() -> PathBuf {
+        std::env::temp_dir().join(format!("vishwakarma-downloads-{}", Uuid::new_v4()))
+    }
+
+    // Requests for the download itself, leaving out checksum sidecar lookups
+    fn file_requests(server: &TestServer) -> Vec<super::test_server::TestRequest> {
+        server
+            .requests()
+            .into_iter()
+            .filter(|request| !request.path.ends_with(".sha256"))
+            .collect()
+    }
+
+    async fn wait_for(
+        manager: &mut DownloadManager,
+        download_id: &str,
+        condition: impl Fn(&DownloadItem) -> bool,
+    ) -> DownloadItem {
+        for _ in 0..500 {
+            manager.process_transfer_updates();
+            let download = manager.get_download(download_id).unwrap();
+            if condition(download) {
+                return download.clone();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Timed out waiting for download {}", download_id);
+    }
+
+    #[tokio::test]
+    async fn download_streams_body_to_file() {
+        let body = b"0123456789".repeat(10_000);
+        let server_body = body.clone();
+        let server = TestServer::start(move |_| {
+            TestResponse::ok(server_body.clone()).header("Content-Type", "application/octet-stream")
+        })
+        .await;
+        let mut manager = DownloadManager::new(test_directory());
+
+        let download_id = manager
+            .start_download(server.url("/file.bin"), Some("file.bin".to_string()))
+            .unwrap();
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+
+        assert_eq!(std::fs::read(&download.file_path).unwrap(), body);
+        assert!(!download.part_path().exists());
+        assert_eq!(download.downloaded_bytes, body.len() as u64);
+        assert_eq!(download.total_bytes, Some(body.len() as u64));
+        assert_eq!(download.mime_type.as_deref(), Some("application/octet-stream"));
+    }
+
+    #[tokio::test]
+    async fn http_error_fails_download() {
+        let server = TestServer::start(|_| TestResponse::status(404)).await;
+        let mut manager = DownloadManager::new(test_directory());
+
+        let download_id = manager
+            .start_download(server.url("/missing.bin"), Some("missing.bin".to_string()))
+            .unwrap();
+        let download = wait_for(&mut manager, &download_id, |download| {
+            matches!(download.status, DownloadStatus::Failed)
+        })
+        .await;
+
+        assert!(download.error_message.unwrap().contains("404"));
+        assert!(!download.file_path.exists());
+    }
+
+    #[tokio::test]
+    async fn cancelling_removes_partial_file() {
+        let server = TestServer::start(|_| {
+            TestResponse::ok(vec![7u8; 64 * 1024]).slow(Duration::from_millis(20))
+        })
+        .await;
+        let mut manager = DownloadManager::new(test_directory());
+
+        let download_id = manager
+            .start_download(server.url("/slow.bin"), Some("slow.bin".to_string()))
+            .unwrap();
+        let download = wait_for(&mut manager, &download_id, |download| download.downloaded_bytes > 0).await;
+        manager.cancel_download(&download_id).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        manager.process_transfer_updates();
+
+        let cancelled = manager.get_download(&download_id).unwrap();
+        assert!(matches!(cancelled.status, DownloadStatus::Cancelled));
+        assert!(!download.part_path().exists());
+        assert!(!download.file_path.exists());
+    }
+
+    // Serves `body` with an ETag, honouring `Range` while `If-Range` matches
+    fn ranged_response(body: &[u8], etag: &str, request: &super::test_server::TestRequest) -> TestResponse {
+        let range_start = request
+            .header("range")
+            .and_then(|range| range.strip_prefix("bytes="))
+            .and_then(|range| range.trim_end_matches('-').parse::<usize>().ok())
+            .filter(|_| request.header("if-range") == Some(etag));
+        match range_start {
+            Some(start) => TestResponse { status: 206, ..TestResponse::ok(body[start..].to_vec()) }
+                .header("Content-Range", &format!("bytes {}-{}/{}", start, body.len() - 1, body.len())),
+            None => TestResponse::ok(body.to_vec()),
+        }
+        .header("ETag", etag)
+        .header("Accept-Ranges", "bytes")
+    }
+
+    #[tokio::test]
+    async fn paused_download_resumes_from_partial_file() {
+        let body: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+        let server_body = body.clone();
+        let server = TestServer::start(move |request| {
+            let response = ranged_response(&server_body, "\"v1\"", request);
+            match request.header("range") {
+                Some(_) => response,
+                None => response.slow(Duration::from_millis(5)),
+            }
+        })
+        .await;
+        let mut manager = DownloadManager::new(test_directory());
+
+        let download_id = manager
+            .start_download(server.url("/big.bin"), Some("big.bin".to_string()))
+            .unwrap();
+        wait_for(&mut manager, &download_id, |download| download.downloaded_bytes >= 8 * 1024).await;
+        manager.pause_download(&download_id).unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        manager.process_transfer_updates();
+        let paused = manager.get_download(&download_id).unwrap().clone();
+        assert!(paused.can_resume());
+        let partial_length = std::fs::metadata(paused.part_path()).unwrap().len();
+        assert!(partial_length > 0);
+
+        manager.resume_download(&download_id).unwrap();
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+
+        assert_eq!(std::fs::read(&download.file_path).unwrap(), body);
+        let requests = file_requests(&server);
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].header("if-range"), Some("\"v1\""));
+        assert!(requests[1].header("range").unwrap().starts_with("bytes="));
+    }
+
+    #[tokio::test]
+    async fn messages_from_a_paused_transfer_are_ignored_after_resuming() {
+        let body = vec![7u8; 32 * 1024];
+        let server_body = body.clone();
+        let server = TestServer::start(move |request| {
+            ranged_response(&server_body, "\"v1\"", request).slow(Duration::from_millis(5))
+        })
+        .await;
+        let mut manager = DownloadManager::new(test_directory());
+
+        let download_id = manager
+            .start_download(server.url("/stale.bin"), Some("stale.bin".to_string()))
+            .unwrap();
+        wait_for(&mut manager, &download_id, |download| download.downloaded_bytes > 0).await;
+        let paused_generation = manager.transfers[&download_id].generation;
+        manager.pause_download(&download_id).unwrap();
+        manager.resume_download(&download_id).unwrap();
+        assert_ne!(manager.transfers[&download_id].generation, paused_generation);
+
+        // The old worker may still have this in flight when it is aborted
+        manager
+            .update_sender
+            .send(TransferMessage {
+                download_id: download_id.clone(),
+                generation: paused_generation,
+                update: TransferUpdate::Failed {
+                    error: "Connection reset".to_string(),
+                    transient: false,
+                },
+            })
+            .unwrap();
+        manager.process_transfer_updates();
+        assert_eq!(manager.get_download(&download_id).unwrap().status, DownloadStatus::InProgress);
+
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+        assert_eq!(std::fs::read(&download.file_path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn download_restarts_when_server_ignores_ranges() {
+        let body = b"abcdefghij".repeat(1000);
+        let server_body = body.clone();
+        let server = TestServer::start(move |_| TestResponse::ok(server_body.clone()).header("ETag", "\"v1\"")).await;
+        let directory = test_directory();
+        let mut download = DownloadItem::new(
+            server.url("/file.txt"),
+            "file.txt".to_string(),
+            directory.join("file.txt"),
+        );
+        download.status = DownloadStatus::InProgress;
+        download.etag = Some("\"v1\"".to_string());
+        download.accepts_ranges = true;
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(download.part_path(), b"stale bytes").unwrap();
+        let download_id = download.id.clone();
+
+        let mut manager = DownloadManager::new(directory);
+        manager.restore_download(download);
+        assert_eq!(manager.get_download(&download_id).unwrap().downloaded_bytes, 11);
+        manager.resume_download(&download_id).unwrap();
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+
+        assert_eq!(std::fs::read(&download.file_path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn restored_download_resumes_after_restart() {
+        let body = b"0123456789abcdef".repeat(1000);
+        let server_body = body.clone();
+        let server = TestServer::start(move |request| ranged_response(&server_body, "\"v2\"", request)).await;
+        let directory = test_directory();
+        let mut download = DownloadItem::new(
+            server.url("/file.bin"),
+            "file.bin".to_string(),
+            directory.join("file.bin"),
+        );
+        download.status = DownloadStatus::InProgress;
+        download.etag = Some("\"v2\"".to_string());
+        download.accepts_ranges = true;
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(download.part_path(), &body[..5000]).unwrap();
+        let download_id = download.id.clone();
+
+        let mut manager = DownloadManager::new(directory);
+        manager.restore_download(download);
+        manager.resume_download(&download_id).unwrap();
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+
+        assert_eq!(std::fs::read(&download.file_path).unwrap(), body);
+        assert_eq!(server.requests()[0].header("range"), Some("bytes=5000-"));
+    }
+
+    #[tokio::test]
+    async fn history_survives_restart() {
+        let server = TestServer::start(|_| TestResponse::ok(b"saved".to_vec())).await;
+        let directory = test_directory();
+        let mut manager = DownloadManager::new(directory.clone());
+        manager.open_history(DownloadHistoryStore::new(directory.clone())).unwrap();
+
+        let download_id = manager
+            .start_download(server.url("/saved.txt"), Some("saved.txt".to_string()))
+            .unwrap();
+        wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+        let failed_id = manager
+            .start_download(server.url("/other.txt"), Some("other.txt".to_string()))
+            .unwrap();
+        manager.fail_download(&failed_id, "Connection reset".to_string());
+        manager.set_global_rate_limit(Some(64 * 1024));
+        let schedule = DownloadSchedule::new(22 * 60, 6 * 60).unwrap();
+        manager.set_schedule(Some(schedule));
+
+        let mut restarted = DownloadManager::new(directory.clone());
+        restarted.open_history(DownloadHistoryStore::new(directory)).unwrap();
+
+        assert_eq!(restarted.global_rate_limit(), Some(64 * 1024));
+        assert_eq!(restarted.schedule(), Some(schedule));
+
+        assert!(restarted.get_download(&download_id).unwrap().is_complete());
+        let failed = restarted.get_download(&failed_id).unwrap();
+        assert!(matches!(failed.status, DownloadStatus::Failed));
+        assert_eq!(failed.error_message.as_deref(), Some("Connection reset"));
+    }
+
+    #[test]
+    fn downloads_started_together_keep_the_order_they_were_added_in() {
+        let directory = test_directory();
+        let mut manager = DownloadManager::new(directory.clone());
+        manager.open_history(DownloadHistoryStore::new(directory.clone())).unwrap();
+        manager.set_schedule_open(false);
+        let added: Vec<String> = (0..8)
+            .map(|i| manager.start_download(format!("https://example.com/{}", i), None).unwrap())
+            .collect();
+        for download in manager.downloads.values_mut() {
+            download.start_time = 1_000;
+        }
+        manager.save_history();
+
+        let ids = |manager: &DownloadManager| -> Vec<String> {
+            manager.get_all_downloads().into_iter().map(|download| download.id.clone()).collect()
+        };
+        assert_eq!(ids(&manager), added);
+
+        let mut restarted = DownloadManager::new(directory.clone());
+        restarted.open_history(DownloadHistoryStore::new(directory)).unwrap();
+        assert_eq!(ids(&restarted), added);
+        let later = restarted.start_download("https://example.com/later".to_string(), None).unwrap();
+        restarted.downloads.get_mut(&later).unwrap().start_time = 1_000;
+        assert_eq!(ids(&restarted).last(), Some(&later));
+    }
+
+    #[test]
+    fn interrupted_downloads_come_back_paused() {
+        let directory = test_directory();
+        let mut download = DownloadItem::new(
+            "https://example.com/big.iso".to_string(),
+            "big.iso".to_string(),
+            directory.join("big.iso"),
+        );
+        download.status = DownloadStatus::InProgress;
+        download.etag = Some("\"abc\"".to_string());
+        download.accepts_ranges = true;
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(download.part_path(), vec![0u8; 4096]).unwrap();
+        DownloadHistoryStore::new(directory.clone()).save(&[&download], &DownloadSettings::default()).unwrap();
+
+        let mut manager = DownloadManager::new(directory.clone());
+        manager.open_history(DownloadHistoryStore::new(directory)).unwrap();
+
+        let restored = manager.get_download(&download.id).unwrap();
+        assert!(matches!(restored.status, DownloadStatus::Paused));
+        assert_eq!(restored.downloaded_bytes, 4096);
+        assert!(restored.can_resume());
+    }
+
+    #[test]
+    fn retention_drops_old_finished_downloads_only() {
+        let directory = test_directory();
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut old_failed = DownloadItem::new(
+            "https://example.com/a".to_string(),
+            "a".to_string(),
+            directory.join("a"),
+        );
+        old_failed.status = DownloadStatus::Failed;
+        old_failed.end_time = Some(1);
+        std::fs::write(old_failed.part_path(), b"partial").unwrap();
+        let mut old_paused = DownloadItem::new(
+            "https://example.com/b".to_string(),
+            "b".to_string(),
+            directory.join("b"),
+        );
+        old_paused.status = DownloadStatus::Paused;
+        old_paused.start_time = 1;
+        let mut recent = DownloadItem::new(
+            "https://example.com/c".to_string(),
+            "c".to_string(),
+            directory.join("c"),
+        );
+        recent.status = DownloadStatus::Completed;
+        recent.end_time = Some(recent.start_time);
+
+        let mut manager = DownloadManager::new(directory);
+        for download in [&old_failed, &old_paused, &recent] {
+            manager.restore_download(download.clone());
+        }
+        let dropped = manager.apply_retention();
+
+        assert_eq!(dropped, vec![old_failed.id.clone()]);
+        assert!(!old_failed.part_path().exists());
+        assert!(manager.get_download(&old_paused.id).is_some());
+        assert!(manager.get_download(&recent.id).is_some());
+    }
+
+    #[tokio::test]
+    async fn server_filename_is_used_without_overwriting() {
+        let server = TestServer::start(|request| {
+            if request.path.starts_with("/export") {
+                TestResponse::ok(b"a,b".to_vec())
+                    .header("Content-Disposition", "attachment; filename*=UTF-8''quarterly%20report.csv")
+            } else {
+                TestResponse::ok(b"%PDF".to_vec()).header("Content-Type", "application/pdf")
+            }
+        })
+        .await;
+        let directory = test_directory();
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("quarterly report.csv"), b"keep me").unwrap();
+        let mut manager = DownloadManager::new(directory.clone());
+
+        let export_id = manager.start_download(server.url("/export?format=csv"), None).unwrap();
+        let generated_id = manager.start_download(server.url("/generated/"), None).unwrap();
+        let export = wait_for(&mut manager, &export_id, |download| download.is_complete()).await;
+        let generated = wait_for(&mut manager, &generated_id, |download| download.is_complete()).await;
+
+        assert_eq!(export.filename, "quarterly report (1).csv");
+        assert_eq!(std::fs::read(directory.join("quarterly report.csv")).unwrap(), b"keep me");
+        assert_eq!(std::fs::read(&export.file_path).unwrap(), b"a,b");
+        assert_eq!(generated.file_path, directory.join("generated.pdf"));
+    }
+
+    // Each download takes a few hundred milliseconds, so queued ones wait
+    async fn slow_server() -> TestServer {
+        TestServer::start(|_| TestResponse::ok(vec![0u8; 8 * 1024]).slow(Duration::from_millis(25))).await
+    }
+
+    #[tokio::test]
+    async fn running_downloads_are_listed_in_the_order_they_started() {
+        let server = slow_server().await;
+        let mut manager = DownloadManager::new(test_directory());
+        manager.set_max_concurrent_downloads(6);
+        let started: Vec<String> = (0..6)
+            .map(|i| manager.start_download(server.url(&format!("/{}", i)), None).unwrap())
+            .collect();
+        for download in manager.downloads.values_mut() {
+            download.start_time = 1_000;
+        }
+
+        assert_eq!(manager.queue_state().running, started);
+        for download_id in &started {
+            manager.cancel_download(download_id).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn queue_starts_downloads_by_priority_then_arrival() {
+        let server = slow_server().await;
+        let mut manager = DownloadManager::new(test_directory());
+        manager.set_max_concurrent_downloads(1);
+
+        let running = manager.start_download(server.url("/0"), None).unwrap();
+        let first = manager.start_download(server.url("/1"), None).unwrap();
+        let second = manager.start_download(server.url("/2"), None).unwrap();
+        let urgent = manager.start_download(server.url("/3"), None).unwrap();
+        manager.set_download_priority(&urgent, DownloadPriority::High).unwrap();
+        let state = manager.queue_state();
+        assert_eq!(state.running, vec![running.clone()]);
+        assert_eq!(state.queued, vec![urgent.clone(), first.clone(), second.clone()]);
+
+        manager.move_download_to_top(&second).unwrap();
+        assert_eq!(manager.get_download(&second).unwrap().priority, DownloadPriority::High);
+        assert_eq!(manager.queue_state().queued, vec![second.clone(), urgent.clone(), first.clone()]);
+
+        wait_for(&mut manager, &running, |download| download.is_complete()).await;
+        assert_eq!(manager.queue_state().running, vec![second.clone()]);
+        assert_eq!(manager.queue_state().queued, vec![urgent.clone(), first.clone()]);
+        wait_for(&mut manager, &first, |download| download.is_complete()).await;
+    }
+
+    #[tokio::test]
+    async fn per_host_limit_lets_other_hosts_through() {
+        let server = slow_server().await;
+        let mut manager = DownloadManager::new(test_directory());
+        manager.set_max_downloads_per_host(Some(1));
+
+        let first = manager.start_download(server.url("/a"), None).unwrap();
+        let same_host = manager.start_download(server.url("/b"), None).unwrap();
+        let other_host = manager
+            .start_download(server.url("/c").replace("127.0.0.1", "localhost"), None)
+            .unwrap();
+
+        let state = manager.queue_state();
+        let mut running = state.running.clone();
+        running.sort();
+        let mut expected = vec![first.clone(), other_host.clone()];
+        expected.sort();
+        assert_eq!(running, expected);
+        assert_eq!(state.queued, vec![same_host.clone()]);
+
+        wait_for(&mut manager, &same_host, |download| download.is_complete()).await;
+    }
+
+    // Serves `body` in whatever `bytes=start-end` range is asked for,
+    // pausing `delay` after each KiB
+    fn segmented_server_response(
+        body: &[u8],
+        request: &super::test_server::TestRequest,
+        delay: Duration,
+    ) -> TestResponse {
+        let range = request
+            .header("range")
+            .and_then(|range| range.strip_prefix("bytes="))
+            .and_then(|range| range.split_once('-'))
+            .and_then(|(start, end)| {
+                let start = start.parse::<usize>().ok()?;
+                let end = end.parse::<usize>().map(|end| end + 1).unwrap_or(body.len());
+                Some((start, end.min(body.len())))
+            });
+        match range {
+            Some((start, end)) => TestResponse { status: 206, ..TestResponse::ok(body[start..end].to_vec()) }
+                .header("Content-Range", &format!("bytes {}-{}/{}", start, end - 1, body.len())),
+            None => TestResponse::ok(body.to_vec()),
+        }
+        .header("ETag", "\"v1\"")
+        .header("Accept-Ranges", "bytes")
+        .slow(delay)
+    }
+
+    fn segmented_manager() -> DownloadManager {
+        let mut manager = DownloadManager::new(test_directory());
+        manager.set_segment_settings(SegmentSettings {
+            max_segments: 4,
+            min_segment_bytes: 16 * 1024,
+        });
+        manager
+    }
+
+    #[tokio::test]
+    async fn large_download_is_split_into_segments() {
+        let body: Vec<u8> = (0..256 * 1024).map(|i| (i % 253) as u8).collect();
+        let server_body = body.clone();
+        let server = TestServer::start(move |request| {
+            segmented_server_response(&server_body, request, Duration::from_millis(2))
+        })
+        .await;
+        let mut manager = segmented_manager();
+
+        let download_id = manager
+            .start_download(server.url("/model.bin"), Some("model.bin".to_string()))
+            .unwrap();
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+
+        assert_eq!(std::fs::read(&download.file_path).unwrap(), body);
+        assert_eq!(download.downloaded_bytes, body.len() as u64);
+        let requests = file_requests(&server);
+        assert!(requests.len() >= 4);
+        assert_eq!(requests[0].header("range"), Some("bytes=0-"));
+        assert!(requests[1..].iter().all(|request| request.header("if-range") == Some("\"v1\"")));
+    }
+
+    #[tokio::test]
+    async fn segmented_download_resumes_its_segments() {
+        let body: Vec<u8> = (0..256 * 1024).map(|i| (i % 241) as u8).collect();
+        let server_body = body.clone();
+        let server = TestServer::start(move |request| {
+            segmented_server_response(&server_body, request, Duration::from_millis(10))
+        })
+        .await;
+        let mut manager = segmented_manager();
+
+        let download_id = manager
+            .start_download(server.url("/model.bin"), Some("model.bin".to_string()))
+            .unwrap();
+        wait_for(&mut manager, &download_id, |download| {
+            !download.segments.is_empty() && download.downloaded_bytes > 0
+        })
+        .await;
+        manager.pause_download(&download_id).unwrap();
+        let paused = manager.get_download(&download_id).unwrap().clone();
+        assert!(!paused.segments.is_empty());
+        let requests_before_resume = file_requests(&server).len();
+
+        manager.resume_download(&download_id).unwrap();
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+
+        assert_eq!(std::fs::read(&download.file_path).unwrap(), body);
+        let resumed = &file_requests(&server)[requests_before_resume..];
+        assert!(resumed.iter().all(|request| request.header("range") != Some("bytes=0-")));
+    }
+
+    #[tokio::test]
+    async fn segmented_download_falls_back_to_one_stream_without_ranges() {
+        let body = b"no ranges here".repeat(10_000);
+        let server_body = body.clone();
+        let server = TestServer::start(move |_| TestResponse::ok(server_body.clone())).await;
+        let mut manager = segmented_manager();
+
+        let download_id = manager
+            .start_download(server.url("/plain.bin"), Some("plain.bin".to_string()))
+            .unwrap();
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+
+        assert_eq!(std::fs::read(&download.file_path).unwrap(), body);
+        assert!(download.segments.is_empty());
+        assert_eq!(file_requests(&server).len(), 1);
+    }
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[tokio::test]
+    async fn expected_checksum_is_verified_while_streaming() {
+        let server = TestServer::start(|_| TestResponse::ok(b"abc".to_vec())).await;
+        let mut manager = DownloadManager::new(test_directory());
+
+        let expected = Checksum::new(ChecksumAlgorithm::Sha256, ABC_SHA256).unwrap();
+        let download_id = manager
+            .start_download_with_checksum(server.url("/abc.txt"), None, Some(expected.clone()))
+            .unwrap();
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+        assert_eq!(download.integrity, IntegrityStatus::Verified);
+        assert_eq!(download.actual_checksum, Some(expected));
+        // A caller-supplied digest means there is no sidecar to look for
+        assert_eq!(server.requests().len(), 1);
+
+        let downloads = SharedDownloadManager::new(Mutex::new(manager));
+        let wrong = Checksum::new(ChecksumAlgorithm::Md5, &"0".repeat(32)).unwrap();
+        let integrity = set_download_checksum(&downloads, &download_id, Some(wrong)).await.unwrap();
+        assert_eq!(integrity, IntegrityStatus::Mismatch);
+        let md5 = Checksum::new(ChecksumAlgorithm::Md5, "900150983cd24fb0d6963f7d28e17f72").unwrap();
+        let integrity = set_download_checksum(&downloads, &download_id, Some(md5)).await.unwrap();
+        assert_eq!(integrity, IntegrityStatus::Verified);
+        let integrity = set_download_checksum(&downloads, &download_id, None).await.unwrap();
+        assert_eq!(integrity, IntegrityStatus::Unverified);
+    }
+
+    #[tokio::test]
+    async fn checksum_set_mid_transfer_covers_the_whole_file() {
+        let body = vec![3u8; 32 * 1024];
+        let server_body = body.clone();
+        let server = TestServer::start(move |request| {
+            ranged_response(&server_body, "\"v1\"", request).slow(Duration::from_millis(5))
+        })
+        .await;
+        let mut manager = DownloadManager::new(test_directory());
+        manager.set_detect_checksum_sidecars(false);
+
+        let download_id = manager.start_download(server.url("/late.bin"), None).unwrap();
+        wait_for(&mut manager, &download_id, |download| download.downloaded_bytes > 0).await;
+        let mut hasher = integrity::Hasher::new(ChecksumAlgorithm::Sha256);
+        hasher.update(&body);
+        let expected = hasher.finish();
+        let check = manager.expect_checksum(&download_id, Some(expected.clone())).unwrap();
+        assert!(matches!(check, ChecksumCheck::Done(IntegrityStatus::Unverified)));
+
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+        assert_eq!(download.actual_checksum, Some(expected));
+        assert_eq!(download.integrity, IntegrityStatus::Verified);
+    }
+
+    #[tokio::test]
+    async fn sidecar_checksum_is_found_next_to_the_url() {
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/abc.txt.sha256" => TestResponse::ok(format!("{}  abc.txt\n", ABC_SHA256).into_bytes()),
+            "/abc.txt" => TestResponse::ok(b"abc".to_vec()),
+            "/tampered.txt.sha256" => TestResponse::ok(format!("{}  tampered.txt\n", ABC_SHA256).into_bytes()),
+            "/tampered.txt" => TestResponse::ok(b"abd".to_vec()),
+            "/plain.txt" => TestResponse::ok(b"plain".to_vec()),
+            _ => TestResponse::status(404),
+        })
+        .await;
+        let mut manager = DownloadManager::new(test_directory());
+
+        let verified = manager.start_download(server.url("/abc.txt"), None).unwrap();
+        let tampered = manager.start_download(server.url("/tampered.txt"), None).unwrap();
+        let unchecked = manager.start_download(server.url("/plain.txt"), None).unwrap();
+
+        let download = wait_for(&mut manager, &verified, |download| download.is_complete()).await;
+        assert_eq!(download.integrity, IntegrityStatus::Verified);
+        assert_eq!(download.expected_checksum.unwrap().value, ABC_SHA256);
+        let download = wait_for(&mut manager, &tampered, |download| download.is_complete()).await;
+        assert_eq!(download.integrity, IntegrityStatus::Mismatch);
+        let download = wait_for(&mut manager, &unchecked, |download| download.is_complete()).await;
+        assert_eq!(download.integrity, IntegrityStatus::Unverified);
+        assert!(download.expected_checksum.is_none());
+    }
+
+    #[tokio::test]
+    async fn rate_limits_slow_transfers_down() {
+        let body = vec![3u8; 48 * 1024];
+        let server_body = body.clone();
+        let server = TestServer::start(move |_| TestResponse::ok(server_body.clone())).await;
+        let mut manager = DownloadManager::new(test_directory());
+        manager.set_global_rate_limit(Some(64 * 1024));
+
+        let started = std::time::Instant::now();
+        let download_id = manager
+            .start_download(server.url("/limited.bin"), Some("limited.bin".to_string()))
+            .unwrap();
+        manager.set_download_rate_limit(&download_id, Some(32 * 1024)).unwrap();
+        let running = wait_for(&mut manager, &download_id, |download| {
+            download.bytes_per_second > 0 || download.is_complete()
+        })
+        .await;
+        if !running.is_complete() {
+            assert!(running.bytes_per_second <= 48 * 1024);
+            assert!(running.eta_secs.is_some());
+        }
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+
+        // 48 KiB at 32 KiB/s, with the bucket starting empty
+        assert!(started.elapsed() >= Duration::from_millis(1200));
+        assert_eq!(std::fs::read(&download.file_path).unwrap(), body);
+        assert_eq!(download.rate_limit, Some(32 * 1024));
+        assert_eq!((download.bytes_per_second, download.eta_secs), (0, None));
+        assert!(download.elapsed_ms >= 1000);
+    }
+
+    #[tokio::test]
+    async fn schedule_suspends_and_restarts_downloads() {
+        let server = slow_server().await;
+        let mut manager = DownloadManager::new(test_directory());
+
+        let running = manager.start_download(server.url("/night.bin"), None).unwrap();
+        assert_eq!(manager.set_schedule_open(false), vec![running.clone()]);
+        let suspended = manager.get_download(&running).unwrap();
+        assert!(matches!(suspended.status, DownloadStatus::Pending));
+
+        let waiting = manager.start_download(server.url("/later.bin"), None).unwrap();
+        let state = manager.queue_state();
+        assert!(state.running.is_empty());
+        assert_eq!(state.queued, vec![running.clone(), waiting.clone()]);
+
+        let mut started = manager.set_schedule_open(true);
+        started.sort();
+        let mut expected = vec![running.clone(), waiting.clone()];
+        expected.sort();
+        assert_eq!(started, expected);
+        wait_for(&mut manager, &running, |download| download.is_complete()).await;
+        wait_for(&mut manager, &waiting, |download| download.is_complete()).await;
+    }
+
+    fn quick_retries() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay_ms: 20,
+            max_delay_ms: 100,
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_with_backoff() {
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let server_calls = calls.clone();
+        let server = TestServer::start(move |request| {
+            if request.path.ends_with(".sha256") {
+                return TestResponse::status(404);
+            }
+            match server_calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 | 1 => TestResponse::status(503),
+                _ => TestResponse::ok(b"finally".to_vec()),
+            }
+        })
+        .await;
+        let mut manager = DownloadManager::new(test_directory());
+        manager.set_retry_policy(quick_retries());
+
+        let download_id = manager
+            .start_download(server.url("/flaky.txt"), Some("flaky.txt".to_string()))
+            .unwrap();
+        let waiting = wait_for(&mut manager, &download_id, |download| download.attempts == 1).await;
+        assert!(matches!(waiting.status, DownloadStatus::Pending));
+        assert!(waiting.next_retry_at.is_some());
+        let download = wait_for(&mut manager, &download_id, |download| download.is_complete()).await;
+
+        assert_eq!(std::fs::read(&download.file_path).unwrap(), b"finally");
+        assert_eq!(download.attempts, 2);
+        assert!(download.last_error.unwrap().contains("503"));
+        assert!(download.next_retry_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn retries_stop_at_permanent_errors_and_max_attempts() {
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/gone.bin" => TestResponse::status(404),
+            _ => TestResponse::status(502),
+        })
+        .await;
+        let mut manager = DownloadManager::new(test_directory());
+        manager.set_retry_policy(quick_retries());
+
+        let gone = manager.start_download(server.url("/gone.bin"), None).unwrap();
+        let broken = manager.start_download(server.url("/broken.bin"), None).unwrap();
+
+        let download = wait_for(&mut manager, &gone, |download| {
+            matches!(download.status, DownloadStatus::Failed)
+        })
+        .await;
+        assert_eq!(download.attempts, 1);
+        let download = wait_for(&mut manager, &broken, |download| {
+            matches!(download.status, DownloadStatus::Failed)
+        })
+        .await;
+        assert_eq!(download.attempts, 3);
+        assert!(download.error_message.unwrap().contains("502"));
+
+        manager.retry_download(&broken).unwrap();
+        let retried = manager.get_download(&broken).unwrap();
+        assert_eq!(retried.attempts, 0);
+        assert!(retried.error_message.is_none());
+    }
+
+    #[tokio::test]
+    async fn dangerous_download_waits_for_confirmation() {
+        let server = TestServer::start(|_| TestResponse::ok(b"#!/bin/sh\n".to_vec())).await;
+        let mut manager = DownloadManager::new(test_directory());
+
+        let kept = manager.start_download(server.url("/install.sh"), None).unwrap();
+        let discarded = manager.start_download(server.url("/other.sh"), None).unwrap();
+        let download = wait_for(&mut manager, &kept, |download| {
+            download.status == DownloadStatus::AwaitingConfirmation
+        })
+        .await;
+        assert!(download.part_path().exists());
+        assert!(!download.file_path.exists());
+        assert!(download.danger_reason.unwrap().contains("sh files"));
+        assert!(manager.retry_download(&kept).is_err());
+
+        manager.confirm_download(&kept).unwrap();
+        let download = manager.get_download(&kept).unwrap();
+        assert!(download.is_complete());
+        assert_eq!(std::fs::read(&download.file_path).unwrap(), b"#!/bin/sh\n");
+
+        let download = wait_for(&mut manager, &discarded, |download| {
+            download.status == DownloadStatus::AwaitingConfirmation
+        })
+        .await;
+        manager.discard_download(&discarded).unwrap();
+        assert!(manager.get_download(&discarded).is_none());
+        assert!(!download.part_path().exists());
+        assert!(manager.queue_state().running.is_empty());
+    }
+
+    #[tokio::test]
+    async fn blocked_downloads_keep_nothing() {
+        let server = TestServer::start(|_| TestResponse::ok(b"MZ".to_vec())).await;
+        let mut manager = DownloadManager::new(test_directory());
+        let mut policy = DownloadSafetyPolicy::default();
+        policy
+            .rules
+            .push(SafetyRule::new(vec![SafetyCondition::Extension("exe".to_string())], SafetyAction::Block));
+        manager.set_safety_policy(policy);
+
+        let download_id = manager.start_download(server.url("/setup.exe"), None).unwrap();
+        let download = wait_for(&mut manager, &download_id, |download| {
+            download.status == DownloadStatus::Blocked
+        })
+        .await;
+        assert!(!download.part_path().exists());
+        assert!(!download.file_path.exists());
+
+        // Refused before any request is made
+        let requests = server.requests().len();
+        let data_url = manager
+            .start_download("data:application/x-msdownload;base64,TVo=".to_string(), None)
+            .unwrap();
+        assert_eq!(manager.get_download(&data_url).unwrap().status, DownloadStatus::Blocked);
+        assert_eq!(server.requests().len(), requests);
+        assert!(manager.confirm_download(&data_url).is_err());
+    }
+}
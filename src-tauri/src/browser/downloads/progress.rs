@@ -0,0 +1,193 @@
+// Download Progress Reporting
+use super::{DownloadItem, DownloadStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+// Speed is averaged over this much recent history
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+// Below this the speed is too noisy to show
+const MIN_RATE_SPAN: Duration = Duration::from_millis(500);
+
+/// Transfer speed over a sliding window of byte counts, and how long the
+/// download has been transferring across all its runs
+#[derive(Debug, Clone)]
+pub struct TransferRate {
+    samples: VecDeque<(Instant, u64)>,
+    started_at: Instant,
+    elapsed_before: Duration,
+}
+
+impl TransferRate {
+    /// `elapsed_before` is the transfer time of earlier runs
+    pub fn new(bytes: u64, now: Instant, elapsed_before: Duration) -> Self {
+        Self {
+            samples: VecDeque::from([(now, bytes)]),
+            started_at: now,
+            elapsed_before,
+        }
+    }
+
+    pub fn record(&mut self, bytes: u64, now: Instant) {
+        self.samples.push_back((now, bytes));
+        // Keep one sample at or before the window start as the baseline
+        let window_start = now.checked_sub(RATE_WINDOW).unwrap_or(self.started_at);
+        while self.samples.len() > 1 && self.samples[1].0 <= window_start {
+            self.samples.pop_front();
+        }
+    }
+
+    /// None until the window spans enough time to be meaningful. Measured
+    /// up to `now`, so a stalled transfer slows down instead of freezing.
+    pub fn bytes_per_second(&self, now: Instant) -> Option<u64> {
+        let (first_at, first_bytes) = *self.samples.front()?;
+        let (_, last_bytes) = *self.samples.back()?;
+        let span = now.saturating_duration_since(first_at);
+        if span < MIN_RATE_SPAN {
+            return None;
+        }
+        Some((last_bytes.saturating_sub(first_bytes) as f64 / span.as_secs_f64()) as u64)
+    }
+
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        self.elapsed_before + now.saturating_duration_since(self.started_at)
+    }
+}
+
+/// What the frontend is told about a download as it moves along
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DownloadProgress {
+    pub id: String,
+    pub status: DownloadStatus,
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+    /// None while the size is unknown, for an indeterminate progress bar
+    pub percentage: Option<f64>,
+    pub bytes_per_second: u64,
+    /// Seconds left at the current speed, if the size is known
+    pub eta_secs: Option<u64>,
+    pub elapsed_secs: u64,
+}
+
+impl DownloadProgress {
+    pub fn new(download: &DownloadItem) -> Self {
+        Self {
+            id: download.id.clone(),
+            status: download.status.clone(),
+            downloaded_bytes: download.downloaded_bytes,
+            total_bytes: download.total_bytes,
+            percentage: download.progress_percentage(),
+            bytes_per_second: download.bytes_per_second,
+            eta_secs: download.eta_secs,
+            elapsed_secs: download.elapsed_ms / 1000,
+        }
+    }
+}
+
+/// Decides which downloads are worth an event: status changes go out
+/// straight away, running downloads at most once per interval
+pub struct ProgressThrottle {
+    interval: Duration,
+    last_emitted: HashMap<String, (DownloadStatus, u64, Instant)>,
+}
+
+impl ProgressThrottle {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_emitted: HashMap::new(),
+        }
+    }
+
+    pub fn should_emit(&mut self, download: &DownloadItem, now: Instant) -> bool {
+        let emit = match self.last_emitted.get(&download.id) {
+            None => true,
+            Some((status, bytes, at)) => {
+                // A stalled download still reports its falling speed
+                let moving = *bytes != download.downloaded_bytes || download.status == DownloadStatus::InProgress;
+                *status != download.status || (moving && now.duration_since(*at) >= self.interval)
+            }
+        };
+        if emit {
+            self.last_emitted.insert(
+                download.id.clone(),
+                (download.status.clone(), download.downloaded_bytes, now),
+            );
+        }
+        emit
+    }
+
+    /// Forgets downloads that no longer exist
+    pub fn retain(&mut self, exists: impl Fn(&str) -> bool) {
+        self.last_emitted.retain(|id, _| exists(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn speed_covers_the_recent_window() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut rate = TransferRate::new(0, start, Duration::from_secs(10));
+        rate.record(100, at(100));
+        assert_eq!(rate.bytes_per_second(at(100)), None);
+
+        for second in 1..=4 {
+            rate.record(second * 1000, at(second * 1000));
+        }
+        assert_eq!(rate.bytes_per_second(at(4000)), Some(1000));
+
+        // The slow start drops out of the window
+        for second in 5..=10 {
+            rate.record(4000 + (second - 4) * 3000, at(second * 1000));
+        }
+        assert_eq!(rate.bytes_per_second(at(10_000)), Some(3000));
+
+        // Nothing arrives for a while
+        assert_eq!(rate.bytes_per_second(at(15_000)), Some(1500));
+        assert_eq!(rate.elapsed(at(15_000)), Duration::from_secs(25));
+    }
+
+    #[test]
+    fn unknown_sizes_are_indeterminate() {
+        let mut download = DownloadItem::new("https://example.com/a".into(), "a".into(), PathBuf::from("a"));
+        download.status = DownloadStatus::InProgress;
+        download.downloaded_bytes = 1000;
+        download.bytes_per_second = 1000;
+        download.update_eta();
+        assert!(download.is_indeterminate());
+        assert_eq!(DownloadProgress::new(&download).percentage, None);
+        assert_eq!(download.eta_secs, None);
+
+        download.total_bytes = Some(4000);
+        download.update_eta();
+        assert_eq!(download.progress_percentage(), Some(25.0));
+        assert_eq!(download.eta_secs, Some(3));
+
+        download.total_bytes = None;
+        download.status = DownloadStatus::Completed;
+        assert_eq!(download.progress_percentage(), Some(100.0));
+    }
+
+    #[test]
+    fn throttle_passes_status_changes_and_spaces_out_progress() {
+        let start = Instant::now();
+        let mut throttle = ProgressThrottle::new(Duration::from_millis(500));
+        let mut download = DownloadItem::new("https://example.com/a".into(), "a".into(), PathBuf::from("a"));
+        download.status = DownloadStatus::InProgress;
+
+        assert!(throttle.should_emit(&download, start));
+        download.downloaded_bytes = 10;
+        assert!(!throttle.should_emit(&download, start + Duration::from_millis(100)));
+        assert!(throttle.should_emit(&download, start + Duration::from_millis(600)));
+        assert!(throttle.should_emit(&download, start + Duration::from_millis(1200)));
+
+        download.status = DownloadStatus::Paused;
+        assert!(throttle.should_emit(&download, start + Duration::from_millis(1210)));
+        assert!(!throttle.should_emit(&download, start + Duration::from_millis(2000)));
+    }
+}
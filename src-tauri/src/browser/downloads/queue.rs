@@ -0,0 +1,32 @@
+// Download Queue
+use super::schedule::DownloadSchedule;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Snapshot of the queue for the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadQueueState {
+    pub max_concurrent_downloads: usize,
+    pub max_downloads_per_host: Option<usize>,
+    /// Downloads currently transferring
+    pub running: Vec<String>,
+    /// Waiting downloads in the order they will start
+    pub queued: Vec<String>,
+    pub schedule: Option<DownloadSchedule>,
+    /// False while the schedule holds the queue
+    pub schedule_open: bool,
+    pub global_rate_limit: Option<u64>,
+}
+
+/// The host a download counts against for per-host limits
+pub fn host_of(url: &str) -> Option<String> {
+    url::Url::parse(url).ok()?.host_str().map(str::to_ascii_lowercase)
+}
@@ -0,0 +1,103 @@
+// Download Retries
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::time::Duration;
+
+/// How failed transfers are retried. Only transient failures are retried.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first; 1 disables retries
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubles with every further attempt
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay_ms: 2_000,
+            max_delay_ms: 5 * 60 * 1000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the next try once `failed_attempts` have failed
+    pub fn delay_after(&self, failed_attempts: u32) -> Duration {
+        let exponent = failed_attempts.saturating_sub(1).min(32);
+        let delay = self.initial_delay_ms.saturating_mul(1u64 << exponent);
+        Duration::from_millis(delay.min(self.max_delay_ms))
+    }
+}
+
+/// A transfer that broke off part way and is worth trying again
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct Interrupted(pub &'static str);
+
+/// Whether a failure can go away on its own (dropped connections, server
+/// overload) or needs someone to act (missing file, full disk)
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    for cause in error.chain() {
+        if cause.is::<Interrupted>() {
+            return true;
+        }
+        if let Some(error) = cause.downcast_ref::<reqwest::Error>() {
+            if let Some(status) = error.status() {
+                return status.is_server_error()
+                    || status == reqwest::StatusCode::REQUEST_TIMEOUT
+                    || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+            }
+            if error.is_timeout() || error.is_connect() || error.is_body() || error.is_request() {
+                return true;
+            }
+        }
+        if let Some(error) = cause.downcast_ref::<std::io::Error>() {
+            return matches!(
+                error.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::ConnectionRefused
+                    | ErrorKind::NotConnected
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::TimedOut
+                    | ErrorKind::UnexpectedEof
+                    | ErrorKind::Interrupted
+            );
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_delay_ms: 1_000,
+            max_delay_ms: 5_000,
+        };
+        assert_eq!(policy.delay_after(1), Duration::from_secs(1));
+        assert_eq!(policy.delay_after(2), Duration::from_secs(2));
+        assert_eq!(policy.delay_after(3), Duration::from_secs(4));
+        assert_eq!(policy.delay_after(4), Duration::from_secs(5));
+        assert_eq!(policy.delay_after(60), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn failures_are_classified_by_cause() {
+        let reset = anyhow::Error::from(std::io::Error::from(ErrorKind::ConnectionReset));
+        assert!(is_transient(&reset.context("Transfer failed")));
+        assert!(is_transient(&Interrupted("Connection closed").into()));
+
+        let disk_full = anyhow::Error::from(std::io::Error::other("No space left on device"));
+        assert!(!is_transient(&disk_full));
+        assert!(!is_transient(&anyhow::anyhow!("Unexpected Content-Range in partial response")));
+    }
+}
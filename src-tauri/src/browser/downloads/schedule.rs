@@ -0,0 +1,70 @@
+// Download Scheduling
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// Daily local-time window in which queued downloads may run, e.g.
+/// 22:00 to 06:00. Outside it, running downloads go back to the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadSchedule {
+    /// Minutes after midnight
+    pub start_minute: u16,
+    /// Minutes after midnight; before `start_minute` for windows that
+    /// cross midnight
+    pub end_minute: u16,
+}
+
+impl DownloadSchedule {
+    pub fn new(start_minute: u16, end_minute: u16) -> anyhow::Result<Self> {
+        if start_minute >= MINUTES_PER_DAY || end_minute >= MINUTES_PER_DAY {
+            anyhow::bail!("Schedule times must be within a day");
+        }
+        Ok(Self {
+            start_minute,
+            end_minute,
+        })
+    }
+
+    pub fn is_open_at(&self, minute_of_day: u16) -> bool {
+        if self.start_minute == self.end_minute {
+            return true;
+        }
+        if self.start_minute < self.end_minute {
+            (self.start_minute..self.end_minute).contains(&minute_of_day)
+        } else {
+            minute_of_day >= self.start_minute || minute_of_day < self.end_minute
+        }
+    }
+
+    pub fn is_open_now(&self) -> bool {
+        let now = chrono::Local::now();
+        self.is_open_at((now.hour() * 60 + now.minute()) as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_may_cross_midnight() {
+        let night = DownloadSchedule::new(22 * 60, 6 * 60).unwrap();
+        assert!(night.is_open_at(23 * 60));
+        assert!(night.is_open_at(60));
+        assert!(!night.is_open_at(12 * 60));
+        assert!(!night.is_open_at(6 * 60));
+
+        let office = DownloadSchedule::new(9 * 60, 17 * 60).unwrap();
+        assert!(office.is_open_at(9 * 60));
+        assert!(!office.is_open_at(17 * 60));
+        assert!(DownloadSchedule::new(0, 0).unwrap().is_open_at(12 * 60));
+    }
+
+    #[test]
+    fn times_must_fall_within_a_day() {
+        assert!(DownloadSchedule::new(24 * 60, 0).is_err());
+        assert!(DownloadSchedule::new(0, 24 * 60).is_err());
+        assert!(DownloadSchedule::new(23 * 60 + 59, 0).is_ok());
+    }
+}
@@ -0,0 +1,312 @@
+// Segmented Downloads
+use super::retry::Interrupted;
+use super::throttle::Throttle;
+use super::transfer::{TransferMessage, TransferSender, TransferUpdate};
+use anyhow::{Context, Result};
+use log::info;
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::fs;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::task::JoinSet;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How a range-capable download may be split across connections
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SegmentSettings {
+    /// Connections per download; 1 keeps every download on a single stream
+    pub max_segments: usize,
+    /// Segments are never split below this size
+    pub min_segment_bytes: u64,
+}
+
+impl Default for SegmentSettings {
+    fn default() -> Self {
+        Self {
+            max_segments: 1,
+            min_segment_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl SegmentSettings {
+    pub fn enabled(&self) -> bool {
+        self.max_segments > 1
+    }
+}
+
+/// A byte range of the download and how much of it is on disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadSegment {
+    pub start: u64,
+    /// Exclusive
+    pub end: u64,
+    pub downloaded: u64,
+}
+
+impl DownloadSegment {
+    pub fn position(&self) -> u64 {
+        self.start + self.downloaded
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.end.saturating_sub(self.position())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
+/// Splits `total_bytes` into at most `count` contiguous segments no smaller
+/// than `min_segment_bytes`
+pub fn plan_segments(total_bytes: u64, count: usize, min_segment_bytes: u64) -> Vec<DownloadSegment> {
+    let count = (total_bytes / min_segment_bytes.max(1)).clamp(1, count.max(1) as u64);
+    let size = total_bytes.div_ceil(count);
+    (0..count)
+        .map(|index| DownloadSegment {
+            start: index * size,
+            end: ((index + 1) * size).min(total_bytes),
+            downloaded: 0,
+        })
+        .collect()
+}
+
+pub fn downloaded_bytes(segments: &[DownloadSegment]) -> u64 {
+    segments.iter().map(|segment| segment.downloaded).sum()
+}
+
+// Hands the back half of the busiest running segment to a new segment, so a
+// connection that frees up helps out a slow one. Returns the new index.
+fn split_slowest(segments: &mut Vec<DownloadSegment>, running: &[usize], min_segment_bytes: u64) -> Option<usize> {
+    let index = running
+        .iter()
+        .copied()
+        .filter(|&index| segments[index].remaining() >= 2 * min_segment_bytes)
+        .max_by_key(|&index| segments[index].remaining())?;
+
+    let segment = &mut segments[index];
+    let middle = segment.position() + segment.remaining() / 2;
+    let end = segment.end;
+    segment.end = middle;
+    segments.push(DownloadSegment {
+        start: middle,
+        end,
+        downloaded: 0,
+    });
+    Some(segments.len() - 1)
+}
+
+/// One segmented transfer. `first_response` already covers the first
+/// unfinished segment and is read instead of opening another connection.
+pub(super) struct SegmentedTransfer {
+    pub download_id: String,
+    pub generation: u64,
+    pub url: String,
+    pub part_path: PathBuf,
+    pub if_range: Option<String>,
+    pub settings: SegmentSettings,
+    pub segments: Vec<DownloadSegment>,
+    pub first_response: Option<reqwest::Response>,
+    pub throttle: Throttle,
+}
+
+// What each segment worker needs to fetch and store its range
+#[derive(Clone)]
+struct SegmentSource {
+    client: reqwest::Client,
+    url: String,
+    if_range: Option<String>,
+    part_path: PathBuf,
+    throttle: Throttle,
+}
+
+impl SegmentedTransfer {
+    /// Downloads every unfinished segment, at most `max_segments` at a time.
+    /// Segments are written in place, so the part file has the full size
+    /// from the start.
+    pub async fn run(mut self, client: &reqwest::Client, sender: &TransferSender) -> Result<()> {
+        let total_bytes = self.segments.iter().map(|segment| segment.end).max().unwrap_or(0);
+        {
+            let file = fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&self.part_path)
+                .await
+                .with_context(|| format!("Failed to open {:?}", self.part_path))?;
+            file.set_len(total_bytes).await?;
+        }
+
+        let segments = Arc::new(Mutex::new(std::mem::take(&mut self.segments)));
+        let mut pending: Vec<usize> = {
+            let segments = segments.lock().unwrap();
+            (0..segments.len()).filter(|&index| !segments[index].is_done()).collect()
+        };
+        info!(
+            "Downloading {} in {} segments for download {}",
+            self.url,
+            pending.len(),
+            self.download_id
+        );
+
+        let mut workers = JoinSet::new();
+        let mut running: Vec<usize> = Vec::new();
+        let mut first_response = self.first_response.take();
+        let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+        let source = SegmentSource {
+            client: client.clone(),
+            url: self.url.clone(),
+            if_range: self.if_range.clone(),
+            part_path: self.part_path.clone(),
+            throttle: self.throttle.clone(),
+        };
+
+        loop {
+            // Fill free connections with waiting segments, then by splitting
+            // whichever running segment has the most left
+            while running.len() < self.settings.max_segments {
+                let next = if !pending.is_empty() {
+                    Some(pending.remove(0))
+                } else {
+                    split_slowest(&mut segments.lock().unwrap(), &running, self.settings.min_segment_bytes)
+                };
+                let Some(index) = next else {
+                    break;
+                };
+                running.push(index);
+                workers.spawn(run_segment(source.clone(), segments.clone(), index, first_response.take()));
+            }
+
+            tokio::select! {
+                finished = workers.join_next() => match finished {
+                    Some(result) => {
+                        let index = result.context("Segment worker stopped")??;
+                        running.retain(|&running_index| running_index != index);
+                    }
+                    None => break,
+                },
+                _ = ticker.tick() => self.send_progress(sender, &segments),
+            }
+        }
+
+        self.send_progress(sender, &segments);
+        fs::OpenOptions::new().write(true).open(&self.part_path).await?.sync_all().await?;
+        Ok(())
+    }
+
+    fn send_progress(&self, sender: &TransferSender, segments: &Mutex<Vec<DownloadSegment>>) {
+        let segments = segments.lock().unwrap().clone();
+        let _ = sender.send(TransferMessage {
+            download_id: self.download_id.clone(),
+            generation: self.generation,
+            update: TransferUpdate::Progress {
+                downloaded_bytes: downloaded_bytes(&segments),
+                segments,
+            },
+        });
+    }
+}
+
+// Streams one segment into its place in the part file. The segment's end
+// can shrink while this runs if another connection takes over its tail.
+async fn run_segment(
+    source: SegmentSource,
+    segments: Arc<Mutex<Vec<DownloadSegment>>>,
+    index: usize,
+    response: Option<reqwest::Response>,
+) -> Result<usize> {
+    let (position, end) = {
+        let segments = segments.lock().unwrap();
+        (segments[index].position(), segments[index].end)
+    };
+
+    let mut response = match response {
+        Some(response) => response,
+        None => {
+            let mut get = source
+                .client
+                .get(&source.url)
+                .header(header::RANGE, format!("bytes={}-{}", position, end - 1));
+            if let Some(validator) = &source.if_range {
+                get = get.header(header::IF_RANGE, validator);
+            }
+            let response = get.send().await?.error_for_status()?;
+            let start = response
+                .headers()
+                .get(header::CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(super::transfer::parse_content_range)
+                .map(|(start, _)| start);
+            if response.status() != StatusCode::PARTIAL_CONTENT || start != Some(position) {
+                return Err(Interrupted("Server stopped honouring byte ranges").into());
+            }
+            response
+        }
+    };
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .open(&source.part_path)
+        .await
+        .with_context(|| format!("Failed to open {:?}", source.part_path))?;
+    file.seek(SeekFrom::Start(position)).await?;
+
+    while let Some(chunk) = response.chunk().await? {
+        let remaining = segments.lock().unwrap()[index].remaining() as usize;
+        let take = chunk.len().min(remaining);
+        source.throttle.acquire(take).await;
+        file.write_all(&chunk[..take]).await?;
+
+        let mut segments = segments.lock().unwrap();
+        let segment = &mut segments[index];
+        segment.downloaded = (segment.downloaded + take as u64).min(segment.end - segment.start);
+        if segment.is_done() {
+            break;
+        }
+    }
+    file.flush().await?;
+
+    if !segments.lock().unwrap()[index].is_done() {
+        return Err(Interrupted("Connection closed before the segment finished").into());
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_respects_minimum_segment_size() {
+        let segments = plan_segments(10 * 1024, 4, 1024);
+        assert_eq!(segments.len(), 4);
+        assert_eq!(segments[0].start, 0);
+        assert_eq!(segments[3].end, 10 * 1024);
+        assert!(segments.windows(2).all(|pair| pair[0].end == pair[1].start));
+
+        assert_eq!(plan_segments(3 * 1024, 8, 1024).len(), 3);
+        assert_eq!(plan_segments(100, 4, 1024).len(), 1);
+    }
+
+    #[test]
+    fn slowest_segment_gives_up_its_tail() {
+        let mut segments = plan_segments(8 * 1024, 2, 1024);
+        segments[0].downloaded = 4 * 1024;
+        segments[1].downloaded = 1024;
+
+        let new_index = split_slowest(&mut segments, &[1], 1024).unwrap();
+        assert_eq!(segments[1].end, 6656);
+        assert_eq!(segments[new_index].start, 6656);
+        assert_eq!(segments[new_index].end, 8 * 1024);
+
+        // Too little left to be worth another connection
+        assert_eq!(split_slowest(&mut segments, &[1, new_index], 1024), None);
+    }
+}
@@ -0,0 +1,85 @@
+// Download Rate Limiting
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Token bucket shared by every transfer it applies to. Consumers take
+/// bytes up front and sleep off any debt, so the average rate holds no
+/// matter how many connections draw from it. Limits can change while
+/// transfers are running.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    bytes_per_second: Option<u64>,
+    /// Negative while consumers are waiting
+    available: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_second: Option<u64>) -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                bytes_per_second: bytes_per_second.filter(|&limit| limit > 0),
+                available: 0.0,
+                updated_at: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        self.bucket.lock().unwrap().bytes_per_second
+    }
+
+    /// `None` or 0 removes the limit
+    pub fn set_limit(&self, bytes_per_second: Option<u64>) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.bytes_per_second = bytes_per_second.filter(|&limit| limit > 0);
+        bucket.available = 0.0;
+        bucket.updated_at = Instant::now();
+    }
+
+    /// Waits until `bytes` fit within the limit
+    pub async fn acquire(&self, bytes: usize) {
+        if let Some(delay) = self.reserve(bytes) {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    fn reserve(&self, bytes: usize) -> Option<Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let rate = bucket.bytes_per_second? as f64;
+
+        // Refill, allowing at most a second's worth of burst
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.available = (bucket.available + elapsed * rate).min(rate);
+        bucket.updated_at = now;
+
+        bucket.available -= bytes as f64;
+        (bucket.available < 0.0).then(|| Duration::from_secs_f64(-bucket.available / rate))
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// The limiters a transfer draws from: the manager-wide one and its own
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    pub global: RateLimiter,
+    pub download: RateLimiter,
+}
+
+impl Throttle {
+    pub async fn acquire(&self, bytes: usize) {
+        self.global.acquire(bytes).await;
+        self.download.acquire(bytes).await;
+    }
+}
@@ -0,0 +1,346 @@
+// Download Transfer Worker
+use super::filename::filename_from_url;
+use super::integrity::{self, Checksum, ChecksumAlgorithm, Hasher};
+use super::retry;
+use super::segments::{self, DownloadSegment, SegmentSettings, SegmentedTransfer};
+use super::throttle::Throttle;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use reqwest::{header, StatusCode};
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use tokio::fs;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+
+/// Everything a worker needs to move one download's bytes to disk
+#[derive(Debug, Clone)]
+pub struct TransferRequest {
+    pub download_id: String,
+    /// Tells this run's messages apart from those of earlier runs
+    pub generation: u64,
+    pub url: String,
+    /// Bytes are streamed here; the manager moves the file into place
+    pub part_path: PathBuf,
+    /// Length of the partial file to continue from, 0 to start over
+    pub resume_from: u64,
+    /// ETag or Last-Modified value the partial file was downloaded with.
+    /// Without one the transfer always starts over.
+    pub if_range: Option<String>,
+    pub segment_settings: SegmentSettings,
+    /// Progress of an earlier segmented transfer to pick up from
+    pub segments: Vec<DownloadSegment>,
+    /// Digest to compute over the finished file
+    pub checksum_algorithm: Option<ChecksumAlgorithm>,
+    /// Where a published checksum might be, checked once the file is done
+    pub sidecar_url: Option<String>,
+    pub throttle: Throttle,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransferUpdate {
+    /// Response headers arrived
+    Started {
+        total_bytes: Option<u64>,
+        mime_type: Option<String>,
+        content_disposition: Option<String>,
+        /// Bytes kept from the partial file, 0 if the transfer restarted
+        resumed_from: u64,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        accepts_ranges: bool,
+    },
+    /// `segments` is empty for single-stream transfers
+    Progress {
+        downloaded_bytes: u64,
+        segments: Vec<DownloadSegment>,
+    },
+    Finished {
+        checksum: Option<Checksum>,
+        sidecar_checksum: Option<Checksum>,
+    },
+    /// `transient` failures are worth retrying
+    Failed { error: String, transient: bool },
+}
+
+/// A worker update tagged with the download it belongs to
+#[derive(Debug, Clone)]
+pub struct TransferMessage {
+    pub download_id: String,
+    /// Generation of the request the worker was started with
+    pub generation: u64,
+    pub update: TransferUpdate,
+}
+
+pub type TransferSender = mpsc::UnboundedSender<TransferMessage>;
+
+/// Spawns a worker on the current tokio runtime. Aborting the returned
+/// handle stops the transfer and leaves the partial file in place.
+pub fn spawn_transfer(
+    client: reqwest::Client,
+    request: TransferRequest,
+    sender: TransferSender,
+) -> Result<AbortHandle> {
+    let runtime = tokio::runtime::Handle::try_current().context("Downloads need a tokio runtime")?;
+    let task = runtime.spawn(async move {
+        let download_id = request.download_id.clone();
+        let generation = request.generation;
+        let update = match run_transfer(&client, &request, &sender).await {
+            Ok(checksum) => {
+                let sidecar_checksum = match &request.sidecar_url {
+                    Some(sidecar_url) => {
+                        let filename = filename_from_url(&request.url);
+                        integrity::fetch_sidecar(&client, sidecar_url, filename.as_deref()).await
+                    }
+                    None => None,
+                };
+                TransferUpdate::Finished {
+                    checksum,
+                    sidecar_checksum,
+                }
+            }
+            Err(e) => {
+                warn!("Transfer for download {} failed: {:#}", download_id, e);
+                TransferUpdate::Failed {
+                    error: format!("{:#}", e),
+                    transient: retry::is_transient(&e),
+                }
+            }
+        };
+        let _ = sender.send(TransferMessage {
+            download_id,
+            generation,
+            update,
+        });
+    });
+    Ok(task.abort_handle())
+}
+
+// Returns the checksum of the finished file, if one was asked for
+async fn run_transfer(
+    client: &reqwest::Client,
+    request: &TransferRequest,
+    sender: &TransferSender,
+) -> Result<Option<Checksum>> {
+    info!("Transferring {} to {:?}", request.url, request.part_path);
+
+    let send = |update| {
+        let _ = sender.send(TransferMessage {
+            download_id: request.download_id.clone(),
+            generation: request.generation,
+            update,
+        });
+    };
+
+    if !request.segments.is_empty() && resume_segments(client, request, sender).await? {
+        return file_checksum(request).await;
+    }
+
+    // Ask only for the missing bytes, unless the file changed on the server.
+    // A fresh download asks for every byte as a range, to learn whether it
+    // can be split.
+    let mut resume_from = request.resume_from;
+    let mut get = client.get(&request.url);
+    match &request.if_range {
+        Some(validator) if resume_from > 0 && request.segments.is_empty() => {
+            info!("Resuming download {} from byte {}", request.download_id, resume_from);
+            get = get
+                .header(header::RANGE, format!("bytes={}-", resume_from))
+                .header(header::IF_RANGE, validator);
+        }
+        _ => {
+            resume_from = 0;
+            if request.segment_settings.enabled() {
+                get = get.header(header::RANGE, "bytes=0-");
+            }
+        }
+    }
+
+    let mut response = get.send().await?;
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        info!("Server rejected the range for download {}, restarting", request.download_id);
+        resume_from = 0;
+        response = client.get(&request.url).send().await?;
+    }
+    let mut response = response.error_for_status()?;
+
+    let headers = response.headers();
+    let header_value = |name| {
+        headers
+            .get(name)
+            .and_then(|value: &header::HeaderValue| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let partial = response.status() == StatusCode::PARTIAL_CONTENT;
+    let content_range = header_value(header::CONTENT_RANGE).and_then(|value| parse_content_range(&value));
+    let total_bytes = if partial {
+        match content_range {
+            Some((start, total)) if start == resume_from => total,
+            _ => anyhow::bail!("Unexpected Content-Range in partial response"),
+        }
+    } else {
+        if resume_from > 0 {
+            info!("Server sent the whole file for download {}, restarting", request.download_id);
+            resume_from = 0;
+        }
+        response.content_length()
+    };
+    let etag = header_value(header::ETAG);
+    let last_modified = header_value(header::LAST_MODIFIED);
+    send(TransferUpdate::Started {
+        total_bytes,
+        mime_type: header_value(header::CONTENT_TYPE),
+        content_disposition: header_value(header::CONTENT_DISPOSITION),
+        resumed_from: resume_from,
+        etag: etag.clone(),
+        last_modified: last_modified.clone(),
+        accepts_ranges: partial || header_value(header::ACCEPT_RANGES).is_some_and(|value| value.contains("bytes")),
+    });
+
+    if let Some(parent) = request.part_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let settings = &request.segment_settings;
+    if let Some(total_bytes) = total_bytes.filter(|_| partial && resume_from == 0 && settings.enabled()) {
+        let segments = segments::plan_segments(total_bytes, settings.max_segments, settings.min_segment_bytes);
+        if segments.len() > 1 {
+            let transfer = SegmentedTransfer {
+                download_id: request.download_id.clone(),
+                generation: request.generation,
+                url: request.url.clone(),
+                part_path: request.part_path.clone(),
+                // Later ranges must come from the same version of the file
+                if_range: etag.filter(|etag| !etag.starts_with("W/")).or(last_modified),
+                settings: settings.clone(),
+                segments,
+                first_response: Some(response),
+                throttle: request.throttle.clone(),
+            };
+            transfer.run(client, sender).await?;
+            return file_checksum(request).await;
+        }
+    }
+    let mut file = if resume_from > 0 {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .open(&request.part_path)
+            .await
+            .with_context(|| format!("Failed to open {:?}", request.part_path))?;
+        // Drop anything written past the point the server resumes from
+        file.set_len(resume_from).await?;
+        file.seek(SeekFrom::Start(resume_from)).await?;
+        file
+    } else {
+        fs::File::create(&request.part_path)
+            .await
+            .with_context(|| format!("Failed to create {:?}", request.part_path))?
+    };
+
+    // Hash as the bytes arrive when this run sees the whole file
+    let mut hasher = request
+        .checksum_algorithm
+        .filter(|_| resume_from == 0)
+        .map(Hasher::new);
+    let mut downloaded_bytes = resume_from;
+    while let Some(chunk) = response.chunk().await? {
+        request.throttle.acquire(chunk.len()).await;
+        file.write_all(&chunk).await?;
+        if let Some(hasher) = &mut hasher {
+            hasher.update(&chunk);
+        }
+        downloaded_bytes += chunk.len() as u64;
+        send(TransferUpdate::Progress {
+            downloaded_bytes,
+            segments: Vec::new(),
+        });
+    }
+    file.sync_all().await?;
+
+    match hasher {
+        Some(hasher) => Ok(Some(hasher.finish())),
+        None => file_checksum(request).await,
+    }
+}
+
+async fn file_checksum(request: &TransferRequest) -> Result<Option<Checksum>> {
+    match request.checksum_algorithm {
+        Some(algorithm) => Ok(Some(integrity::hash_file(&request.part_path, algorithm).await?)),
+        None => Ok(None),
+    }
+}
+
+// Continues an interrupted segmented transfer. Returns false when the file
+// changed on the server or ranges stopped working, so the caller starts over.
+async fn resume_segments(client: &reqwest::Client, request: &TransferRequest, sender: &TransferSender) -> Result<bool> {
+    let Some(validator) = &request.if_range else {
+        return Ok(false);
+    };
+    let Some(first) = request.segments.iter().find(|segment| !segment.is_done()) else {
+        return Ok(false);
+    };
+    info!(
+        "Resuming {} segments of download {}",
+        request.segments.iter().filter(|segment| !segment.is_done()).count(),
+        request.download_id
+    );
+
+    let response = client
+        .get(&request.url)
+        .header(header::RANGE, format!("bytes={}-{}", first.position(), first.end - 1))
+        .header(header::IF_RANGE, validator)
+        .send()
+        .await?;
+    let headers = response.headers();
+    let header_value = |name| {
+        headers
+            .get(name)
+            .and_then(|value: &header::HeaderValue| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let content_range = header_value(header::CONTENT_RANGE).and_then(|value| parse_content_range(&value));
+    let total_bytes = request.segments.iter().map(|segment| segment.end).max();
+    let matches = response.status() == StatusCode::PARTIAL_CONTENT
+        && content_range.is_some_and(|(start, total)| start == first.position() && total == total_bytes);
+    if !matches {
+        info!("Segments of download {} are stale, restarting", request.download_id);
+        return Ok(false);
+    }
+
+    let _ = sender.send(TransferMessage {
+        download_id: request.download_id.clone(),
+        generation: request.generation,
+        update: TransferUpdate::Started {
+            total_bytes,
+            mime_type: header_value(header::CONTENT_TYPE),
+            content_disposition: header_value(header::CONTENT_DISPOSITION),
+            resumed_from: segments::downloaded_bytes(&request.segments),
+            etag: header_value(header::ETAG),
+            last_modified: header_value(header::LAST_MODIFIED),
+            accepts_ranges: true,
+        },
+    });
+
+    let transfer = SegmentedTransfer {
+        download_id: request.download_id.clone(),
+        generation: request.generation,
+        url: request.url.clone(),
+        part_path: request.part_path.clone(),
+        if_range: Some(validator.clone()),
+        settings: request.segment_settings.clone(),
+        segments: request.segments.clone(),
+        first_response: Some(response),
+        throttle: request.throttle.clone(),
+    };
+    transfer.run(client, sender).await?;
+    Ok(true)
+}
+
+// Parses `bytes <start>-<end>/<total>` into the start offset and the total
+// size, if known
+pub(super) fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
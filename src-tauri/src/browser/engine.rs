@@ -1,166 +0,0 @@
-// Servo Engine Integration
-use anyhow::{Result, Context};
-use log::{info, error, warn};
-use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
-use uuid::Uuid;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EngineConfig {
-    pub width: u32,
-    pub height: u32,
-    pub user_agent: String,
-    pub enable_javascript: bool,
-    pub enable_webgl: bool,
-    pub enable_media: bool,
-}
-
-impl Default for EngineConfig {
-    fn default() -> Self {
-        Self {
-            width: 1200,
-            height: 800,
-            user_agent: "Vishwakarma Express/0.1.0 (Servo)".to_string(),
-            enable_javascript: true,
-            enable_webgl: true,
-            enable_media: true,
-        }
-    }
-}
-
-#[derive(Debug, thiserror::Error)]
-pub enum EngineError {
-    #[error("Engine initialization failed: {0}")]
-    InitializationFailed(String),
-    #[error("Navigation failed to {url}: {reason}")]
-    NavigationFailed { url: String, reason: String },
-    #[error("Rendering error: {0}")]
-    RenderingError(String),
-    #[error("JavaScript error: {0}")]
-    JavaScriptError(String),
-}
-
-pub struct ServoEngine {
-    id: String,
-    config: EngineConfig,
-    current_url: Option<String>,
-    is_loading: bool,
-    // TODO: Add actual Servo instance once dependencies are ready
-    // servo: Option<servo::Servo<ServoCallbacks>>,
-}
-
-impl ServoEngine {
-    pub fn new(config: EngineConfig) -> Self {
-        let id = Uuid::new_v4().to_string();
-        info!("Creating new Servo engine instance: {}", id);
-        
-        Self {
-            id,
-            config,
-            current_url: None,
-            is_loading: false,
-            // servo: None,
-        }
-    }
-
-    pub fn initialize(&mut self) -> Result<()> {
-        info!("Initializing Servo engine {}", self.id);
-        
-        // TODO: Initialize actual Servo engine
-        // For now, we'll create a placeholder implementation
-        
-        info!("Servo engine {} initialized successfully", self.id);
-        Ok(())
-    }
-
-    pub fn navigate_to(&mut self, url: &str) -> Result<()> {
-        info!("Navigating to: {}", url);
-        
-        // Validate URL
-        if url.is_empty() {
-            return Err(EngineError::NavigationFailed {
-                url: url.to_string(),
-                reason: "Empty URL".to_string(),
-            }.into());
-        }
-
-        // TODO: Implement actual navigation with Servo
-        self.is_loading = true;
-        self.current_url = Some(url.to_string());
-        
-        // Simulate navigation completion for now
-        self.is_loading = false;
-        
-        info!("Navigation to {} completed", url);
-        Ok(())
-    }
-
-    pub fn reload(&mut self) -> Result<()> {
-        if let Some(ref url) = self.current_url.clone() {
-            self.navigate_to(url)
-        } else {
-            warn!("Cannot reload: no current URL");
-            Ok(())
-        }
-    }
-
-    pub fn go_back(&mut self) -> Result<bool> {
-        // TODO: Implement history navigation
-        warn!("History navigation not yet implemented");
-        Ok(false)
-    }
-
-    pub fn go_forward(&mut self) -> Result<bool> {
-        // TODO: Implement history navigation
-        warn!("History navigation not yet implemented");
-        Ok(false)
-    }
-
-    pub fn stop_loading(&mut self) {
-        if self.is_loading {
-            info!("Stopping page load for engine {}", self.id);
-            self.is_loading = false;
-            // TODO: Stop actual Servo loading
-        }
-    }
-
-    pub fn set_viewport_size(&mut self, width: u32, height: u32) {
-        info!("Setting viewport size to {}x{}", width, height);
-        self.config.width = width;
-        self.config.height = height;
-        // TODO: Update Servo viewport
-    }
-
-    // Getters
-    pub fn id(&self) -> &str {
-        &self.id
-    }
-
-    pub fn current_url(&self) -> Option<&str> {
-        self.current_url.as_deref()
-    }
-
-    pub fn is_loading(&self) -> bool {
-        self.is_loading
-    }
-
-    pub fn can_go_back(&self) -> bool {
-        // TODO: Check actual history
-        false
-    }
-
-    pub fn can_go_forward(&self) -> bool {
-        // TODO: Check actual history
-        false
-    }
-}
-
-// Thread-safe wrapper for the engine
-pub type SharedEngine = Arc<Mutex<ServoEngine>>;
-
-pub fn create_engine(config: Option<EngineConfig>) -> Result<SharedEngine> {
-    let config = config.unwrap_or_default();
-    let mut engine = ServoEngine::new(config);
-    engine.initialize().context("Failed to initialize Servo engine")?;
-    Ok(Arc::new(Mutex::new(engine)))
-}
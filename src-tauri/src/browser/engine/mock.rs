@@ -0,0 +1,278 @@
+// In-memory rendering backend used for headless runs and tests
+use super::{EngineConfig, EngineError, EngineEvent, PageState, RenderingEngine};
+use anyhow::Result;
+use log::{info, warn};
+use uuid::Uuid;
+
+/// Deterministic backend that starts a load when asked and finishes it on
+/// the next `take_events` poll, the way a real engine reports back later.
+/// No network or rendering happens.
+///
+/// Hosts under the reserved `.invalid` TLD always fail to load, which lets
+/// callers exercise the error path.
+pub struct MockEngine {
+    id: String,
+    config: EngineConfig,
+    current_url: Option<String>,
+    is_loading: bool,
+    // Started but not yet committed or failed
+    pending_load: Option<String>,
+    page_state: PageState,
+    events: Vec<EngineEvent>,
+}
+
+impl MockEngine {
+    pub fn new(config: EngineConfig) -> Self {
+        let id = Uuid::new_v4().to_string();
+        info!("Creating new mock engine instance: {}", id);
+
+        Self {
+            id,
+            config,
+            current_url: None,
+            is_loading: false,
+            pending_load: None,
+            page_state: PageState::default(),
+            events: Vec::new(),
+        }
+    }
+
+    fn load(&mut self, url: &str) {
+        self.abort_load("Superseded by another navigation");
+        self.is_loading = true;
+        self.pending_load = Some(url.to_string());
+        self.page_state = PageState {
+            zoom_level: Some(1.0),
+            scroll_position: Some((0.0, 0.0)),
+        };
+        self.events.push(EngineEvent::LoadStarted { url: url.to_string() });
+        self.events.push(EngineEvent::Progress { progress: 0.1 });
+    }
+
+    fn finish_load(&mut self) {
+        let Some(url) = self.pending_load.take() else {
+            return;
+        };
+        self.is_loading = false;
+
+        let host = host_of(&url);
+        if host.is_some_and(|host| host.ends_with(".invalid")) {
+            self.events.push(EngineEvent::LoadFailed {
+                url: url.clone(),
+                error: EngineError::NavigationFailed {
+                    url,
+                    reason: "Name not resolved".to_string(),
+                },
+            });
+            return;
+        }
+
+        self.events.push(EngineEvent::Committed { url: url.clone() });
+        self.events.push(EngineEvent::TitleChanged {
+            title: host.unwrap_or(&url).to_string(),
+        });
+        if let Some(origin) = origin_of(&url) {
+            self.events.push(EngineEvent::FaviconChanged {
+                url: format!("{}/favicon.ico", origin),
+            });
+        }
+        self.events.push(EngineEvent::Progress { progress: 1.0 });
+        self.events.push(EngineEvent::LoadFinished { url });
+    }
+
+    // Ends a load that has not finished yet with `LoadFailed`
+    fn abort_load(&mut self, reason: &str) {
+        let Some(url) = self.pending_load.take() else {
+            return;
+        };
+        self.is_loading = false;
+        self.events.push(EngineEvent::LoadFailed {
+            url: url.clone(),
+            error: EngineError::NavigationFailed {
+                url,
+                reason: reason.to_string(),
+            },
+        });
+    }
+}
+
+fn origin_of(url: &str) -> Option<&str> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return None;
+    }
+    let scheme_end = url.find("://")? + 3;
+    let host_end = url[scheme_end..]
+        .find(['/', '?', '#'])
+        .map_or(url.len(), |index| scheme_end + index);
+    Some(&url[..host_end])
+}
+
+fn host_of(url: &str) -> Option<&str> {
+    origin_of(url)
+        .and_then(|origin| origin.split("://").nth(1))
+        .map(|authority| authority.rsplit('@').next().unwrap_or(authority))
+        .map(|host_port| host_port.split(':').next().unwrap_or(host_port))
+}
+
+impl RenderingEngine for MockEngine {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn initialize(&mut self) -> Result<()> {
+        info!("Mock engine {} initialized", self.id);
+        Ok(())
+    }
+
+    fn navigate_to(&mut self, url: &str) -> Result<()> {
+        if url.is_empty() {
+            return Err(EngineError::NavigationFailed {
+                url: url.to_string(),
+                reason: "Empty URL".to_string(),
+            }.into());
+        }
+
+        self.current_url = Some(url.to_string());
+        self.load(url);
+        Ok(())
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        if let Some(url) = self.current_url().map(str::to_string) {
+            self.load(&url);
+        } else {
+            warn!("Cannot reload: no current URL");
+        }
+        Ok(())
+    }
+
+    fn stop_loading(&mut self) {
+        self.abort_load("Stopped");
+    }
+
+    fn set_viewport_size(&mut self, width: u32, height: u32) {
+        self.config.width = width;
+        self.config.height = height;
+    }
+
+    fn viewport_size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    fn evaluate_script(&mut self, script: &str) -> Result<serde_json::Value> {
+        if !self.config.enable_javascript {
+            return Err(EngineError::JavaScriptError("JavaScript is disabled".to_string()).into());
+        }
+
+        // Only a handful of well-known expressions have an answer
+        let value = match script.trim().trim_end_matches(';') {
+            "location.href" | "window.location.href" => {
+                self.current_url().map_or(serde_json::Value::Null, |url| url.into())
+            }
+            "navigator.userAgent" => self.config.user_agent.clone().into(),
+            "window.innerWidth" => self.config.width.into(),
+            "window.innerHeight" => self.config.height.into(),
+            _ => serde_json::Value::Null,
+        };
+        Ok(value)
+    }
+
+    fn current_url(&self) -> Option<&str> {
+        self.current_url.as_deref()
+    }
+
+    fn is_loading(&self) -> bool {
+        self.is_loading
+    }
+
+    fn page_state(&self) -> PageState {
+        self.page_state.clone()
+    }
+
+    fn restore_page_state(&mut self, state: &PageState) {
+        if let Some(zoom_level) = state.zoom_level {
+            self.page_state.zoom_level = Some(zoom_level);
+        }
+        if let Some(scroll_position) = state.scroll_position {
+            self.page_state.scroll_position = Some(scroll_position);
+        }
+    }
+
+    fn take_events(&mut self) -> Vec<EngineEvent> {
+        self.finish_load();
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_types(engine: &mut MockEngine) -> Vec<String> {
+        engine
+            .take_events()
+            .iter()
+            .map(|event| serde_json::to_value(event).unwrap()["type"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn loads_finish_when_events_are_polled() {
+        let mut engine = MockEngine::new(EngineConfig::default());
+        assert!(engine.navigate_to("").is_err());
+
+        engine.navigate_to("https://example.com/a").unwrap();
+        assert!(engine.is_loading());
+        assert_eq!(engine.current_url(), Some("https://example.com/a"));
+        assert_eq!(
+            event_types(&mut engine),
+            vec!["load_started", "progress", "committed", "title_changed", "favicon_changed", "progress", "load_finished"]
+        );
+        assert!(!engine.is_loading());
+        assert!(engine.take_events().is_empty());
+    }
+
+    #[test]
+    fn navigating_and_reloading_track_the_current_url() {
+        let mut engine = MockEngine::new(EngineConfig::default());
+        engine.reload().unwrap();
+        assert!(engine.take_events().is_empty());
+
+        engine.navigate_to("https://example.com/a").unwrap();
+        engine.navigate_to("https://example.com/b").unwrap();
+        let events = engine.take_events();
+        assert_eq!(
+            events[2],
+            EngineEvent::LoadFailed {
+                url: "https://example.com/a".to_string(),
+                error: EngineError::NavigationFailed {
+                    url: "https://example.com/a".to_string(),
+                    reason: "Superseded by another navigation".to_string(),
+                },
+            }
+        );
+        assert_eq!(engine.current_url(), Some("https://example.com/b"));
+
+        engine.reload().unwrap();
+        assert!(engine.take_events().contains(&EngineEvent::Committed {
+            url: "https://example.com/b".to_string()
+        }));
+        assert_eq!(
+            engine.evaluate_script("location.href;").unwrap(),
+            serde_json::json!("https://example.com/b")
+        );
+    }
+
+    #[test]
+    fn stopping_and_invalid_hosts_fail_the_load() {
+        let mut engine = MockEngine::new(EngineConfig::default());
+        engine.navigate_to("https://example.com/").unwrap();
+        engine.stop_loading();
+        assert!(!engine.is_loading());
+        assert_eq!(event_types(&mut engine), vec!["load_started", "progress", "load_failed"]);
+
+        engine.navigate_to("https://nowhere.invalid/").unwrap();
+        assert_eq!(event_types(&mut engine), vec!["load_started", "progress", "load_failed"]);
+        assert!(!engine.is_loading());
+    }
+}
@@ -0,0 +1,157 @@
+// Rendering Engine Abstraction
+use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+pub mod mock;
+#[cfg(feature = "servo")]
+pub mod servo;
+
+pub use mock::MockEngine;
+#[cfg(feature = "servo")]
+pub use servo::ServoEngine;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineConfig {
+    pub width: u32,
+    pub height: u32,
+    pub user_agent: String,
+    pub enable_javascript: bool,
+    pub enable_webgl: bool,
+    pub enable_media: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            width: 1200,
+            height: 800,
+            user_agent: "Vishwakarma Express/0.1.0 (Servo)".to_string(),
+            enable_javascript: true,
+            enable_webgl: true,
+            enable_media: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum EngineError {
+    #[error("Engine initialization failed: {0}")]
+    InitializationFailed(String),
+    #[error("Navigation failed to {url}: {reason}")]
+    NavigationFailed { url: String, reason: String },
+    #[error("Rendering error: {0}")]
+    RenderingError(String),
+    #[error("JavaScript error: {0}")]
+    JavaScriptError(String),
+}
+
+/// Page lifecycle notifications, emitted in the order they happen.
+///
+/// A load always begins with `LoadStarted` and ends with either
+/// `LoadFinished` or `LoadFailed`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EngineEvent {
+    LoadStarted { url: String },
+    Committed { url: String },
+    TitleChanged { title: String },
+    FaviconChanged { url: String },
+    /// Load progress between 0.0 and 1.0
+    Progress { progress: f64 },
+    LoadFinished { url: String },
+    LoadFailed { url: String, error: EngineError },
+    /// The page started or stopped playing audio
+    AudioStateChanged { audible: bool },
+}
+
+/// View state of the current page, where the backend knows it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PageState {
+    pub zoom_level: Option<f64>,
+    pub scroll_position: Option<(f64, f64)>,
+}
+
+/// A backend capable of loading and rendering web content for a single tab.
+///
+/// Tabs and commands only talk to this trait, so backends can be swapped
+/// without touching them. Session history belongs to the tab's
+/// `NavigationController`; engines only load the URL they are given.
+pub trait RenderingEngine: Send {
+    fn id(&self) -> &str;
+    fn initialize(&mut self) -> Result<()>;
+
+    // Loading
+    fn navigate_to(&mut self, url: &str) -> Result<()>;
+    fn reload(&mut self) -> Result<()>;
+    fn stop_loading(&mut self);
+
+    // Viewport
+    fn set_viewport_size(&mut self, width: u32, height: u32);
+    fn viewport_size(&self) -> (u32, u32);
+
+    // Scripting
+    fn evaluate_script(&mut self, script: &str) -> Result<serde_json::Value>;
+
+    // State
+    fn current_url(&self) -> Option<&str>;
+    fn is_loading(&self) -> bool;
+
+    fn page_state(&self) -> PageState {
+        PageState::default()
+    }
+
+    fn restore_page_state(&mut self, _state: &PageState) {}
+
+    /// Returns the events emitted since the last call, oldest first.
+    fn take_events(&mut self) -> Vec<EngineEvent>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EngineBackend {
+    #[cfg_attr(not(feature = "servo"), default)]
+    Mock,
+    #[cfg(feature = "servo")]
+    #[default]
+    Servo,
+}
+
+// Thread-safe wrapper for the engine
+pub type SharedEngine = Arc<Mutex<Box<dyn RenderingEngine>>>;
+
+pub fn create_engine(config: Option<EngineConfig>) -> Result<SharedEngine> {
+    create_engine_with_backend(EngineBackend::default(), config)
+}
+
+pub fn create_engine_with_backend(backend: EngineBackend, config: Option<EngineConfig>) -> Result<SharedEngine> {
+    let config = config.unwrap_or_default();
+    let mut engine: Box<dyn RenderingEngine> = match backend {
+        EngineBackend::Mock => Box::new(MockEngine::new(config)),
+        #[cfg(feature = "servo")]
+        EngineBackend::Servo => Box::new(ServoEngine::new(config)),
+    };
+    engine.initialize().with_context(|| format!("Failed to initialize {:?} engine", backend))?;
+    Ok(Arc::new(Mutex::new(engine)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_an_initialized_mock_engine() {
+        let config = EngineConfig {
+            width: 640,
+            height: 480,
+            ..EngineConfig::default()
+        };
+        let engine = create_engine_with_backend(EngineBackend::Mock, Some(config)).unwrap();
+        let mut engine = engine.lock().unwrap();
+
+        assert_eq!(engine.viewport_size(), (640, 480));
+        assert_eq!(engine.current_url(), None);
+        engine.navigate_to("https://example.com/").unwrap();
+        assert!(!engine.take_events().is_empty());
+    }
+}
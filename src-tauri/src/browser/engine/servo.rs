@@ -0,0 +1,128 @@
+// Servo Engine Integration
+use super::{EngineConfig, EngineError, EngineEvent, RenderingEngine};
+use anyhow::Result;
+use log::{info, warn};
+use uuid::Uuid;
+
+pub struct ServoEngine {
+    id: String,
+    config: EngineConfig,
+    current_url: Option<String>,
+    is_loading: bool,
+    // Started but not yet committed
+    pending_load: Option<String>,
+    events: Vec<EngineEvent>,
+    // TODO: Add actual Servo instance once dependencies are ready
+    // servo: Option<servo::Servo<ServoCallbacks>>,
+}
+
+impl ServoEngine {
+    pub fn new(config: EngineConfig) -> Self {
+        let id = Uuid::new_v4().to_string();
+        info!("Creating new Servo engine instance: {}", id);
+
+        Self {
+            id,
+            config,
+            current_url: None,
+            is_loading: false,
+            pending_load: None,
+            events: Vec::new(),
+            // servo: None,
+        }
+    }
+}
+
+impl RenderingEngine for ServoEngine {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn initialize(&mut self) -> Result<()> {
+        info!("Initializing Servo engine {}", self.id);
+
+        // TODO: Initialize actual Servo engine
+
+        info!("Servo engine {} initialized successfully", self.id);
+        Ok(())
+    }
+
+    fn navigate_to(&mut self, url: &str) -> Result<()> {
+        info!("Navigating to: {}", url);
+
+        // Validate URL
+        if url.is_empty() {
+            return Err(EngineError::NavigationFailed {
+                url: url.to_string(),
+                reason: "Empty URL".to_string(),
+            }.into());
+        }
+
+        // TODO: Implement actual navigation with Servo. Its embedder
+        // callbacks will report the rest of the load.
+        self.stop_loading();
+        self.is_loading = true;
+        self.current_url = Some(url.to_string());
+        self.pending_load = Some(url.to_string());
+        self.events.push(EngineEvent::LoadStarted { url: url.to_string() });
+        Ok(())
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        if let Some(url) = self.current_url.clone() {
+            self.navigate_to(&url)
+        } else {
+            warn!("Cannot reload: no current URL");
+            Ok(())
+        }
+    }
+
+    fn stop_loading(&mut self) {
+        if let Some(url) = self.pending_load.take() {
+            info!("Stopping page load for engine {}", self.id);
+            self.is_loading = false;
+            self.events.push(EngineEvent::LoadFailed {
+                url: url.clone(),
+                error: EngineError::NavigationFailed {
+                    url,
+                    reason: "Stopped".to_string(),
+                },
+            });
+            // TODO: Stop actual Servo loading
+        }
+    }
+
+    fn set_viewport_size(&mut self, width: u32, height: u32) {
+        info!("Setting viewport size to {}x{}", width, height);
+        self.config.width = width;
+        self.config.height = height;
+        // TODO: Update Servo viewport
+    }
+
+    fn viewport_size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    fn evaluate_script(&mut self, _script: &str) -> Result<serde_json::Value> {
+        // TODO: Forward to Servo's script thread
+        Err(EngineError::JavaScriptError("Script evaluation is not available yet".to_string()).into())
+    }
+
+    fn current_url(&self) -> Option<&str> {
+        self.current_url.as_deref()
+    }
+
+    fn is_loading(&self) -> bool {
+        self.is_loading
+    }
+
+    fn take_events(&mut self) -> Vec<EngineEvent> {
+        // Until embedder callbacks exist, a load finishes when polled
+        if let Some(url) = self.pending_load.take() {
+            self.is_loading = false;
+            self.events.push(EngineEvent::Committed { url: url.clone() });
+            self.events.push(EngineEvent::LoadFinished { url });
+        }
+        std::mem::take(&mut self.events)
+    }
+}
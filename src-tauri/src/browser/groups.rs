@@ -0,0 +1,41 @@
+// Tab Groups
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TabGroupColor {
+    #[default]
+    Grey,
+    Blue,
+    Red,
+    Yellow,
+    Green,
+    Pink,
+    Purple,
+    Cyan,
+    Orange,
+}
+
+/// A named set of adjacent tabs in the tab strip.
+///
+/// Membership is stored on each tab's `TabInfo::group_id`; the group itself
+/// only carries presentation state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabGroup {
+    pub id: String,
+    pub name: String,
+    pub color: TabGroupColor,
+    pub collapsed: bool,
+}
+
+impl TabGroup {
+    pub fn new(name: String, color: TabGroupColor) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            color,
+            collapsed: false,
+        }
+    }
+}
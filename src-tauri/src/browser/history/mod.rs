@@ -0,0 +1,468 @@
+// Global Browsing History
+use anyhow::{Context, Result};
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+mod search;
+
+pub use search::{HistorySearch, HistorySearchResult};
+
+pub const HISTORY_SCHEMA_VERSION: u32 = 2;
+
+pub const HISTORY_FILE_NAME: &str = "history.sqlite";
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+// Migration `n` takes the schema from version `n` to `n + 1`
+const MIGRATIONS: [&str; HISTORY_SCHEMA_VERSION as usize] = [
+    "
+    CREATE TABLE IF NOT EXISTS pages (
+        id INTEGER PRIMARY KEY,
+        url TEXT NOT NULL UNIQUE,
+        title TEXT NOT NULL DEFAULT '',
+        visit_count INTEGER NOT NULL DEFAULT 0,
+        typed_count INTEGER NOT NULL DEFAULT 0,
+        last_visit_at INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS visits (
+        id INTEGER PRIMARY KEY,
+        page_id INTEGER NOT NULL REFERENCES pages(id) ON DELETE CASCADE,
+        visited_at INTEGER NOT NULL,
+        transition TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS visits_by_time ON visits(visited_at);
+    CREATE INDEX IF NOT EXISTS visits_by_page ON visits(page_id);
+    ",
+    // Full-text index of every page, kept in step with `pages` by triggers
+    "
+    CREATE VIRTUAL TABLE pages_fts USING fts5(url, title, body, tokenize = 'unicode61 remove_diacritics 2');
+    CREATE VIRTUAL TABLE pages_vocab USING fts5vocab(pages_fts, 'row');
+    INSERT INTO pages_fts (rowid, url, title, body) SELECT id, url, title, '' FROM pages;
+    CREATE TRIGGER pages_fts_insert AFTER INSERT ON pages BEGIN
+        INSERT INTO pages_fts (rowid, url, title, body) VALUES (new.id, new.url, new.title, '');
+    END;
+    CREATE TRIGGER pages_fts_title AFTER UPDATE OF title ON pages BEGIN
+        UPDATE pages_fts SET title = new.title WHERE rowid = new.id;
+    END;
+    CREATE TRIGGER pages_fts_delete AFTER DELETE ON pages BEGIN
+        DELETE FROM pages_fts WHERE rowid = old.id;
+    END;
+    CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+    ",
+];
+
+/// How the user got to a page
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageTransition {
+    /// Followed a link, or anything the page started itself
+    #[default]
+    Link,
+    /// Entered in the address bar
+    Typed,
+    /// A search results page the address bar made from the input
+    Generated,
+    Reload,
+    BackForward,
+}
+
+impl PageTransition {
+    fn as_str(self) -> &'static str {
+        match self {
+            PageTransition::Link => "link",
+            PageTransition::Typed => "typed",
+            PageTransition::Generated => "generated",
+            PageTransition::Reload => "reload",
+            PageTransition::BackForward => "back_forward",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "typed" => PageTransition::Typed,
+            "generated" => PageTransition::Generated,
+            "reload" => PageTransition::Reload,
+            "back_forward" => PageTransition::BackForward,
+            _ => PageTransition::Link,
+        }
+    }
+}
+
+/// Something a tab saw that belongs in the history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HistoryUpdate {
+    Visit {
+        url: String,
+        transition: PageTransition,
+        visited_at: u64,
+    },
+    Title { url: String, title: String },
+}
+
+/// A URL and what is known about all its visits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VisitedPage {
+    pub url: String,
+    pub title: String,
+    pub visit_count: u32,
+    /// Visits typed into the address bar
+    pub typed_count: u32,
+    pub last_visit_at: u64,
+}
+
+/// One visit along with its page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryVisit {
+    pub id: i64,
+    pub visited_at: u64,
+    pub transition: PageTransition,
+    #[serde(flatten)]
+    pub page: VisitedPage,
+}
+
+/// Selects visits newest first. Times are Unix seconds; `start_time` is
+/// inclusive and `end_time` exclusive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryQuery {
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+impl Default for HistoryQuery {
+    fn default() -> Self {
+        Self {
+            start_time: None,
+            end_time: None,
+            limit: DEFAULT_PAGE_SIZE,
+            offset: 0,
+        }
+    }
+}
+
+/// Every committed navigation across all windows, kept in SQLite
+pub struct HistoryStore {
+    connection: Connection,
+    // Whether page text sent by the frontend is indexed for search
+    page_text_indexing: bool,
+}
+
+pub type SharedHistoryStore = Arc<Mutex<HistoryStore>>;
+
+impl HistoryStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create history directory {:?}", parent))?;
+        }
+        let connection = Connection::open(path).with_context(|| format!("Failed to open history {:?}", path))?;
+        let store = Self::with_connection(connection)?;
+        info!("Opened browsing history at {:?}", path);
+        Ok(store)
+    }
+
+    /// A history that is gone when the store is dropped
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut connection: Connection) -> Result<Self> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+
+        let version: u32 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > HISTORY_SCHEMA_VERSION {
+            anyhow::bail!("Unsupported history schema version {}", version);
+        }
+        let transaction = connection.transaction()?;
+        for migration in &MIGRATIONS[version as usize..] {
+            transaction.execute_batch(migration)?;
+        }
+        transaction.pragma_update(None, "user_version", HISTORY_SCHEMA_VERSION)?;
+        transaction.commit()?;
+
+        let page_text_indexing = connection
+            .query_row("SELECT value FROM settings WHERE key = 'page_text_indexing'", [], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()?
+            .is_some_and(|value| value == "on");
+        Ok(Self {
+            connection,
+            page_text_indexing,
+        })
+    }
+
+    /// Only web pages and local files are kept, not `about:` or `data:`
+    pub fn is_recordable(url: &str) -> bool {
+        Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https" | "file"))
+    }
+
+    /// Applies updates from tabs in one transaction
+    pub fn apply(&mut self, updates: &[HistoryUpdate]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        for update in updates {
+            match update {
+                HistoryUpdate::Visit {
+                    url,
+                    transition,
+                    visited_at,
+                } => {
+                    add_visit(&transaction, url, *transition, *visited_at)?;
+                }
+                HistoryUpdate::Title { url, title } => {
+                    transaction.execute("UPDATE pages SET title = ?1 WHERE url = ?2", params![title, url])?;
+                }
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Returns the new visit's id, or None if the URL is not recorded
+    pub fn record_visit(&mut self, url: &str, transition: PageTransition, visited_at: u64) -> Result<Option<i64>> {
+        add_visit(&self.connection, url, transition, visited_at)
+    }
+
+    pub fn page(&self, url: &str) -> Result<Option<VisitedPage>> {
+        let page = self
+            .connection
+            .query_row(
+                "SELECT url, title, visit_count, typed_count, last_visit_at FROM pages WHERE url = ?1",
+                params![url],
+                page_from_row,
+            )
+            .optional()?;
+        Ok(page)
+    }
+
+    pub fn visits(&self, query: &HistoryQuery) -> Result<Vec<HistoryVisit>> {
+        let mut statement = self.connection.prepare(
+            "SELECT visits.id, visits.visited_at, visits.transition,
+                    pages.url, pages.title, pages.visit_count, pages.typed_count, pages.last_visit_at
+             FROM visits JOIN pages ON pages.id = visits.page_id
+             WHERE visits.visited_at >= ?1 AND visits.visited_at < ?2
+             ORDER BY visits.visited_at DESC, visits.id DESC
+             LIMIT ?3 OFFSET ?4",
+        )?;
+        let (start_time, end_time) = time_bounds(query.start_time, query.end_time);
+        let visits = statement
+            .query_map(
+                params![start_time, end_time, query.limit as i64, query.offset as i64],
+                |row| {
+                    Ok(HistoryVisit {
+                        id: row.get(0)?,
+                        visited_at: row.get(1)?,
+                        transition: PageTransition::parse(&row.get::<_, String>(2)?),
+                        page: VisitedPage {
+                            url: row.get(3)?,
+                            title: row.get(4)?,
+                            visit_count: row.get(5)?,
+                            typed_count: row.get(6)?,
+                            last_visit_at: row.get(7)?,
+                        },
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(visits)
+    }
+
+    /// Returns false if there was no such visit
+    pub fn delete_visit(&mut self, visit_id: i64) -> Result<bool> {
+        let transaction = self.connection.transaction()?;
+        let deleted = transaction.execute("DELETE FROM visits WHERE id = ?1", params![visit_id])?;
+        refresh_pages(&transaction)?;
+        transaction.commit()?;
+        Ok(deleted > 0)
+    }
+
+    /// Forgets a URL and every visit to it
+    pub fn delete_url(&mut self, url: &str) -> Result<bool> {
+        let deleted = self.connection.execute("DELETE FROM pages WHERE url = ?1", params![url])?;
+        info!("Deleted {} from history", url);
+        Ok(deleted > 0)
+    }
+
+    /// Deletes visits between `start_time` (inclusive) and `end_time`
+    /// (exclusive), either open-ended. Returns how many went.
+    pub fn delete_range(&mut self, start_time: Option<u64>, end_time: Option<u64>) -> Result<usize> {
+        let (start_time, end_time) = time_bounds(start_time, end_time);
+        let transaction = self.connection.transaction()?;
+        let deleted = transaction.execute(
+            "DELETE FROM visits WHERE visited_at >= ?1 AND visited_at < ?2",
+            params![start_time, end_time],
+        )?;
+        refresh_pages(&transaction)?;
+        transaction.commit()?;
+        info!("Deleted {} history visits", deleted);
+        Ok(deleted)
+    }
+
+    pub fn clear(&mut self) -> Result<()> {
+        self.connection.execute_batch("DELETE FROM visits; DELETE FROM pages;")?;
+        info!("Browsing history cleared");
+        Ok(())
+    }
+}
+
+fn add_visit(connection: &Connection, url: &str, transition: PageTransition, visited_at: u64) -> Result<Option<i64>> {
+    if !HistoryStore::is_recordable(url) {
+        return Ok(None);
+    }
+    let typed = u32::from(transition == PageTransition::Typed);
+    connection.execute(
+        "INSERT INTO pages (url, visit_count, typed_count, last_visit_at) VALUES (?1, 1, ?2, ?3)
+         ON CONFLICT(url) DO UPDATE SET
+             visit_count = visit_count + 1,
+             typed_count = typed_count + excluded.typed_count,
+             last_visit_at = MAX(last_visit_at, excluded.last_visit_at)",
+        params![url, typed, visited_at],
+    )?;
+    connection.execute(
+        "INSERT INTO visits (page_id, visited_at, transition)
+         SELECT id, ?2, ?3 FROM pages WHERE url = ?1",
+        params![url, visited_at, transition.as_str()],
+    )?;
+    Ok(Some(connection.last_insert_rowid()))
+}
+
+// Recounts pages after visits were deleted and drops pages left without any
+fn refresh_pages(connection: &Connection) -> Result<()> {
+    connection.execute_batch(
+        "DELETE FROM pages WHERE NOT EXISTS (SELECT 1 FROM visits WHERE visits.page_id = pages.id);
+         UPDATE pages SET
+             visit_count = (SELECT COUNT(*) FROM visits WHERE visits.page_id = pages.id),
+             typed_count = (SELECT COUNT(*) FROM visits WHERE visits.page_id = pages.id AND transition = 'typed'),
+             last_visit_at = (SELECT MAX(visited_at) FROM visits WHERE visits.page_id = pages.id);",
+    )?;
+    Ok(())
+}
+
+fn page_from_row(row: &Row) -> rusqlite::Result<VisitedPage> {
+    Ok(VisitedPage {
+        url: row.get(0)?,
+        title: row.get(1)?,
+        visit_count: row.get(2)?,
+        typed_count: row.get(3)?,
+        last_visit_at: row.get(4)?,
+    })
+}
+
+pub(super) fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// SQLite integers are signed, so open ends become the signed extremes
+fn time_bounds(start_time: Option<u64>, end_time: Option<u64>) -> (i64, i64) {
+    let clamp = |time: u64| i64::try_from(time).unwrap_or(i64::MAX);
+    (start_time.map_or(0, clamp), end_time.map_or(i64::MAX, clamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visit(url: &str, transition: PageTransition, visited_at: u64) -> HistoryUpdate {
+        HistoryUpdate::Visit {
+            url: url.to_string(),
+            transition,
+            visited_at,
+        }
+    }
+
+    #[test]
+    fn visits_update_their_page() {
+        let mut history = HistoryStore::open_in_memory().unwrap();
+        history
+            .apply(&[
+                visit("https://example.com/", PageTransition::Typed, 100),
+                HistoryUpdate::Title {
+                    url: "https://example.com/".to_string(),
+                    title: "Example".to_string(),
+                },
+                visit("https://example.com/", PageTransition::Link, 200),
+                visit("about:blank", PageTransition::Typed, 300),
+            ])
+            .unwrap();
+
+        let page = history.page("https://example.com/").unwrap().unwrap();
+        assert_eq!(page.title, "Example");
+        assert_eq!((page.visit_count, page.typed_count, page.last_visit_at), (2, 1, 200));
+        assert!(history.page("about:blank").unwrap().is_none());
+    }
+
+    #[test]
+    fn pages_through_visits_by_date() {
+        let mut history = HistoryStore::open_in_memory().unwrap();
+        for (index, time) in [100, 200, 300, 400].into_iter().enumerate() {
+            history
+                .record_visit(&format!("https://example.com/{}", index), PageTransition::Link, time)
+                .unwrap();
+        }
+
+        let query = HistoryQuery {
+            start_time: Some(200),
+            end_time: Some(400),
+            ..HistoryQuery::default()
+        };
+        let visits = history.visits(&query).unwrap();
+        let times: Vec<u64> = visits.iter().map(|visit| visit.visited_at).collect();
+        assert_eq!(times, vec![300, 200]);
+
+        let query = HistoryQuery {
+            limit: 2,
+            offset: 2,
+            ..HistoryQuery::default()
+        };
+        let visits = history.visits(&query).unwrap();
+        assert_eq!(visits[0].page.url, "https://example.com/1");
+        assert_eq!(visits.len(), 2);
+    }
+
+    #[test]
+    fn deleting_visits_recounts_and_drops_pages() {
+        let mut history = HistoryStore::open_in_memory().unwrap();
+        let first = history
+            .record_visit("https://example.com/a", PageTransition::Typed, 100)
+            .unwrap()
+            .unwrap();
+        history.record_visit("https://example.com/a", PageTransition::Link, 200).unwrap();
+        history.record_visit("https://example.com/b", PageTransition::Link, 300).unwrap();
+        history.record_visit("https://example.com/c", PageTransition::Link, 400).unwrap();
+
+        assert!(history.delete_visit(first).unwrap());
+        let page = history.page("https://example.com/a").unwrap().unwrap();
+        assert_eq!((page.visit_count, page.typed_count), (1, 0));
+
+        assert_eq!(history.delete_range(Some(150), Some(350)).unwrap(), 2);
+        assert!(history.page("https://example.com/a").unwrap().is_none());
+        assert!(history.delete_url("https://example.com/c").unwrap());
+        assert!(history.visits(&HistoryQuery::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn history_survives_reopening() {
+        let path = std::env::temp_dir()
+            .join(format!("vishwakarma-history-{}", uuid::Uuid::new_v4()))
+            .join(HISTORY_FILE_NAME);
+        {
+            let mut history = HistoryStore::open(&path).unwrap();
+            history.record_visit("https://example.com/", PageTransition::Typed, 100).unwrap();
+        }
+
+        let mut history = HistoryStore::open(&path).unwrap();
+        assert_eq!(history.page("https://example.com/").unwrap().unwrap().visit_count, 1);
+        history.clear().unwrap();
+        assert!(history.page("https://example.com/").unwrap().is_none());
+    }
+}
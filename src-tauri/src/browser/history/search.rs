@@ -0,0 +1,358 @@
+// History Search
+use super::{page_from_row, time_bounds, HistoryStore, VisitedPage};
+use anyhow::Result;
+use log::info;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+// Column weights for url, title and page text
+const BM25_WEIGHTS: (f64, f64, f64) = (2.0, 4.0, 1.0);
+// Matches ranked by text relevance before visits and recency are weighed in
+const MAX_CANDIDATES: usize = 500;
+// Shorter words only match by prefix
+const FUZZY_MIN_LENGTH: usize = 4;
+const MAX_FUZZY_TERMS: usize = 8;
+// How long it takes a page's recency boost to halve
+const RECENCY_HALF_LIFE_DAYS: f64 = 30.0;
+// Page text beyond this is not indexed
+const MAX_PAGE_TEXT_CHARS: usize = 64 * 1024;
+
+/// Pages matching every word of `query`, by prefix or a close spelling.
+/// Only pages visited between `start_time` (inclusive) and `end_time`
+/// (exclusive) match.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistorySearch {
+    pub query: String,
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+impl Default for HistorySearch {
+    fn default() -> Self {
+        Self {
+            query: String::new(),
+            start_time: None,
+            end_time: None,
+            limit: 20,
+            offset: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistorySearchResult {
+    #[serde(flatten)]
+    pub page: VisitedPage,
+    /// Higher is better
+    pub score: f64,
+    /// Part of the page text, when the text was indexed
+    pub snippet: Option<String>,
+}
+
+impl HistoryStore {
+    /// Best matches first
+    pub fn search(&self, search: &HistorySearch) -> Result<Vec<HistorySearchResult>> {
+        let Some(expression) = self.match_expression(&search.query)? else {
+            return Ok(Vec::new());
+        };
+        let mut statement = self.connection.prepare(
+            "SELECT pages.url, pages.title, pages.visit_count, pages.typed_count, pages.last_visit_at,
+                    bm25(pages_fts, ?1, ?2, ?3),
+                    CASE WHEN pages_fts.body = '' THEN NULL ELSE snippet(pages_fts, 2, '', '', '…', 16) END
+             FROM pages_fts JOIN pages ON pages.id = pages_fts.rowid
+             WHERE pages_fts MATCH ?4
+               AND EXISTS (SELECT 1 FROM visits WHERE visits.page_id = pages.id
+                           AND visits.visited_at >= ?5 AND visits.visited_at < ?6)
+             ORDER BY bm25(pages_fts, ?1, ?2, ?3)
+             LIMIT ?7",
+        )?;
+        let (start_time, end_time) = time_bounds(search.start_time, search.end_time);
+        let (url_weight, title_weight, body_weight) = BM25_WEIGHTS;
+        let now = super::now_secs();
+        let mut results = statement
+            .query_map(
+                params![
+                    url_weight,
+                    title_weight,
+                    body_weight,
+                    expression,
+                    start_time,
+                    end_time,
+                    MAX_CANDIDATES as i64
+                ],
+                |row| {
+                    let page = page_from_row(row)?;
+                    // bm25 is negative, and more so the better the match
+                    let relevance = -row.get::<_, f64>(5)?;
+                    Ok(HistorySearchResult {
+                        score: rank(relevance, &page, now),
+                        snippet: row.get(6)?,
+                        page,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(results.into_iter().skip(search.offset).take(search.limit).collect())
+    }
+
+    pub fn page_text_indexing(&self) -> bool {
+        self.page_text_indexing
+    }
+
+    /// Turning indexing off forgets the text indexed so far
+    pub fn set_page_text_indexing(&mut self, enabled: bool) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO settings (key, value) VALUES ('page_text_indexing', ?1)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![if enabled { "on" } else { "off" }],
+        )?;
+        if !enabled {
+            transaction.execute("UPDATE pages_fts SET body = '' WHERE body != ''", [])?;
+        }
+        transaction.commit()?;
+        self.page_text_indexing = enabled;
+        info!("History page text indexing {}", if enabled { "enabled" } else { "disabled" });
+        Ok(())
+    }
+
+    /// Indexes the text of a visited page. Returns false if indexing is off
+    /// or the page is not in the history.
+    pub fn index_page_text(&mut self, url: &str, text: &str) -> Result<bool> {
+        if !self.page_text_indexing {
+            return Ok(false);
+        }
+        let text: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let text: String = text.chars().take(MAX_PAGE_TEXT_CHARS).collect();
+        let updated = self.connection.execute(
+            "UPDATE pages_fts SET body = ?1 WHERE rowid = (SELECT id FROM pages WHERE url = ?2)",
+            params![text, url],
+        )?;
+        Ok(updated > 0)
+    }
+
+    // Turns free text into an FTS5 query where every word must match,
+    // either as a prefix or as an indexed word within a few typos of it
+    fn match_expression(&self, query: &str) -> Result<Option<String>> {
+        let words: Vec<String> = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        if words.is_empty() {
+            return Ok(None);
+        }
+
+        let mut clauses = Vec::with_capacity(words.len());
+        for word in &words {
+            let mut alternatives = vec![format!("{}*", quote(word))];
+            for term in self.similar_terms(word)? {
+                alternatives.push(quote(&term));
+            }
+            clauses.push(format!("({})", alternatives.join(" OR ")));
+        }
+        Ok(Some(clauses.join(" AND ")))
+    }
+
+    // Indexed words close enough to `word` to be a misspelling of it
+    fn similar_terms(&self, word: &str) -> Result<Vec<String>> {
+        let length = word.chars().count();
+        if length < FUZZY_MIN_LENGTH {
+            return Ok(Vec::new());
+        }
+        let max_distance = if length <= 6 { 1 } else { 2 };
+
+        let mut statement = self
+            .connection
+            .prepare_cached("SELECT term FROM pages_vocab WHERE length(term) BETWEEN ?1 AND ?2")?;
+        let terms = statement.query_map(
+            params![(length - max_distance) as i64, (length + max_distance) as i64],
+            |row| row.get::<_, String>(0),
+        )?;
+
+        let mut similar = Vec::new();
+        for term in terms {
+            let term = term?;
+            // Already covered by the prefix match
+            if term.starts_with(word) {
+                continue;
+            }
+            let distance = edit_distance(word, &term);
+            if distance <= max_distance {
+                similar.push((distance, term));
+            }
+        }
+        similar.sort();
+        similar.truncate(MAX_FUZZY_TERMS);
+        Ok(similar.into_iter().map(|(_, term)| term).collect())
+    }
+}
+
+fn quote(term: &str) -> String {
+    format!("\"{}\"", term.replace('"', "\"\""))
+}
+
+// Text relevance, boosted for pages visited often, typed or seen lately
+fn rank(relevance: f64, page: &VisitedPage, now: u64) -> f64 {
+    let visits = 1.0 + (1.0 + page.visit_count as f64).ln() * 0.5;
+    let typed = if page.typed_count > 0 { 1.2 } else { 1.0 };
+    let age_days = now.saturating_sub(page.last_visit_at) as f64 / 86_400.0;
+    let recency = 0.5 + 0.5 * 0.5f64.powf(age_days / RECENCY_HALF_LIFE_DAYS);
+    relevance * visits * typed * recency
+}
+
+// Levenshtein distance, counting an adjacent swap as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = rows[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            row[j] = substitution.min(rows[i - 1][j] + 1).min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::history::{HistoryUpdate, PageTransition, HISTORY_FILE_NAME, MIGRATIONS};
+
+    fn store_with(pages: &[(&str, &str, u64)]) -> HistoryStore {
+        let mut history = HistoryStore::open_in_memory().unwrap();
+        for (url, title, visited_at) in pages {
+            history.record_visit(url, PageTransition::Link, *visited_at).unwrap();
+            history
+                .apply(&[HistoryUpdate::Title {
+                    url: url.to_string(),
+                    title: title.to_string(),
+                }])
+                .unwrap();
+        }
+        history
+    }
+
+    fn urls(history: &HistoryStore, search: HistorySearch) -> Vec<String> {
+        history
+            .search(&search)
+            .unwrap()
+            .into_iter()
+            .map(|result| result.page.url)
+            .collect()
+    }
+
+    fn query(text: &str) -> HistorySearch {
+        HistorySearch {
+            query: text.to_string(),
+            ..HistorySearch::default()
+        }
+    }
+
+    #[test]
+    fn matches_prefixes_and_misspellings() {
+        let history = store_with(&[
+            ("https://docs.rs/tokio", "Tokio runtime documentation", 100),
+            ("https://example.com/", "Example Domain", 200),
+        ]);
+
+        assert_eq!(urls(&history, query("runt doc")), vec!["https://docs.rs/tokio"]);
+        assert_eq!(urls(&history, query("exmaple")), vec!["https://example.com/"]);
+        assert_eq!(urls(&history, query("documentaiton tokio")), vec!["https://docs.rs/tokio"]);
+        assert!(urls(&history, query("tokio example")).is_empty());
+        assert!(urls(&history, query("  \"* ")).is_empty());
+    }
+
+    #[test]
+    fn title_matches_and_frequent_pages_rank_first() {
+        let mut history = store_with(&[
+            ("https://a.example/rust", "Something else", 100),
+            ("https://b.example/", "Rust language", 100),
+            ("https://c.example/", "Learning Rust", 100),
+        ]);
+        for time in [110, 120, 130] {
+            history.record_visit("https://c.example/", PageTransition::Link, time).unwrap();
+        }
+
+        assert_eq!(
+            urls(&history, query("rust")),
+            vec!["https://c.example/", "https://b.example/", "https://a.example/rust"]
+        );
+    }
+
+    #[test]
+    fn filters_by_visit_date() {
+        let history = store_with(&[
+            ("https://example.com/old", "Release notes", 100),
+            ("https://example.com/new", "Release notes", 500),
+        ]);
+
+        let search = HistorySearch {
+            start_time: Some(400),
+            ..query("release")
+        };
+        assert_eq!(urls(&history, search), vec!["https://example.com/new"]);
+        let search = HistorySearch {
+            end_time: Some(400),
+            ..query("release")
+        };
+        assert_eq!(urls(&history, search), vec!["https://example.com/old"]);
+    }
+
+    #[test]
+    fn page_text_is_only_indexed_when_enabled() {
+        let mut history = store_with(&[("https://example.com/", "Example", 100)]);
+
+        assert!(!history.index_page_text("https://example.com/", "the quick brown fox").unwrap());
+        assert!(urls(&history, query("fox")).is_empty());
+
+        history.set_page_text_indexing(true).unwrap();
+        assert!(history.index_page_text("https://example.com/", "the quick\n brown fox").unwrap());
+        let results = history.search(&query("fox")).unwrap();
+        assert_eq!(results[0].snippet.as_deref(), Some("the quick brown fox"));
+
+        history.set_page_text_indexing(false).unwrap();
+        assert!(urls(&history, query("fox")).is_empty());
+    }
+
+    #[test]
+    fn upgrading_indexes_existing_pages() {
+        let path = std::env::temp_dir()
+            .join(format!("vishwakarma-history-{}", uuid::Uuid::new_v4()))
+            .join(HISTORY_FILE_NAME);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        {
+            let connection = rusqlite::Connection::open(&path).unwrap();
+            connection.execute_batch(MIGRATIONS[0]).unwrap();
+            connection
+                .execute_batch(
+                    "INSERT INTO pages (url, title, visit_count, last_visit_at)
+                     VALUES ('https://example.com/', 'Example Domain', 1, 100);
+                     INSERT INTO visits (page_id, visited_at, transition) VALUES (1, 100, 'link');
+                     PRAGMA user_version = 1;",
+                )
+                .unwrap();
+        }
+
+        let history = HistoryStore::open(&path).unwrap();
+        assert_eq!(urls(&history, query("domain")), vec!["https://example.com/"]);
+    }
+
+    #[test]
+    fn swaps_count_as_one_edit() {
+        assert_eq!(edit_distance("example", "exmaple"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -1,8 +1,21 @@
 // Browser module - Core browser functionality
 pub mod engine;
 pub mod tabs;
+pub mod discard;
+pub mod groups;
 pub mod navigation;
 pub mod downloads;
+pub mod history;
+pub mod omnibox;
+pub mod session;
+pub mod windows;
 
 pub use engine::*;
 pub use tabs::*;
+pub use discard::*;
+pub use groups::*;
+pub use downloads::*;
+pub use history::*;
+pub use omnibox::*;
+pub use session::*;
+pub use windows::*;
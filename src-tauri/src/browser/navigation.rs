@@ -26,6 +26,13 @@ impl HistoryEntry {
     }
 }
 
+/// Serializable copy of a navigation history, used for session restore
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NavigationState {
+    pub entries: Vec<HistoryEntry>,
+    pub current_index: Option<usize>,
+}
+
 #[derive(Debug)]
 pub struct NavigationHistory {
     entries: VecDeque<HistoryEntry>,
@@ -42,6 +49,25 @@ impl NavigationHistory {
         }
     }
 
+    pub fn from_state(state: NavigationState) -> Self {
+        let mut history = Self::new();
+        let skipped = state.entries.len().saturating_sub(history.max_entries);
+        history.entries = state.entries.into_iter().skip(skipped).collect();
+        history.current_index = state
+            .current_index
+            .map(|index| index.saturating_sub(skipped))
+            .filter(|index| *index < history.entries.len())
+            .or_else(|| history.entries.len().checked_sub(1));
+        history
+    }
+
+    pub fn state(&self) -> NavigationState {
+        NavigationState {
+            entries: self.entries.iter().cloned().collect(),
+            current_index: self.current_index,
+        }
+    }
+
     pub fn add_entry(&mut self, url: String, title: String) {
         let entry = HistoryEntry::new(url, title);
         
@@ -165,6 +191,16 @@ impl NavigationController {
         }
     }
 
+    pub fn from_state(state: NavigationState) -> Self {
+        Self {
+            history: NavigationHistory::from_state(state),
+        }
+    }
+
+    pub fn state(&self) -> NavigationState {
+        self.history.state()
+    }
+
     pub fn navigate(&mut self, request: NavigationRequest) -> Result<()> {
         info!("Navigation request: {}", request.url);
 
@@ -0,0 +1,250 @@
+// Address Bar Input
+use super::history::PageTransition;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use url::{form_urlencoded, Host, Url};
+
+/// Replaced with the encoded query in search engine templates
+pub const SEARCH_TERMS_PLACEHOLDER: &str = "{searchTerms}";
+
+// Schemes typed input may use as-is. `javascript:` is deliberately absent.
+const NAVIGABLE_SCHEMES: &[&str] = &["http", "https", "file", "ftp", "about", "data", "view-source"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchEngine {
+    pub name: String,
+    /// An http(s) URL containing `{searchTerms}`
+    pub template: String,
+}
+
+impl Default for SearchEngine {
+    fn default() -> Self {
+        Self::builtin("google").expect("Google is a built-in search engine")
+    }
+}
+
+impl SearchEngine {
+    pub fn new(name: String, template: String) -> Result<Self> {
+        if !template.contains(SEARCH_TERMS_PLACEHOLDER) {
+            anyhow::bail!("Search engine template must contain {}", SEARCH_TERMS_PLACEHOLDER);
+        }
+        let engine = Self { name, template };
+        let url = Url::parse(&engine.search_url("test"))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            anyhow::bail!("Search engine template must be an http or https URL");
+        }
+        Ok(engine)
+    }
+
+    /// One of `google`, `bing` or `duckduckgo`
+    pub fn builtin(id: &str) -> Option<Self> {
+        let (name, template) = match id {
+            "google" => ("Google", "https://www.google.com/search?q={searchTerms}"),
+            "bing" => ("Bing", "https://www.bing.com/search?q={searchTerms}"),
+            "duckduckgo" => ("DuckDuckGo", "https://duckduckgo.com/?q={searchTerms}"),
+            _ => return None,
+        };
+        Some(Self {
+            name: name.to_string(),
+            template: template.to_string(),
+        })
+    }
+
+    pub fn search_url(&self, query: &str) -> String {
+        let terms: String = form_urlencoded::byte_serialize(query.as_bytes()).collect();
+        self.template.replace(SEARCH_TERMS_PLACEHOLDER, &terms)
+    }
+}
+
+/// Where typed input leads and how the visit is recorded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OmniboxTarget {
+    pub url: String,
+    /// `Typed` for addresses, `Generated` for searches
+    pub transition: PageTransition,
+}
+
+/// Turns address bar input into a normalized URL: explicit URLs, bare
+/// hosts such as `example.com` or `localhost:3000`, IP literals and file
+/// paths are navigated to, and anything else is searched for.
+pub fn resolve_input(input: &str, search_engine: &SearchEngine) -> Result<OmniboxTarget> {
+    let input = input.trim();
+    if input.is_empty() {
+        anyhow::bail!("Nothing to navigate to");
+    }
+
+    let url = file_path_url(input).or_else(|| {
+        if input.chars().any(char::is_whitespace) {
+            return None;
+        }
+        explicit_url(input).or_else(|| bare_host_url(input))
+    });
+    Ok(match url {
+        Some(url) => OmniboxTarget {
+            url: url.to_string(),
+            transition: PageTransition::Typed,
+        },
+        None => OmniboxTarget {
+            url: search_engine.search_url(input),
+            transition: PageTransition::Generated,
+        },
+    })
+}
+
+// `/tmp/a.html` or `~/notes.txt`, and `C:\Users` on Windows
+fn file_path_url(input: &str) -> Option<Url> {
+    let looks_like_path = input.starts_with('/')
+        || input.starts_with("~/")
+        || input.starts_with("\\\\")
+        || (cfg!(windows) && has_drive_letter(input));
+    if !looks_like_path {
+        return None;
+    }
+    let path = match input.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()?.join(rest),
+        None => PathBuf::from(input),
+    };
+    Url::from_file_path(Path::new(&path)).ok()
+}
+
+fn has_drive_letter(input: &str) -> bool {
+    let bytes = input.as_bytes();
+    bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && matches!(bytes[2], b'\\' | b'/')
+}
+
+// Input that already names a scheme we are willing to load. `localhost:3000`
+// also parses with a scheme, which is why the scheme must be a known one.
+fn explicit_url(input: &str) -> Option<Url> {
+    let url = Url::parse(input).ok()?;
+    if !NAVIGABLE_SCHEMES.contains(&url.scheme()) {
+        return None;
+    }
+    if matches!(url.scheme(), "http" | "https" | "ftp") && url.host().is_none() {
+        return None;
+    }
+    Some(url)
+}
+
+// `example.com/path`, `localhost:3000`, `192.168.1.1` or `[::1]:8080`.
+// Public names get https; local, IP and single-label hosts get http, since
+// they rarely serve TLS.
+fn bare_host_url(input: &str) -> Option<Url> {
+    let authority = input.split(['/', '?', '#']).next()?;
+    // Reads as an email address rather than credentials
+    if authority.contains('@') {
+        return None;
+    }
+    let (host, port) = split_port(authority)?;
+
+    let url = Url::parse(&format!("http://{}", input)).ok()?;
+    let secure = match url.host()? {
+        // The URL parser reads `3.14` or `42` as IPv4, so require all four parts
+        Host::Ipv4(_) => {
+            host.parse::<Ipv4Addr>().ok()?;
+            false
+        }
+        Host::Ipv6(_) => false,
+        Host::Domain(domain) => {
+            if domain == "localhost" || domain.ends_with(".localhost") {
+                false
+            } else if has_top_level_domain(domain) {
+                true
+            } else if port.is_some() {
+                false
+            } else {
+                return None;
+            }
+        }
+    };
+    if !secure {
+        return Some(url);
+    }
+    Url::parse(&format!("https://{}", input)).ok()
+}
+
+// Splits `host:port`, rejecting anything after the colon that is not a port
+fn split_port(authority: &str) -> Option<(&str, Option<u16>)> {
+    if authority.starts_with('[') {
+        let end = authority.find(']')?;
+        let (host, rest) = authority.split_at(end + 1);
+        return match rest.strip_prefix(':') {
+            Some(port) => Some((host, Some(port.parse().ok()?))),
+            None if rest.is_empty() => Some((host, None)),
+            None => None,
+        };
+    }
+    match authority.rsplit_once(':') {
+        Some((host, port)) => Some((host, Some(port.parse().ok()?))),
+        None => Some((authority, None)),
+    }
+}
+
+// `example.com` or `münchen.de`, but not `file.txt` lookalikes such as `3.14`
+fn has_top_level_domain(domain: &str) -> bool {
+    let Some((name, tld)) = domain.trim_end_matches('.').rsplit_once('.') else {
+        return false;
+    };
+    !name.is_empty()
+        && (tld.starts_with("xn--") || (tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(input: &str) -> (String, PageTransition) {
+        let target = resolve_input(input, &SearchEngine::default()).unwrap();
+        (target.url, target.transition)
+    }
+
+    fn typed(url: &str) -> (String, PageTransition) {
+        (url.to_string(), PageTransition::Typed)
+    }
+
+    #[test]
+    fn addresses_get_a_scheme_and_are_normalized() {
+        assert_eq!(resolve("example.com"), typed("https://example.com/"));
+        assert_eq!(resolve("EXAMPLE.com/Path?q=1#top"), typed("https://example.com/Path?q=1#top"));
+        assert_eq!(resolve("http://example.com:80/"), typed("http://example.com/"));
+        assert_eq!(resolve("münchen.de"), typed("https://xn--mnchen-3ya.de/"));
+        assert_eq!(resolve("localhost:3000"), typed("http://localhost:3000/"));
+        assert_eq!(resolve("app.localhost/login"), typed("http://app.localhost/login"));
+        assert_eq!(resolve("192.168.1.1"), typed("http://192.168.1.1/"));
+        assert_eq!(resolve("[::1]:8080/x"), typed("http://[::1]:8080/x"));
+        assert_eq!(resolve(" intranet:8443 "), typed("http://intranet:8443/"));
+        assert_eq!(resolve("about:blank"), typed("about:blank"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_paths_become_file_urls() {
+        assert_eq!(resolve("/tmp/my page.html"), typed("file:///tmp/my%20page.html"));
+        let home = Url::from_file_path(dirs::home_dir().unwrap().join("notes.txt")).unwrap();
+        assert_eq!(resolve("~/notes.txt"), typed(home.as_str()));
+    }
+
+    #[test]
+    fn everything_else_is_searched_for() {
+        let generated = |url: &str| (url.to_string(), PageTransition::Generated);
+
+        assert_eq!(resolve("rust borrow checker"), generated("https://www.google.com/search?q=rust+borrow+checker"));
+        assert_eq!(resolve("3.14"), generated("https://www.google.com/search?q=3.14"));
+        assert_eq!(resolve("42"), generated("https://www.google.com/search?q=42"));
+        assert_eq!(resolve("intranet"), generated("https://www.google.com/search?q=intranet"));
+        assert_eq!(resolve("me@example.com"), generated("https://www.google.com/search?q=me%40example.com"));
+        assert_eq!(resolve("javascript:alert(1)"), generated("https://www.google.com/search?q=javascript%3Aalert%281%29"));
+        assert_eq!(resolve("c++ & rust"), generated("https://www.google.com/search?q=c%2B%2B+%26+rust"));
+        assert!(resolve_input("  ", &SearchEngine::default()).is_err());
+    }
+
+    #[test]
+    fn custom_search_engines_need_a_placeholder_and_http() {
+        let engine = SearchEngine::new("Local".to_string(), "http://localhost:8888/?q={searchTerms}".to_string()).unwrap();
+        assert_eq!(engine.search_url("a b"), "http://localhost:8888/?q=a+b");
+
+        assert!(SearchEngine::new("None".to_string(), "https://example.com/".to_string()).is_err());
+        assert!(SearchEngine::new("Bad".to_string(), "ftp://example.com/{searchTerms}".to_string()).is_err());
+    }
+}
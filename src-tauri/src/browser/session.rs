@@ -0,0 +1,321 @@
+// Session Persistence
+use super::engine::PageState;
+use super::groups::TabGroup;
+use super::navigation::NavigationState;
+use super::tabs::TabInfo;
+use super::windows::{SharedWindowRegistry, MAIN_WINDOW_ID};
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+pub const SESSION_FORMAT_VERSION: u32 = 2;
+
+const SESSION_FILE_NAME: &str = "session.json";
+const BACKUP_FILE_NAME: &str = "session.bak.json";
+const TEMP_FILE_NAME: &str = "session.json.tmp";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabSnapshot {
+    pub info: TabInfo,
+    pub navigation: NavigationState,
+    #[serde(default)]
+    pub page_state: PageState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowSnapshot {
+    #[serde(default = "main_window_id")]
+    pub id: String,
+    /// Tabs in strip order
+    pub tabs: Vec<TabSnapshot>,
+    #[serde(default)]
+    pub groups: Vec<TabGroup>,
+    pub active_tab_id: Option<String>,
+}
+
+fn main_window_id() -> String {
+    MAIN_WINDOW_ID.to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub version: u32,
+    pub saved_at: u64,
+    #[serde(default)]
+    pub windows: Vec<WindowSnapshot>,
+}
+
+impl SessionSnapshot {
+    pub fn new(windows: Vec<WindowSnapshot>) -> Self {
+        Self {
+            version: SESSION_FORMAT_VERSION,
+            saved_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            windows,
+        }
+    }
+
+    pub fn tab_count(&self) -> usize {
+        self.windows.iter().map(|window| window.tabs.len()).sum()
+    }
+}
+
+/// Reads and writes session snapshots inside a directory.
+///
+/// Every save goes to a temporary file that is renamed over `session.json`,
+/// and the previous snapshot is kept as `session.bak.json` so a damaged
+/// primary file can fall back to the last good one.
+pub struct SessionStore {
+    directory: PathBuf,
+    // Saves share the temporary file, so only one may run at a time
+    write_lock: Mutex<()>,
+}
+
+impl SessionStore {
+    pub fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn session_path(&self) -> PathBuf {
+        self.directory.join(SESSION_FILE_NAME)
+    }
+
+    pub fn backup_path(&self) -> PathBuf {
+        self.directory.join(BACKUP_FILE_NAME)
+    }
+
+    pub fn save(&self, snapshot: &SessionSnapshot) -> Result<()> {
+        let _write = self.write_lock.lock().unwrap();
+        fs::create_dir_all(&self.directory)
+            .with_context(|| format!("Failed to create session directory {:?}", self.directory))?;
+
+        let temp_path = self.directory.join(TEMP_FILE_NAME);
+        let data = serde_json::to_vec_pretty(snapshot)?;
+        {
+            let mut file = fs::File::create(&temp_path)?;
+            file.write_all(&data)?;
+            file.sync_all()?;
+        }
+
+        let session_path = self.session_path();
+        if session_path.exists() {
+            fs::rename(&session_path, self.backup_path())?;
+        }
+        fs::rename(&temp_path, &session_path)?;
+
+        info!("Saved session with {} windows and {} tabs", snapshot.windows.len(), snapshot.tab_count());
+        Ok(())
+    }
+
+    /// Loads the newest readable snapshot, falling back to the backup.
+    pub fn load(&self) -> Result<Option<SessionSnapshot>> {
+        for path in [self.session_path(), self.backup_path()] {
+            if !path.exists() {
+                continue;
+            }
+            match Self::read_snapshot(&path) {
+                Ok(snapshot) => {
+                    info!("Loaded session from {:?}", path);
+                    return Ok(Some(snapshot));
+                }
+                Err(e) => warn!("Ignoring unreadable session file {:?}: {}", path, e),
+            }
+        }
+        Ok(None)
+    }
+
+    fn read_snapshot(path: &Path) -> Result<SessionSnapshot> {
+        let snapshot: SessionSnapshot = serde_json::from_slice(&fs::read(path)?)?;
+        if snapshot.version > SESSION_FORMAT_VERSION {
+            anyhow::bail!("Unsupported session format version {}", snapshot.version);
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Coalesces save requests so bursts of tab changes cause a single write.
+#[derive(Clone)]
+pub struct SessionSaver {
+    store: Arc<SessionStore>,
+    windows: SharedWindowRegistry,
+    sender: mpsc::UnboundedSender<()>,
+}
+
+impl SessionSaver {
+    /// Returns the saver and the background task that performs the writes.
+    /// The caller is responsible for spawning the task on its runtime.
+    pub fn new(
+        store: Arc<SessionStore>,
+        windows: SharedWindowRegistry,
+        debounce: Duration,
+    ) -> (Self, impl Future<Output = ()> + Send + 'static) {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<()>();
+        let saver = Self {
+            store,
+            windows,
+            sender,
+        };
+
+        let task_saver = saver.clone();
+        let task = async move {
+            while receiver.recv().await.is_some() {
+                // Wait until no new request arrived for a whole debounce period
+                while let Ok(Some(())) = tokio::time::timeout(debounce, receiver.recv()).await {}
+                if let Err(e) = task_saver.save_now() {
+                    error!("Failed to save session: {}", e);
+                }
+            }
+        };
+
+        (saver, task)
+    }
+
+    pub fn schedule_save(&self) {
+        if self.sender.send(()).is_err() {
+            warn!("Session saver is no longer running");
+        }
+    }
+
+    pub fn save_now(&self) -> Result<()> {
+        let windows = match self.windows.lock() {
+            Ok(windows) => windows.snapshot(),
+            Err(_) => anyhow::bail!("Window registry lock poisoned"),
+        };
+        self.store.save(&SessionSnapshot::new(windows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::tabs::TabManager;
+    use crate::browser::windows::WindowRegistry;
+
+    fn temp_store() -> SessionStore {
+        SessionStore::new(std::env::temp_dir().join(format!("vishwakarma-session-{}", uuid::Uuid::new_v4())))
+    }
+
+    fn snapshot_with_tabs(urls: &[&str]) -> SessionSnapshot {
+        let mut manager = TabManager::new();
+        for url in urls {
+            manager.create_tab(Some(url.to_string()), None).unwrap();
+        }
+        SessionSnapshot::new(vec![manager.window_snapshot(MAIN_WINDOW_ID.to_string())])
+    }
+
+    fn tab_urls(snapshot: &SessionSnapshot) -> Vec<String> {
+        snapshot.windows[0].tabs.iter().map(|tab| tab.info.url.clone()).collect()
+    }
+
+    #[test]
+    fn saved_sessions_load_back() {
+        let store = temp_store();
+        assert!(store.load().unwrap().is_none());
+
+        let snapshot = snapshot_with_tabs(&["https://example.com/a", "https://example.com/b"]);
+        store.save(&snapshot).unwrap();
+        let loaded = store.load().unwrap().unwrap();
+
+        assert_eq!(tab_urls(&loaded), tab_urls(&snapshot));
+        assert_eq!(loaded.windows[0].active_tab_id, snapshot.windows[0].active_tab_id);
+        assert_eq!(loaded.windows[0].tabs[1].navigation.current_index, Some(0));
+        fs::remove_dir_all(&store.directory).unwrap();
+    }
+
+    #[test]
+    fn saving_replaces_the_file_and_keeps_a_backup() {
+        let store = temp_store();
+        store.save(&snapshot_with_tabs(&["https://example.com/first"])).unwrap();
+        assert!(!store.backup_path().exists());
+
+        store.save(&snapshot_with_tabs(&["https://example.com/second"])).unwrap();
+        assert!(!store.directory.join(TEMP_FILE_NAME).exists());
+        let backup = SessionStore::read_snapshot(&store.backup_path()).unwrap();
+        assert_eq!(tab_urls(&backup), vec!["https://example.com/first"]);
+        let current = SessionStore::read_snapshot(&store.session_path()).unwrap();
+        assert_eq!(tab_urls(&current), vec!["https://example.com/second"]);
+        fs::remove_dir_all(&store.directory).unwrap();
+    }
+
+    #[test]
+    fn corrupt_session_falls_back_to_the_backup() {
+        let store = temp_store();
+        store.save(&snapshot_with_tabs(&["https://example.com/first"])).unwrap();
+        store.save(&snapshot_with_tabs(&["https://example.com/second"])).unwrap();
+
+        // A crash halfway through writing
+        fs::write(store.session_path(), b"{\"version\": 2, \"windows\": [").unwrap();
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(tab_urls(&loaded), vec!["https://example.com/first"]);
+
+        let mut future = snapshot_with_tabs(&["https://example.com/future"]);
+        future.version = SESSION_FORMAT_VERSION + 1;
+        fs::write(store.backup_path(), serde_json::to_vec(&future).unwrap()).unwrap();
+        assert!(store.load().unwrap().is_none());
+        fs::remove_dir_all(&store.directory).unwrap();
+    }
+
+    #[test]
+    fn concurrent_saves_do_not_interleave() {
+        let store = Arc::new(temp_store());
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    let url = format!("https://example.com/{}", writer);
+                    let snapshot = snapshot_with_tabs(&[url.as_str()]);
+                    for _ in 0..10 {
+                        store.save(&snapshot).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert!(!store.directory.join(TEMP_FILE_NAME).exists());
+        let current = SessionStore::read_snapshot(&store.session_path()).unwrap();
+        assert_eq!(current.tab_count(), 1);
+        let backup = SessionStore::read_snapshot(&store.backup_path()).unwrap();
+        assert_eq!(backup.tab_count(), 1);
+        fs::remove_dir_all(&store.directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bursts_of_save_requests_are_written_once() {
+        let store = Arc::new(temp_store());
+        let mut registry = WindowRegistry::new();
+        registry.insert_window(MAIN_WINDOW_ID.to_string(), TabManager::new());
+        let windows = Arc::new(Mutex::new(registry));
+        let (saver, task) = SessionSaver::new(store.clone(), windows, Duration::from_millis(50));
+        tokio::spawn(task);
+
+        for _ in 0..5 {
+            saver.schedule_save();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!store.session_path().exists());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(store.session_path().exists());
+        // A second write would have moved the first to the backup
+        assert!(!store.backup_path().exists());
+
+        saver.schedule_save();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(store.backup_path().exists());
+        fs::remove_dir_all(&store.directory).unwrap();
+    }
+}
@@ -1,10 +1,17 @@
 // Tab Management System
-use super::engine::{EngineConfig, SharedEngine, create_engine};
+use super::discard::TabDiscardPolicy;
+use super::engine::{EngineConfig, EngineEvent, PageState, SharedEngine, create_engine};
+use super::groups::{TabGroup, TabGroupColor};
+use super::history::{now_secs, HistoryUpdate, PageTransition};
+use super::navigation::{NavigationController, NavigationRequest};
+use super::session::{TabSnapshot, WindowSnapshot};
 use anyhow::Result;
 use log::{info, warn, error};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,7 +22,20 @@ pub struct TabInfo {
     pub favicon_url: Option<String>,
     pub is_active: bool,
     pub is_loading: bool,
+    pub load_progress: f64,
     pub is_pinned: bool,
+    pub can_go_back: bool,
+    pub can_go_forward: bool,
+    /// Tab this one was opened from, if any
+    #[serde(default)]
+    pub opener_id: Option<String>,
+    #[serde(default)]
+    pub group_id: Option<String>,
+    /// The engine was dropped to save memory and is recreated on activation
+    #[serde(default)]
+    pub is_discarded: bool,
+    #[serde(default)]
+    pub is_audible: bool,
     pub created_at: u64,
 }
 
@@ -28,7 +48,14 @@ impl TabInfo {
             favicon_url: None,
             is_active: false,
             is_loading: false,
+            load_progress: 0.0,
             is_pinned: false,
+            can_go_back: false,
+            can_go_forward: false,
+            opener_id: None,
+            group_id: None,
+            is_discarded: false,
+            is_audible: false,
             created_at: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
@@ -37,48 +64,193 @@ impl TabInfo {
     }
 }
 
+/// An engine event tagged with the tab it came from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabEvent {
+    pub tab_id: String,
+    #[serde(flatten)]
+    pub event: EngineEvent,
+}
+
+// A load the tab asked its engine for and how to record it once it
+// commits. `None` marks reloads of discarded tabs, which are not visits.
+struct PendingVisit {
+    url: String,
+    transition: Option<PageTransition>,
+}
+
 pub struct BrowserTab {
     pub info: TabInfo,
-    pub engine: SharedEngine,
+    /// `None` while the tab is discarded
+    pub engine: Option<SharedEngine>,
+    navigation: NavigationController,
+    config: Option<EngineConfig>,
+    // Page state saved when the engine was discarded
+    discarded_page_state: PageState,
+    last_active_at: Instant,
+    // Loads asked for that the engine has not started yet, oldest first
+    pending_visits: VecDeque<PendingVisit>,
+    // The load the engine is working on, if the tab asked for it
+    current_visit: Option<PendingVisit>,
+    history_updates: Vec<HistoryUpdate>,
 }
 
 impl BrowserTab {
     pub fn new(url: String, config: Option<EngineConfig>) -> Result<Self> {
         let info = TabInfo::new(url.clone());
-        let engine = create_engine(config)?;
-        
+        let engine = create_engine(config.clone())?;
+        let mut tab = Self {
+            info,
+            engine: Some(engine),
+            navigation: NavigationController::new(),
+            config,
+            discarded_page_state: PageState::default(),
+            last_active_at: Instant::now(),
+            pending_visits: VecDeque::new(),
+            current_visit: None,
+            history_updates: Vec::new(),
+        };
+
         // Navigate to initial URL if provided and not empty
         if !url.is_empty() && url != "about:blank" {
-            if let Ok(mut engine_lock) = engine.lock() {
-                if let Err(e) = engine_lock.navigate_to(&url) {
-                    error!("Failed to navigate to initial URL {}: {}", url, e);
-                }
+            if let Err(e) = tab.navigate(&url) {
+                error!("Failed to navigate to initial URL {}: {}", url, e);
             }
         }
 
-        Ok(Self { info, engine })
+        Ok(tab)
     }
 
-    pub fn navigate(&mut self, url: &str) -> Result<()> {
-        info!("Tab {} navigating to: {}", self.info.id, url);
-        
-        self.info.is_loading = true;
-        self.info.url = url.to_string();
-        
-        if let Ok(mut engine) = self.engine.lock() {
-            engine.navigate_to(url)?;
-            self.info.is_loading = engine.is_loading();
+    /// Rebuilds a tab from a session snapshot. The tab starts out discarded
+    /// and loads its current entry once activated.
+    pub fn restore(snapshot: TabSnapshot, config: Option<EngineConfig>) -> Result<Self> {
+        let mut info = snapshot.info;
+        info.is_active = false;
+        info.is_loading = false;
+        info.load_progress = 0.0;
+        info.is_discarded = true;
+        info.is_audible = false;
+
+        let mut tab = Self {
+            info,
+            engine: None,
+            navigation: NavigationController::from_state(snapshot.navigation),
+            config,
+            discarded_page_state: snapshot.page_state,
+            last_active_at: Instant::now(),
+            pending_visits: VecDeque::new(),
+            current_visit: None,
+            history_updates: Vec::new(),
+        };
+        tab.sync_navigation_state();
+
+        Ok(tab)
+    }
+
+    pub fn snapshot(&self) -> TabSnapshot {
+        let page_state = match &self.engine {
+            Some(engine) => engine.lock().map(|engine| engine.page_state()).unwrap_or_default(),
+            None => self.discarded_page_state.clone(),
+        };
+        TabSnapshot {
+            info: self.info.clone(),
+            navigation: self.navigation.state(),
+            page_state,
         }
-        
+    }
+
+    pub fn is_discarded(&self) -> bool {
+        self.engine.is_none()
+    }
+
+    /// Drops the engine, keeping the tab's info and history.
+    pub fn discard(&mut self) {
+        let Some(engine) = self.engine.take() else {
+            return;
+        };
+        info!("Discarding tab {}", self.info.id);
+
+        if let Ok(engine) = engine.lock() {
+            self.discarded_page_state = engine.page_state();
+        }
+        self.info.is_discarded = true;
+        self.info.is_loading = false;
+        self.info.load_progress = 0.0;
+        self.info.is_audible = false;
+    }
+
+    /// Recreates the engine of a discarded tab and reloads its current
+    /// history entry.
+    pub fn reactivate(&mut self) -> Result<()> {
+        self.reactivate_as(None)
+    }
+
+    fn reactivate_as(&mut self, visit: Option<PageTransition>) -> Result<()> {
+        if self.engine.is_some() {
+            return Ok(());
+        }
+
+        let engine = self.ensure_engine()?;
+        if let Some(url) = self.navigation.current_url() {
+            info!("Reloading discarded tab {}: {}", self.info.id, url);
+            if let Err(e) = self.load_url(&url, visit) {
+                error!("Failed to reload {} in tab {}: {}", url, self.info.id, e);
+            }
+            if let Ok(mut engine) = engine.lock() {
+                engine.restore_page_state(&self.discarded_page_state);
+            }
+        }
+        self.discarded_page_state = PageState::default();
+
         Ok(())
     }
 
+    // Returns the live engine, creating one without loading anything if
+    // the tab was discarded
+    fn ensure_engine(&mut self) -> Result<SharedEngine> {
+        if let Some(engine) = &self.engine {
+            return Ok(engine.clone());
+        }
+        let engine = create_engine(self.config.clone())?;
+        self.engine = Some(engine.clone());
+        self.info.is_discarded = false;
+        Ok(engine)
+    }
+
+    pub fn navigate(&mut self, url: &str) -> Result<()> {
+        self.navigate_with_transition(url, PageTransition::Link)
+    }
+
+    /// Navigates, recording the visit in the history as `transition`
+    pub fn navigate_with_transition(&mut self, url: &str, transition: PageTransition) -> Result<()> {
+        info!("Tab {} navigating to: {}", self.info.id, url);
+
+        self.navigation.navigate(NavigationRequest::new(url.to_string()))?;
+        self.load_url(url, Some(transition))
+    }
+
     pub fn reload(&mut self) -> Result<()> {
         info!("Reloading tab {}", self.info.id);
-        
-        if let Ok(mut engine) = self.engine.lock() {
-            engine.reload()?;
-            self.info.is_loading = engine.is_loading();
+
+        if self.is_discarded() {
+            return self.reactivate_as(Some(PageTransition::Reload));
+        }
+        if let Some(engine) = &self.engine {
+            if let Ok(mut engine) = engine.lock() {
+                // Without a page there is nothing to reload
+                let Some(url) = engine.current_url().map(str::to_string) else {
+                    return engine.reload();
+                };
+                self.pending_visits.push_back(PendingVisit {
+                    url,
+                    transition: Some(PageTransition::Reload),
+                });
+                if let Err(e) = engine.reload() {
+                    self.pending_visits.pop_back();
+                    return Err(e);
+                }
+                self.info.is_loading = engine.is_loading();
+            }
         }
         
         Ok(())
@@ -87,45 +259,162 @@ impl BrowserTab {
     pub fn stop(&mut self) {
         info!("Stopping tab {}", self.info.id);
         
-        if let Ok(mut engine) = self.engine.lock() {
-            engine.stop_loading();
-            self.info.is_loading = false;
+        if let Some(engine) = &self.engine {
+            if let Ok(mut engine) = engine.lock() {
+                engine.stop_loading();
+                self.info.is_loading = false;
+                self.pending_visits.clear();
+                self.current_visit = None;
+            }
         }
     }
 
     pub fn go_back(&mut self) -> Result<bool> {
-        if let Ok(mut engine) = self.engine.lock() {
-            engine.go_back()
-        } else {
-            Ok(false)
+        match self.navigation.go_back() {
+            Some(url) => {
+                self.load_url(&url, Some(PageTransition::BackForward))?;
+                Ok(true)
+            }
+            None => Ok(false),
         }
     }
 
     pub fn go_forward(&mut self) -> Result<bool> {
-        if let Ok(mut engine) = self.engine.lock() {
-            engine.go_forward()
-        } else {
-            Ok(false)
+        match self.navigation.go_forward() {
+            Some(url) => {
+                self.load_url(&url, Some(PageTransition::BackForward))?;
+                Ok(true)
+            }
+            None => Ok(false),
         }
     }
 
     pub fn can_go_back(&self) -> bool {
-        if let Ok(engine) = self.engine.lock() {
-            engine.can_go_back()
-        } else {
-            false
-        }
+        self.navigation.can_go_back()
     }
 
     pub fn can_go_forward(&self) -> bool {
-        if let Ok(engine) = self.engine.lock() {
-            engine.can_go_forward()
-        } else {
-            false
+        self.navigation.can_go_forward()
+    }
+
+    // Loads a URL in the engine without touching the navigation history.
+    // `visit` is how the load is recorded once it commits.
+    fn load_url(&mut self, url: &str, visit: Option<PageTransition>) -> Result<()> {
+        self.info.is_loading = true;
+        self.info.url = url.to_string();
+        self.sync_navigation_state();
+
+        let engine = self.ensure_engine()?;
+        if let Ok(mut engine) = engine.lock() {
+            self.pending_visits.push_back(PendingVisit {
+                url: url.to_string(),
+                transition: visit,
+            });
+            if let Err(e) = engine.navigate_to(url) {
+                self.pending_visits.pop_back();
+                return Err(e);
+            }
+            self.info.is_loading = engine.is_loading();
+        }
+
+        Ok(())
+    }
+
+    fn sync_navigation_state(&mut self) {
+        self.info.can_go_back = self.navigation.can_go_back();
+        self.info.can_go_forward = self.navigation.can_go_forward();
+    }
+
+    /// Drains pending engine events and folds them into `info`.
+    pub fn process_engine_events(&mut self) -> Vec<EngineEvent> {
+        let events = match self.engine.as_ref().map(|engine| engine.lock()) {
+            Some(Ok(mut engine)) => engine.take_events(),
+            _ => return Vec::new(),
+        };
+
+        for event in &events {
+            self.apply_engine_event(event);
+        }
+        events
+    }
+
+    // Pairs a load the engine started with the request that asked for it.
+    // Requests queued before that one never started and are dropped; a load
+    // nobody asked for was started by the page.
+    fn start_visit(&mut self, url: &str) {
+        self.current_visit = self
+            .pending_visits
+            .iter()
+            .position(|visit| visit.url == url)
+            .and_then(|position| {
+                self.pending_visits.drain(..position);
+                self.pending_visits.pop_front()
+            });
+    }
+
+    // Loads the page started itself count as links
+    fn record_visit(&mut self, url: &str) {
+        let transition = match self.current_visit.take() {
+            Some(visit) => visit.transition,
+            None => Some(PageTransition::Link),
+        };
+        let Some(transition) = transition else {
+            return;
+        };
+        self.history_updates.push(HistoryUpdate::Visit {
+            url: url.to_string(),
+            transition,
+            visited_at: now_secs(),
+        });
+    }
+
+    /// Returns the history updates seen since the last call
+    pub fn take_history_updates(&mut self) -> Vec<HistoryUpdate> {
+        std::mem::take(&mut self.history_updates)
+    }
+
+    fn apply_engine_event(&mut self, event: &EngineEvent) {
+        match event {
+            EngineEvent::LoadStarted { url } => {
+                self.start_visit(url);
+                self.info.is_loading = true;
+                self.info.load_progress = 0.0;
+            }
+            EngineEvent::Committed { url } => {
+                self.info.url = url.clone();
+                self.record_visit(url);
+            }
+            EngineEvent::TitleChanged { title } => {
+                self.update_title(title.clone());
+                self.history_updates.push(HistoryUpdate::Title {
+                    url: self.info.url.clone(),
+                    title: title.clone(),
+                });
+            }
+            EngineEvent::FaviconChanged { url } => {
+                self.set_favicon(url.clone());
+            }
+            EngineEvent::Progress { progress } => {
+                self.info.load_progress = progress.clamp(0.0, 1.0);
+            }
+            EngineEvent::LoadFinished { .. } => {
+                self.info.is_loading = false;
+                self.info.load_progress = 1.0;
+            }
+            EngineEvent::LoadFailed { url, error } => {
+                warn!("Tab {} failed to load {}: {}", self.info.id, url, error);
+                self.current_visit = None;
+                self.info.is_loading = false;
+                self.info.load_progress = 0.0;
+            }
+            EngineEvent::AudioStateChanged { audible } => {
+                self.info.is_audible = *audible;
+            }
         }
     }
 
     pub fn update_title(&mut self, title: String) {
+        self.navigation.update_title(title.clone());
         self.info.title = title;
     }
 
@@ -138,11 +427,46 @@ impl BrowserTab {
     }
 }
 
+/// Which tab becomes active when the active tab is closed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TabSelectionPolicy {
+    /// The tab to the right of the closed one, or the left if it was last
+    #[default]
+    Next,
+    /// The tab to the left of the closed one, or the right if it was first
+    Previous,
+    /// The tab that opened the closed one, falling back to `Next`
+    Opener,
+    /// The most recently active remaining tab, falling back to `Next`
+    MostRecentlyUsed,
+}
+
+// How many closed tabs can be reopened
+const MAX_CLOSED_TABS: usize = 25;
+
+/// A closed tab kept around so it can be reopened where it was
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClosedTab {
+    pub snapshot: TabSnapshot,
+    pub index: usize,
+    pub closed_at: u64,
+    /// Engine settings the tab was created with
+    #[serde(default)]
+    pub config: Option<EngineConfig>,
+}
+
 pub struct TabManager {
     tabs: HashMap<String, BrowserTab>,
     tab_order: Vec<String>,
     active_tab_id: Option<String>,
     next_new_tab_index: usize,
+    closed_tabs: VecDeque<ClosedTab>,
+    selection_policy: TabSelectionPolicy,
+    // Tab ids ordered from most to least recently activated
+    mru: Vec<String>,
+    groups: HashMap<String, TabGroup>,
+    discard_policy: TabDiscardPolicy,
 }
 
 impl TabManager {
@@ -152,12 +476,29 @@ impl TabManager {
             tab_order: Vec::new(),
             active_tab_id: None,
             next_new_tab_index: 1,
+            closed_tabs: VecDeque::new(),
+            selection_policy: TabSelectionPolicy::default(),
+            mru: Vec::new(),
+            groups: HashMap::new(),
+            discard_policy: TabDiscardPolicy::default(),
         }
     }
 
     pub fn create_tab(&mut self, url: Option<String>, config: Option<EngineConfig>) -> Result<String> {
+        self.create_tab_with_opener(url, None, config)
+    }
+
+    /// Creates a tab that remembers which tab opened it, so closing it can
+    /// return to the opener.
+    pub fn create_tab_with_opener(
+        &mut self,
+        url: Option<String>,
+        opener_id: Option<String>,
+        config: Option<EngineConfig>,
+    ) -> Result<String> {
         let url = url.unwrap_or_else(|| "about:blank".to_string());
-        let tab = BrowserTab::new(url, config)?;
+        let mut tab = BrowserTab::new(url, config)?;
+        tab.info.opener_id = opener_id.filter(|id| self.tabs.contains_key(id));
         let tab_id = tab.info.id.clone();
         
         info!("Creating new tab: {}", tab_id);
@@ -174,7 +515,16 @@ impl TabManager {
         Ok(tab_id)
     }
 
+    /// Closes a tab. Pinned tabs are protected and must be unpinned or
+    /// closed with `force_close_tab`.
     pub fn close_tab(&mut self, tab_id: &str) -> Result<()> {
+        if self.tabs.get(tab_id).is_some_and(|tab| tab.info.is_pinned) {
+            return Err(anyhow::anyhow!("Tab is pinned: {}", tab_id));
+        }
+        self.force_close_tab(tab_id)
+    }
+
+    pub fn force_close_tab(&mut self, tab_id: &str) -> Result<()> {
         info!("Closing tab: {}", tab_id);
         
         if !self.tabs.contains_key(tab_id) {
@@ -182,17 +532,22 @@ impl TabManager {
             return Ok(());
         }
 
-        // Remove from tabs and order
-        self.tabs.remove(tab_id);
+        // Remove from tabs and order, remembering it for reopening
+        let index = self.tab_order.iter().position(|id| id == tab_id).unwrap_or(self.tab_order.len());
+        let mut opener_id = None;
+        if let Some(tab) = self.tabs.remove(tab_id) {
+            opener_id = tab.info.opener_id.clone();
+            self.remember_closed_tab(&tab, index);
+        }
         self.tab_order.retain(|id| id != tab_id);
+        self.mru.retain(|id| id != tab_id);
+        self.prune_empty_groups();
 
         // Handle active tab change
-        if self.active_tab_id.as_ref() == Some(&tab_id.to_string()) {
+        if self.active_tab_id.as_deref() == Some(tab_id) {
             self.active_tab_id = None;
-            
-            // Set new active tab (prefer next tab, fallback to previous)
-            if !self.tab_order.is_empty() {
-                let new_active_id = self.tab_order[0].clone();
+
+            if let Some(new_active_id) = self.select_tab_after_close(index, opener_id.as_deref()) {
                 self.set_active_tab(&new_active_id)?;
             }
         }
@@ -200,22 +555,167 @@ impl TabManager {
         Ok(())
     }
 
+    /// Removes a tab without closing it, so it can be inserted elsewhere
+    /// with its engine and history intact.
+    pub fn take_tab(&mut self, tab_id: &str) -> Result<BrowserTab> {
+        let index = self
+            .tab_order
+            .iter()
+            .position(|id| id == tab_id)
+            .ok_or_else(|| anyhow::anyhow!("Tab not found: {}", tab_id))?;
+        let mut tab = self
+            .tabs
+            .remove(tab_id)
+            .ok_or_else(|| anyhow::anyhow!("Tab not found: {}", tab_id))?;
+        self.tab_order.remove(index);
+        self.mru.retain(|id| id != tab_id);
+        self.prune_empty_groups();
+
+        if self.active_tab_id.as_deref() == Some(tab_id) {
+            self.active_tab_id = None;
+            if let Some(new_active_id) = self.select_tab_after_close(index, tab.info.opener_id.as_deref()) {
+                self.set_active_tab(&new_active_id)?;
+            }
+        }
+
+        tab.info.is_active = false;
+        Ok(tab)
+    }
+
+    /// Adopts a tab taken from another tab manager and activates it.
+    /// Group and opener links do not carry over.
+    pub fn insert_tab(&mut self, mut tab: BrowserTab, index: Option<usize>) -> Result<String> {
+        let tab_id = tab.info.id.clone();
+        if self.tabs.contains_key(&tab_id) {
+            return Err(anyhow::anyhow!("Tab already exists: {}", tab_id));
+        }
+        tab.info.group_id = None;
+        tab.info.opener_id = None;
+
+        let index = index.unwrap_or(self.tab_order.len());
+        let insert_index = self.snap_out_of_groups(self.clamp_insert_index(index, tab.info.is_pinned));
+        self.tabs.insert(tab_id.clone(), tab);
+        self.tab_order.insert(insert_index, tab_id.clone());
+        self.set_active_tab(&tab_id)?;
+
+        Ok(tab_id)
+    }
+
+    fn select_tab_after_close(&self, closed_index: usize, opener_id: Option<&str>) -> Option<String> {
+        let next = || {
+            self.tab_order
+                .get(closed_index)
+                .or_else(|| self.tab_order.last())
+                .cloned()
+        };
+
+        match self.selection_policy {
+            TabSelectionPolicy::Next => next(),
+            TabSelectionPolicy::Previous => closed_index
+                .checked_sub(1)
+                .and_then(|index| self.tab_order.get(index))
+                .or_else(|| self.tab_order.first())
+                .cloned(),
+            TabSelectionPolicy::Opener => opener_id
+                .filter(|id| self.tabs.contains_key(*id))
+                .map(str::to_string)
+                .or_else(next),
+            TabSelectionPolicy::MostRecentlyUsed => self.mru.first().cloned().or_else(next),
+        }
+    }
+
+    pub fn selection_policy(&self) -> TabSelectionPolicy {
+        self.selection_policy
+    }
+
+    pub fn set_selection_policy(&mut self, policy: TabSelectionPolicy) {
+        info!("Tab selection policy set to {:?}", policy);
+        self.selection_policy = policy;
+    }
+
+    fn remember_closed_tab(&mut self, tab: &BrowserTab, index: usize) {
+        // Blank tabs that never navigated are not worth reopening
+        let snapshot = tab.snapshot();
+        if snapshot.navigation.entries.is_empty() {
+            return;
+        }
+
+        self.closed_tabs.push_front(ClosedTab {
+            snapshot,
+            index,
+            closed_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            config: tab.config.clone(),
+        });
+        self.closed_tabs.truncate(MAX_CLOSED_TABS);
+    }
+
+    /// Reopens a recently closed tab at its old position, most recent first
+    /// when no id is given. Returns the id of the reopened tab.
+    pub fn reopen_closed_tab(&mut self, tab_id: Option<&str>) -> Result<String> {
+        let position = match tab_id {
+            Some(tab_id) => self
+                .closed_tabs
+                .iter()
+                .position(|closed| closed.snapshot.info.id == tab_id)
+                .ok_or_else(|| anyhow::anyhow!("Closed tab not found: {}", tab_id))?,
+            None => 0,
+        };
+        let closed = self
+            .closed_tabs
+            .remove(position)
+            .ok_or_else(|| anyhow::anyhow!("No recently closed tabs"))?;
+
+        let mut tab = BrowserTab::restore(closed.snapshot, closed.config)?;
+        let tab_id = tab.info.id.clone();
+        info!("Reopening closed tab: {}", tab_id);
+
+        // Rejoin the old group if it still exists
+        tab.info.group_id = tab.info.group_id.filter(|group_id| self.groups.contains_key(group_id));
+        let insert_index = match tab.info.group_id.as_deref().and_then(|group_id| self.group_span(group_id)) {
+            Some(span) => closed.index.clamp(span.start, span.end),
+            None => self.snap_out_of_groups(self.clamp_insert_index(closed.index, tab.info.is_pinned)),
+        };
+        self.tabs.insert(tab_id.clone(), tab);
+        self.tab_order.insert(insert_index, tab_id.clone());
+        self.set_active_tab(&tab_id)?;
+
+        Ok(tab_id)
+    }
+
+    /// Recently closed tabs, most recently closed first
+    pub fn get_closed_tabs(&self) -> Vec<&ClosedTab> {
+        self.closed_tabs.iter().collect()
+    }
+
     pub fn set_active_tab(&mut self, tab_id: &str) -> Result<()> {
         if !self.tabs.contains_key(tab_id) {
             return Err(anyhow::anyhow!("Tab not found: {}", tab_id));
         }
 
+        // Bring the new tab back first if it was discarded, so a failure
+        // leaves the current tab active
+        if let Some(new_tab) = self.tabs.get_mut(tab_id) {
+            new_tab.reactivate()?;
+        }
+
         // Deactivate current active tab
         if let Some(ref current_active_id) = self.active_tab_id {
             if let Some(current_tab) = self.tabs.get_mut(current_active_id) {
                 current_tab.info.is_active = false;
+                current_tab.last_active_at = Instant::now();
             }
         }
 
         // Activate new tab
         if let Some(new_tab) = self.tabs.get_mut(tab_id) {
             new_tab.info.is_active = true;
+            new_tab.last_active_at = Instant::now();
             self.active_tab_id = Some(tab_id.to_string());
+            self.mru.retain(|id| id != tab_id);
+            self.mru.insert(0, tab_id.to_string());
             info!("Set active tab: {}", tab_id);
         }
 
@@ -250,6 +750,184 @@ impl TabManager {
         self.active_tab_id.as_deref()
     }
 
+    /// Collects pending engine events from every tab, updating tab state.
+    pub fn process_engine_events(&mut self) -> Vec<TabEvent> {
+        let mut tab_events = Vec::new();
+        for tab_id in &self.tab_order {
+            if let Some(tab) = self.tabs.get_mut(tab_id) {
+                tab_events.extend(tab.process_engine_events().into_iter().map(|event| TabEvent {
+                    tab_id: tab_id.clone(),
+                    event,
+                }));
+            }
+        }
+        tab_events
+    }
+
+    /// Drains the history updates of every tab, in tab order
+    pub fn take_history_updates(&mut self) -> Vec<HistoryUpdate> {
+        let mut updates = Vec::new();
+        for tab_id in &self.tab_order {
+            if let Some(tab) = self.tabs.get_mut(tab_id) {
+                updates.extend(tab.take_history_updates());
+            }
+        }
+        updates
+    }
+
+    pub fn discard_policy(&self) -> &TabDiscardPolicy {
+        &self.discard_policy
+    }
+
+    pub fn set_discard_policy(&mut self, policy: TabDiscardPolicy) {
+        info!("Tab discard policy set to {:?}", policy);
+        self.discard_policy = policy;
+    }
+
+    /// Discards a background tab regardless of the discard policy.
+    pub fn discard_tab(&mut self, tab_id: &str) -> Result<()> {
+        if self.active_tab_id.as_deref() == Some(tab_id) {
+            return Err(anyhow::anyhow!("Cannot discard the active tab: {}", tab_id));
+        }
+        let tab = self
+            .tabs
+            .get_mut(tab_id)
+            .ok_or_else(|| anyhow::anyhow!("Tab not found: {}", tab_id))?;
+        tab.discard();
+        Ok(())
+    }
+
+    /// Discards background tabs that have been idle too long, then the least
+    /// recently used ones until no more than the allowed number of engines
+    /// remain. Returns the ids of the discarded tabs.
+    pub fn apply_discard_policy(&mut self) -> Vec<String> {
+        let mut discarded = Vec::new();
+
+        if let Some(idle_timeout) = self.discard_policy.idle_timeout() {
+            let now = Instant::now();
+            for tab_id in self.discard_candidates() {
+                let idle = self
+                    .tabs
+                    .get(&tab_id)
+                    .is_some_and(|tab| now.duration_since(tab.last_active_at) >= idle_timeout);
+                if idle {
+                    discarded.push(tab_id);
+                }
+            }
+            for tab_id in &discarded {
+                if let Some(tab) = self.tabs.get_mut(tab_id) {
+                    tab.discard();
+                }
+            }
+        }
+
+        if let Some(max_live_engines) = self.discard_policy.max_live_engines {
+            let mut live_engines = self.tabs.values().filter(|tab| !tab.is_discarded()).count();
+            for tab_id in self.discard_candidates() {
+                if live_engines <= max_live_engines {
+                    break;
+                }
+                if let Some(tab) = self.tabs.get_mut(&tab_id) {
+                    tab.discard();
+                    live_engines -= 1;
+                    discarded.push(tab_id);
+                }
+            }
+        }
+
+        discarded
+    }
+
+    /// Discards every background tab the policy does not exempt, for use
+    /// when the system is low on memory. Returns the ids of the discarded tabs.
+    pub fn discard_background_tabs(&mut self) -> Vec<String> {
+        let discarded = self.discard_candidates();
+        for tab_id in &discarded {
+            if let Some(tab) = self.tabs.get_mut(tab_id) {
+                tab.discard();
+            }
+        }
+        discarded
+    }
+
+    // Live background tabs the policy allows discarding, least recently
+    // used first
+    fn discard_candidates(&self) -> Vec<String> {
+        let mut candidates: Vec<&BrowserTab> = self
+            .tabs
+            .values()
+            .filter(|tab| !tab.info.is_active && !tab.is_discarded())
+            .filter(|tab| !(self.discard_policy.exempt_pinned && tab.info.is_pinned))
+            .filter(|tab| !(self.discard_policy.exempt_audible && tab.info.is_audible))
+            .collect();
+        // Tabs that were never activated count as the least recently used
+        candidates.sort_by_key(|tab| {
+            std::cmp::Reverse(self.mru.iter().position(|id| *id == tab.info.id).unwrap_or(usize::MAX))
+        });
+        candidates.into_iter().map(|tab| tab.info.id.clone()).collect()
+    }
+
+    pub fn window_snapshot(&self, window_id: String) -> WindowSnapshot {
+        let tabs = self
+            .tab_order
+            .iter()
+            .filter_map(|id| self.tabs.get(id))
+            .map(BrowserTab::snapshot)
+            .collect();
+        WindowSnapshot {
+            id: window_id,
+            tabs,
+            groups: self.get_tab_groups().into_iter().cloned().collect(),
+            active_tab_id: self.active_tab_id.clone(),
+        }
+    }
+
+    /// Replaces every open tab with the tabs recorded in `snapshot`, whose
+    /// engines are created with `config` once activated.
+    pub fn restore_window(&mut self, snapshot: WindowSnapshot, config: Option<EngineConfig>) -> Result<()> {
+        self.tabs.clear();
+        self.tab_order.clear();
+        self.mru.clear();
+        self.groups = snapshot
+            .groups
+            .into_iter()
+            .map(|group| (group.id.clone(), group))
+            .collect();
+        self.active_tab_id = None;
+
+        for tab_snapshot in snapshot.tabs {
+            match BrowserTab::restore(tab_snapshot, config.clone()) {
+                Ok(mut tab) => {
+                    if tab.info.is_pinned || !tab.info.group_id.as_ref().is_some_and(|id| self.groups.contains_key(id)) {
+                        tab.info.group_id = None;
+                    }
+                    let tab_id = tab.info.id.clone();
+                    if self.tabs.insert(tab_id.clone(), tab).is_none() {
+                        self.tab_order.push(tab_id);
+                    }
+                }
+                Err(e) => error!("Failed to restore tab: {}", e),
+            }
+        }
+
+        // Older or hand-edited snapshots may interleave pinned tabs
+        let tabs = &self.tabs;
+        self.tab_order.sort_by_key(|id| !tabs.get(id).is_some_and(|tab| tab.info.is_pinned));
+        self.normalize_groups();
+        self.prune_empty_groups();
+
+        let active_tab_id = snapshot
+            .active_tab_id
+            .filter(|id| self.tabs.contains_key(id))
+            .or_else(|| self.tab_order.first().cloned());
+        if let Some(tab_id) = active_tab_id {
+            self.set_active_tab(&tab_id)?;
+        }
+
+        info!("Restored window {} with {} tabs", snapshot.id, self.tab_order.len());
+        Ok(())
+    }
+
     pub fn tab_count(&self) -> usize {
         self.tabs.len()
     }
@@ -259,16 +937,315 @@ impl TabManager {
             return Err(anyhow::anyhow!("Tab not found: {}", tab_id));
         }
 
+        // Grouped tabs reorder inside their group, or take the group along
+        if let Some(group_id) = self.group_of(tab_id).map(str::to_string) {
+            let span = self.group_span(&group_id).unwrap_or(0..0);
+            if !span.contains(&new_index) {
+                return self.move_tab_group(&group_id, new_index);
+            }
+            self.tab_order.retain(|id| id != tab_id);
+            self.tab_order.insert(new_index, tab_id.to_string());
+            info!("Moved tab {} to position {} within group {}", tab_id, new_index, group_id);
+            return Ok(());
+        }
+
         // Remove from current position
         self.tab_order.retain(|id| id != tab_id);
         
-        // Insert at new position
-        let insert_index = new_index.min(self.tab_order.len());
+        // Insert at new position, without crossing the pinned boundary
+        // or splitting a group
+        let is_pinned = self.tabs.get(tab_id).is_some_and(|tab| tab.info.is_pinned);
+        let insert_index = self.snap_out_of_groups(self.clamp_insert_index(new_index, is_pinned));
         self.tab_order.insert(insert_index, tab_id.to_string());
         
-        info!("Moved tab {} to position {}", tab_id, new_index);
+        info!("Moved tab {} to position {}", tab_id, insert_index);
+        Ok(())
+    }
+
+    /// Pins a tab, moving it to the end of the pinned tabs.
+    pub fn pin_tab(&mut self, tab_id: &str) -> Result<()> {
+        self.set_tab_pinned(tab_id, true)
+    }
+
+    /// Unpins a tab, moving it to the start of the unpinned tabs.
+    pub fn unpin_tab(&mut self, tab_id: &str) -> Result<()> {
+        self.set_tab_pinned(tab_id, false)
+    }
+
+    fn set_tab_pinned(&mut self, tab_id: &str, pinned: bool) -> Result<()> {
+        let tab = self
+            .tabs
+            .get_mut(tab_id)
+            .ok_or_else(|| anyhow::anyhow!("Tab not found: {}", tab_id))?;
+        if tab.info.is_pinned == pinned {
+            return Ok(());
+        }
+        tab.set_pinned(pinned);
+        if pinned {
+            tab.info.group_id = None;
+        }
+
+        self.tab_order.retain(|id| id != tab_id);
+        let insert_index = self.pinned_count();
+        self.tab_order.insert(insert_index, tab_id.to_string());
+
+        self.prune_empty_groups();
+
+        info!("{} tab {}", if pinned { "Pinned" } else { "Unpinned" }, tab_id);
+        Ok(())
+    }
+
+    pub fn pinned_count(&self) -> usize {
+        self.tab_order
+            .iter()
+            .filter(|id| self.tabs.get(*id).is_some_and(|tab| tab.info.is_pinned))
+            .count()
+    }
+
+    // Keeps pinned tabs in front of unpinned ones. Expects the tab being
+    // placed to be absent from `tab_order`.
+    fn clamp_insert_index(&self, index: usize, pinned: bool) -> usize {
+        let pinned_count = self.pinned_count();
+        if pinned {
+            index.min(pinned_count)
+        } else {
+            index.clamp(pinned_count, self.tab_order.len())
+        }
+    }
+
+    /// Groups the given tabs, gathering them next to the left-most one.
+    pub fn create_tab_group(&mut self, tab_ids: &[String], name: String, color: TabGroupColor) -> Result<String> {
+        if tab_ids.is_empty() {
+            return Err(anyhow::anyhow!("A tab group needs at least one tab"));
+        }
+        for tab_id in tab_ids {
+            let tab = self
+                .tabs
+                .get(tab_id)
+                .ok_or_else(|| anyhow::anyhow!("Tab not found: {}", tab_id))?;
+            if tab.info.is_pinned {
+                return Err(anyhow::anyhow!("Pinned tabs cannot be grouped: {}", tab_id));
+            }
+        }
+
+        let group = TabGroup::new(name, color);
+        let group_id = group.id.clone();
+        self.groups.insert(group_id.clone(), group);
+
+        // Keep the tabs in their current strip order
+        let anchor = self
+            .tab_order
+            .iter()
+            .position(|id| tab_ids.contains(id))
+            .unwrap_or(self.tab_order.len());
+        let members: Vec<String> = self
+            .tab_order
+            .iter()
+            .filter(|id| tab_ids.contains(id))
+            .cloned()
+            .collect();
+        self.tab_order.retain(|id| !tab_ids.contains(id));
+        for tab_id in &members {
+            if let Some(tab) = self.tabs.get_mut(tab_id) {
+                tab.info.group_id = Some(group_id.clone());
+            }
+        }
+
+        let insert_index = self.snap_out_of_groups(anchor.min(self.tab_order.len()));
+        self.tab_order.splice(insert_index..insert_index, members);
+        self.prune_empty_groups();
+
+        info!("Created tab group {} with {} tabs", group_id, tab_ids.len());
+        Ok(group_id)
+    }
+
+    /// Adds a tab to the end of a group, taking it out of any other group.
+    pub fn add_tab_to_group(&mut self, tab_id: &str, group_id: &str) -> Result<()> {
+        if !self.groups.contains_key(group_id) {
+            return Err(anyhow::anyhow!("Tab group not found: {}", group_id));
+        }
+        let tab = self
+            .tabs
+            .get_mut(tab_id)
+            .ok_or_else(|| anyhow::anyhow!("Tab not found: {}", tab_id))?;
+        if tab.info.is_pinned {
+            return Err(anyhow::anyhow!("Pinned tabs cannot be grouped: {}", tab_id));
+        }
+        if tab.info.group_id.as_deref() == Some(group_id) {
+            return Ok(());
+        }
+        tab.info.group_id = Some(group_id.to_string());
+
+        self.tab_order.retain(|id| id != tab_id);
+        let insert_index = self
+            .group_span(group_id)
+            .map_or(self.tab_order.len(), |span| span.end);
+        self.tab_order.insert(insert_index, tab_id.to_string());
+        self.prune_empty_groups();
+
+        info!("Added tab {} to group {}", tab_id, group_id);
         Ok(())
     }
+
+    /// Takes a tab out of its group, placing it right after the group.
+    pub fn remove_tab_from_group(&mut self, tab_id: &str) -> Result<()> {
+        let group_id = match self.group_of(tab_id) {
+            Some(group_id) => group_id.to_string(),
+            None => return Ok(()),
+        };
+        let span = self.group_span(&group_id).unwrap_or(0..0);
+
+        self.tab_order.retain(|id| id != tab_id);
+        if let Some(tab) = self.tabs.get_mut(tab_id) {
+            tab.info.group_id = None;
+        }
+        self.tab_order.insert(span.end.saturating_sub(1), tab_id.to_string());
+        self.prune_empty_groups();
+
+        info!("Removed tab {} from group {}", tab_id, group_id);
+        Ok(())
+    }
+
+    /// Dissolves a group, leaving its tabs where they are.
+    pub fn ungroup_tab_group(&mut self, group_id: &str) -> Result<()> {
+        if self.groups.remove(group_id).is_none() {
+            return Err(anyhow::anyhow!("Tab group not found: {}", group_id));
+        }
+        for tab in self.tabs.values_mut() {
+            if tab.info.group_id.as_deref() == Some(group_id) {
+                tab.info.group_id = None;
+            }
+        }
+
+        info!("Ungrouped tab group {}", group_id);
+        Ok(())
+    }
+
+    /// Closes every tab in a group, and with it the group.
+    pub fn close_tab_group(&mut self, group_id: &str) -> Result<()> {
+        if !self.groups.contains_key(group_id) {
+            return Err(anyhow::anyhow!("Tab group not found: {}", group_id));
+        }
+        for tab_id in self.get_group_tab_ids(group_id) {
+            self.close_tab(&tab_id)?;
+        }
+
+        info!("Closed tab group {}", group_id);
+        Ok(())
+    }
+
+    pub fn rename_tab_group(&mut self, group_id: &str, name: String) -> Result<()> {
+        self.get_group_mut(group_id)?.name = name;
+        Ok(())
+    }
+
+    pub fn set_tab_group_color(&mut self, group_id: &str, color: TabGroupColor) -> Result<()> {
+        self.get_group_mut(group_id)?.color = color;
+        Ok(())
+    }
+
+    pub fn set_tab_group_collapsed(&mut self, group_id: &str, collapsed: bool) -> Result<()> {
+        self.get_group_mut(group_id)?.collapsed = collapsed;
+        Ok(())
+    }
+
+    /// Moves all tabs of a group together, keeping their order.
+    pub fn move_tab_group(&mut self, group_id: &str, new_index: usize) -> Result<()> {
+        let span = self
+            .group_span(group_id)
+            .ok_or_else(|| anyhow::anyhow!("Tab group not found: {}", group_id))?;
+
+        let members: Vec<String> = self.tab_order.drain(span).collect();
+        let insert_index = self.snap_out_of_groups(self.clamp_insert_index(new_index, false));
+        self.tab_order.splice(insert_index..insert_index, members);
+
+        info!("Moved tab group {} to position {}", group_id, insert_index);
+        Ok(())
+    }
+
+    /// Groups in tab strip order
+    pub fn get_tab_groups(&self) -> Vec<&TabGroup> {
+        let mut seen = HashSet::new();
+        self.tab_order
+            .iter()
+            .filter_map(|id| self.group_of(id))
+            .filter(|group_id| seen.insert(*group_id))
+            .filter_map(|group_id| self.groups.get(group_id))
+            .collect()
+    }
+
+    pub fn get_group_tab_ids(&self, group_id: &str) -> Vec<String> {
+        self.tab_order
+            .iter()
+            .filter(|id| self.group_of(id) == Some(group_id))
+            .cloned()
+            .collect()
+    }
+
+    fn get_group_mut(&mut self, group_id: &str) -> Result<&mut TabGroup> {
+        self.groups
+            .get_mut(group_id)
+            .ok_or_else(|| anyhow::anyhow!("Tab group not found: {}", group_id))
+    }
+
+    fn group_of(&self, tab_id: &str) -> Option<&str> {
+        self.tabs.get(tab_id).and_then(|tab| tab.info.group_id.as_deref())
+    }
+
+    // Range of `tab_order` covered by a group, which is always contiguous
+    fn group_span(&self, group_id: &str) -> Option<Range<usize>> {
+        let start = self
+            .tab_order
+            .iter()
+            .position(|id| self.group_of(id) == Some(group_id))?;
+        let len = self.tab_order[start..]
+            .iter()
+            .take_while(|id| self.group_of(id) == Some(group_id))
+            .count();
+        Some(start..start + len)
+    }
+
+    // Moves an insertion point that would split a group to just after it
+    fn snap_out_of_groups(&self, index: usize) -> usize {
+        if index == 0 || index >= self.tab_order.len() {
+            return index;
+        }
+        match (self.group_of(&self.tab_order[index - 1]), self.group_of(&self.tab_order[index])) {
+            (Some(before), Some(after)) if before == after => {
+                self.group_span(before).map_or(index, |span| span.end)
+            }
+            _ => index,
+        }
+    }
+
+    // Gathers each group's tabs next to its first tab
+    fn normalize_groups(&mut self) {
+        let mut order = Vec::with_capacity(self.tab_order.len());
+        let mut placed = HashSet::new();
+        for tab_id in &self.tab_order {
+            match self.group_of(tab_id) {
+                Some(group_id) => {
+                    if placed.insert(group_id) {
+                        order.extend(
+                            self.tab_order
+                                .iter()
+                                .filter(|id| self.group_of(id) == Some(group_id))
+                                .cloned(),
+                        );
+                    }
+                }
+                None => order.push(tab_id.clone()),
+            }
+        }
+        self.tab_order = order;
+    }
+
+    fn prune_empty_groups(&mut self) {
+        let tabs = &self.tabs;
+        self.groups.retain(|group_id, _| {
+            tabs.values().any(|tab| tab.info.group_id.as_deref() == Some(group_id.as_str()))
+        });
+    }
 }
 
 impl Default for TabManager {
@@ -279,3 +1256,554 @@ impl Default for TabManager {
 
 // Thread-safe wrapper for the tab manager
 pub type SharedTabManager = Arc<Mutex<TabManager>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager_with_tabs(count: usize) -> (TabManager, Vec<String>) {
+        let mut manager = TabManager::new();
+        let ids = (0..count)
+            .map(|i| manager.create_tab(Some(format!("https://example.com/{}", i)), None).unwrap())
+            .collect();
+        (manager, ids)
+    }
+
+    #[test]
+    fn next_policy_activates_right_neighbour() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        manager.set_active_tab(&ids[1]).unwrap();
+
+        manager.close_tab(&ids[1]).unwrap();
+
+        assert_eq!(manager.get_active_tab_id(), Some(ids[2].as_str()));
+    }
+
+    #[test]
+    fn next_policy_falls_back_to_left_neighbour_at_end() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        manager.set_active_tab(&ids[2]).unwrap();
+
+        manager.close_tab(&ids[2]).unwrap();
+
+        assert_eq!(manager.get_active_tab_id(), Some(ids[1].as_str()));
+    }
+
+    #[test]
+    fn previous_policy_activates_left_neighbour() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        manager.set_selection_policy(TabSelectionPolicy::Previous);
+        manager.set_active_tab(&ids[1]).unwrap();
+
+        manager.close_tab(&ids[1]).unwrap();
+
+        assert_eq!(manager.get_active_tab_id(), Some(ids[0].as_str()));
+    }
+
+    #[test]
+    fn previous_policy_falls_back_to_right_neighbour_at_start() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        manager.set_selection_policy(TabSelectionPolicy::Previous);
+
+        manager.close_tab(&ids[0]).unwrap();
+
+        assert_eq!(manager.get_active_tab_id(), Some(ids[1].as_str()));
+    }
+
+    #[test]
+    fn opener_policy_returns_to_opener() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        manager.set_selection_policy(TabSelectionPolicy::Opener);
+        let child = manager
+            .create_tab_with_opener(Some("https://example.com/child".to_string()), Some(ids[0].clone()), None)
+            .unwrap();
+        manager.set_active_tab(&child).unwrap();
+
+        manager.close_tab(&child).unwrap();
+
+        assert_eq!(manager.get_active_tab_id(), Some(ids[0].as_str()));
+    }
+
+    #[test]
+    fn opener_policy_falls_back_to_next_without_opener() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        manager.set_selection_policy(TabSelectionPolicy::Opener);
+        let child = manager
+            .create_tab_with_opener(Some("https://example.com/child".to_string()), Some(ids[2].clone()), None)
+            .unwrap();
+        manager.close_tab(&ids[2]).unwrap();
+        manager.set_active_tab(&ids[1]).unwrap();
+
+        manager.close_tab(&ids[1]).unwrap();
+
+        assert_eq!(manager.get_active_tab_id(), Some(child.as_str()));
+    }
+
+    #[test]
+    fn opener_policy_falls_back_to_next_when_opener_was_closed() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        manager.set_selection_policy(TabSelectionPolicy::Opener);
+        let child = manager
+            .create_tab_with_opener(Some("https://example.com/child".to_string()), Some(ids[1].clone()), None)
+            .unwrap();
+        manager.close_tab(&ids[1]).unwrap();
+        manager.set_active_tab(&child).unwrap();
+
+        manager.close_tab(&child).unwrap();
+
+        // The child was last, so `Next` takes its left neighbour
+        assert_eq!(manager.get_active_tab_id(), Some(ids[2].as_str()));
+    }
+
+    #[test]
+    fn mru_policy_returns_to_previously_active_tab() {
+        let (mut manager, ids) = manager_with_tabs(4);
+        manager.set_selection_policy(TabSelectionPolicy::MostRecentlyUsed);
+        manager.set_active_tab(&ids[3]).unwrap();
+        manager.set_active_tab(&ids[1]).unwrap();
+        manager.set_active_tab(&ids[2]).unwrap();
+
+        manager.close_tab(&ids[2]).unwrap();
+        assert_eq!(manager.get_active_tab_id(), Some(ids[1].as_str()));
+
+        manager.close_tab(&ids[1]).unwrap();
+        assert_eq!(manager.get_active_tab_id(), Some(ids[3].as_str()));
+    }
+
+    #[test]
+    fn closing_inactive_tab_keeps_active_tab() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        manager.set_active_tab(&ids[2]).unwrap();
+
+        manager.close_tab(&ids[0]).unwrap();
+
+        assert_eq!(manager.get_active_tab_id(), Some(ids[2].as_str()));
+    }
+
+    #[test]
+    fn closing_last_tab_clears_active_tab() {
+        let (mut manager, ids) = manager_with_tabs(1);
+
+        manager.close_tab(&ids[0]).unwrap();
+
+        assert_eq!(manager.get_active_tab_id(), None);
+    }
+
+    #[test]
+    fn pinned_tabs_stay_in_front() {
+        let (mut manager, ids) = manager_with_tabs(3);
+
+        manager.pin_tab(&ids[2]).unwrap();
+        manager.pin_tab(&ids[1]).unwrap();
+        let order: Vec<String> = manager.get_all_tab_info().into_iter().map(|info| info.id).collect();
+        assert_eq!(order, vec![ids[2].clone(), ids[1].clone(), ids[0].clone()]);
+
+        manager.move_tab(&ids[0], 0).unwrap();
+        manager.move_tab(&ids[2], 5).unwrap();
+        let order: Vec<String> = manager.get_all_tab_info().into_iter().map(|info| info.id).collect();
+        assert_eq!(order, vec![ids[1].clone(), ids[2].clone(), ids[0].clone()]);
+
+        manager.unpin_tab(&ids[1]).unwrap();
+        let order: Vec<String> = manager.get_all_tab_info().into_iter().map(|info| info.id).collect();
+        assert_eq!(order, vec![ids[2].clone(), ids[1].clone(), ids[0].clone()]);
+    }
+
+    #[test]
+    fn pinned_tabs_need_force_to_close() {
+        let (mut manager, ids) = manager_with_tabs(2);
+        manager.pin_tab(&ids[0]).unwrap();
+
+        assert!(manager.close_tab(&ids[0]).is_err());
+        assert_eq!(manager.tab_count(), 2);
+
+        manager.force_close_tab(&ids[0]).unwrap();
+        assert_eq!(manager.tab_count(), 1);
+    }
+
+    #[test]
+    fn pinned_state_survives_session_restore() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        manager.pin_tab(&ids[1]).unwrap();
+
+        let mut restored = TabManager::new();
+        restored.restore_window(manager.window_snapshot("main".to_string()), None).unwrap();
+
+        let infos = restored.get_all_tab_info();
+        assert_eq!(infos[0].id, ids[1]);
+        assert!(infos[0].is_pinned);
+        assert!(!infos[1].is_pinned);
+    }
+
+    #[test]
+    fn restored_background_tabs_stay_discarded() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        manager.set_active_tab(&ids[1]).unwrap();
+
+        let mut restored = TabManager::new();
+        restored
+            .restore_window(manager.window_snapshot("main".to_string()), Some(small_viewport()))
+            .unwrap();
+
+        assert_eq!(restored.get_active_tab_id(), Some(ids[1].as_str()));
+        assert!(!restored.get_tab_info(&ids[1]).unwrap().is_discarded);
+        assert!(restored.get_tab_info(&ids[0]).unwrap().is_discarded);
+        assert!(restored.get_tab_info(&ids[2]).unwrap().is_discarded);
+
+        restored.set_active_tab(&ids[2]).unwrap();
+        let tab = restored.get_tab(&ids[2]).unwrap();
+        assert!(!tab.is_discarded());
+        assert_eq!(engine_url(tab).as_deref(), Some("https://example.com/2"));
+        assert_eq!(viewport(tab), (640, 480));
+    }
+
+    fn order(manager: &TabManager) -> Vec<String> {
+        manager.get_all_tab_info().into_iter().map(|info| info.id).collect()
+    }
+
+    #[test]
+    fn creating_group_gathers_tabs() {
+        let (mut manager, ids) = manager_with_tabs(4);
+
+        let group_id = manager
+            .create_tab_group(&[ids[3].clone(), ids[1].clone()], "Work".to_string(), TabGroupColor::Blue)
+            .unwrap();
+
+        assert_eq!(order(&manager), vec![ids[0].clone(), ids[1].clone(), ids[3].clone(), ids[2].clone()]);
+        assert_eq!(manager.get_group_tab_ids(&group_id), vec![ids[1].clone(), ids[3].clone()]);
+    }
+
+    #[test]
+    fn moving_grouped_tab_outside_group_moves_whole_group() {
+        let (mut manager, ids) = manager_with_tabs(4);
+        manager
+            .create_tab_group(&[ids[0].clone(), ids[1].clone()], "Docs".to_string(), TabGroupColor::Green)
+            .unwrap();
+
+        manager.move_tab(&ids[0], 3).unwrap();
+        assert_eq!(order(&manager), vec![ids[2].clone(), ids[3].clone(), ids[0].clone(), ids[1].clone()]);
+
+        manager.move_tab(&ids[1], 2).unwrap();
+        assert_eq!(order(&manager), vec![ids[2].clone(), ids[3].clone(), ids[1].clone(), ids[0].clone()]);
+    }
+
+    #[test]
+    fn ungrouped_tab_cannot_split_group() {
+        let (mut manager, ids) = manager_with_tabs(4);
+        manager
+            .create_tab_group(&[ids[1].clone(), ids[2].clone()], "Docs".to_string(), TabGroupColor::Red)
+            .unwrap();
+
+        manager.move_tab(&ids[0], 1).unwrap();
+
+        assert_eq!(order(&manager), vec![ids[1].clone(), ids[2].clone(), ids[0].clone(), ids[3].clone()]);
+    }
+
+    #[test]
+    fn empty_groups_disappear() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        let group_id = manager
+            .create_tab_group(&[ids[1].clone()], "Solo".to_string(), TabGroupColor::Grey)
+            .unwrap();
+
+        manager.remove_tab_from_group(&ids[1]).unwrap();
+
+        assert!(manager.get_tab_groups().is_empty());
+        assert!(manager.rename_tab_group(&group_id, "Gone".to_string()).is_err());
+    }
+
+    #[test]
+    fn closing_group_closes_its_tabs() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        let group_id = manager
+            .create_tab_group(&[ids[0].clone(), ids[2].clone()], "Temp".to_string(), TabGroupColor::Cyan)
+            .unwrap();
+
+        manager.close_tab_group(&group_id).unwrap();
+
+        assert_eq!(order(&manager), vec![ids[1].clone()]);
+        assert!(manager.get_tab_groups().is_empty());
+    }
+
+    #[test]
+    fn unknown_opener_is_ignored() {
+        let (mut manager, _) = manager_with_tabs(1);
+
+        let child = manager
+            .create_tab_with_opener(None, Some("missing".to_string()), None)
+            .unwrap();
+
+        assert_eq!(manager.get_tab_info(&child).unwrap().opener_id, None);
+    }
+
+    #[test]
+    fn moved_tab_leaves_group_and_activates_in_target() {
+        let (mut source, ids) = manager_with_tabs(3);
+        source
+            .create_tab_group(&[ids[1].clone(), ids[2].clone()], "Work".to_string(), TabGroupColor::Blue)
+            .unwrap();
+        source.set_active_tab(&ids[1]).unwrap();
+        let (mut target, target_ids) = manager_with_tabs(2);
+
+        let tab = source.take_tab(&ids[1]).unwrap();
+        target.insert_tab(tab, Some(1)).unwrap();
+
+        assert_eq!(order(&source), vec![ids[0].clone(), ids[2].clone()]);
+        assert_eq!(source.get_active_tab_id(), Some(ids[2].as_str()));
+        assert_eq!(order(&target), vec![target_ids[0].clone(), ids[1].clone(), target_ids[1].clone()]);
+        assert_eq!(target.get_active_tab_id(), Some(ids[1].as_str()));
+        assert_eq!(target.get_tab_info(&ids[1]).unwrap().group_id, None);
+    }
+
+    #[test]
+    fn engine_events_are_folded_into_tab_info() {
+        let (mut manager, ids) = manager_with_tabs(1);
+        manager.process_engine_events();
+
+        let tab = manager.get_tab(&ids[0]).unwrap();
+        tab.navigate("https://example.com/page").unwrap();
+        let info = manager.get_tab_info(&ids[0]).unwrap();
+        assert!(info.is_loading);
+        assert_eq!(info.title, "example.com");
+
+        let events = manager.process_engine_events();
+        assert!(events.iter().all(|event| event.tab_id == ids[0]));
+        assert!(matches!(events.last().unwrap().event, EngineEvent::LoadFinished { .. }));
+        let info = manager.get_tab_info(&ids[0]).unwrap();
+        assert!(!info.is_loading);
+        assert_eq!(info.load_progress, 1.0);
+        assert_eq!(info.url, "https://example.com/page");
+        assert_eq!(info.favicon_url.as_deref(), Some("https://example.com/favicon.ico"));
+
+        manager.get_tab(&ids[0]).unwrap().navigate("https://broken.invalid/").unwrap();
+        let events = manager.process_engine_events();
+        assert!(matches!(
+            &events.last().unwrap().event,
+            EngineEvent::LoadFailed { url, .. } if url == "https://broken.invalid/"
+        ));
+        let info = manager.get_tab_info(&ids[0]).unwrap();
+        assert!(!info.is_loading);
+        assert_eq!(info.load_progress, 0.0);
+        assert_eq!(info.title, "example.com");
+        assert!(manager.process_engine_events().is_empty());
+    }
+
+    fn engine_url(tab: &BrowserTab) -> Option<String> {
+        let engine = tab.engine.clone().unwrap();
+        let url = engine.lock().unwrap().current_url().map(str::to_string);
+        url
+    }
+
+    #[test]
+    fn back_and_forward_follow_the_tab_history() {
+        let (mut manager, ids) = manager_with_tabs(1);
+        let tab = manager.get_tab(&ids[0]).unwrap();
+        assert!(!tab.can_go_back() && !tab.can_go_forward());
+        tab.navigate("https://example.com/a").unwrap();
+        tab.navigate("https://example.com/b").unwrap();
+
+        assert!(tab.go_back().unwrap());
+        assert!(tab.go_back().unwrap());
+        assert!(!tab.go_back().unwrap());
+        assert!(!tab.can_go_back() && tab.can_go_forward());
+        assert!(!tab.info.can_go_back && tab.info.can_go_forward);
+        assert_eq!(engine_url(tab).as_deref(), Some("https://example.com/0"));
+
+        assert!(tab.go_forward().unwrap());
+        assert!(tab.can_go_back() && tab.can_go_forward());
+        assert_eq!(engine_url(tab).as_deref(), Some("https://example.com/a"));
+
+        // A new navigation drops the forward entries
+        tab.navigate("https://example.com/c").unwrap();
+        assert!(!tab.can_go_forward() && !tab.go_forward().unwrap());
+        assert!(tab.go_back().unwrap());
+        assert_eq!(engine_url(tab).as_deref(), Some("https://example.com/a"));
+    }
+
+    #[test]
+    fn reopened_tab_returns_to_its_index_with_its_history() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        let tab = manager.get_tab(&ids[1]).unwrap();
+        tab.navigate("https://example.com/1/next").unwrap();
+        tab.go_back().unwrap();
+        manager.close_tab(&ids[1]).unwrap();
+        assert_eq!(order(&manager), vec![ids[0].clone(), ids[2].clone()]);
+
+        let reopened = manager.reopen_closed_tab(None).unwrap();
+
+        assert_eq!(reopened, ids[1]);
+        assert_eq!(order(&manager), ids);
+        assert_eq!(manager.get_active_tab_id(), Some(ids[1].as_str()));
+        let tab = manager.get_tab(&ids[1]).unwrap();
+        assert_eq!(tab.info.url, "https://example.com/1");
+        assert!(tab.can_go_forward() && !tab.can_go_back());
+        assert!(tab.go_forward().unwrap());
+        assert_eq!(tab.info.url, "https://example.com/1/next");
+        assert!(manager.get_closed_tabs().is_empty());
+        assert!(manager.reopen_closed_tab(None).is_err());
+    }
+
+    fn small_viewport() -> EngineConfig {
+        EngineConfig {
+            width: 640,
+            height: 480,
+            ..EngineConfig::default()
+        }
+    }
+
+    fn viewport(tab: &BrowserTab) -> (u32, u32) {
+        let engine = tab.engine.clone().unwrap();
+        let size = engine.lock().unwrap().viewport_size();
+        size
+    }
+
+    #[test]
+    fn reopened_tab_keeps_its_engine_config() {
+        let (mut manager, _) = manager_with_tabs(1);
+        let tab_id = manager
+            .create_tab(Some("https://example.com/small".to_string()), Some(small_viewport()))
+            .unwrap();
+        manager.close_tab(&tab_id).unwrap();
+
+        manager.reopen_closed_tab(None).unwrap();
+
+        assert_eq!(viewport(manager.get_tab(&tab_id).unwrap()), (640, 480));
+    }
+
+    #[test]
+    fn only_the_most_recent_closed_tabs_are_kept() {
+        let (mut manager, ids) = manager_with_tabs(MAX_CLOSED_TABS + 2);
+        for id in &ids[..MAX_CLOSED_TABS + 1] {
+            manager.close_tab(id).unwrap();
+        }
+
+        let closed = manager.get_closed_tabs();
+        assert_eq!(closed.len(), MAX_CLOSED_TABS);
+        assert_eq!(closed[0].snapshot.info.id, ids[MAX_CLOSED_TABS]);
+        assert_eq!(closed[MAX_CLOSED_TABS - 1].snapshot.info.id, ids[1]);
+        assert!(manager.reopen_closed_tab(Some(&ids[0])).is_err());
+    }
+
+    fn no_automatic_discards() -> TabDiscardPolicy {
+        TabDiscardPolicy {
+            idle_timeout_secs: None,
+            max_live_engines: None,
+            ..TabDiscardPolicy::default()
+        }
+    }
+
+    #[test]
+    fn discarded_tab_keeps_history_and_revives_on_activation() {
+        let (mut manager, ids) = manager_with_tabs(2);
+        manager.get_tab(&ids[1]).unwrap().navigate("https://example.com/next").unwrap();
+
+        manager.discard_tab(&ids[1]).unwrap();
+        let info = manager.get_tab_info(&ids[1]).unwrap();
+        assert!(info.is_discarded);
+        assert_eq!(info.url, "https://example.com/next");
+        assert!(info.can_go_back);
+
+        manager.set_active_tab(&ids[1]).unwrap();
+        let tab = manager.get_tab(&ids[1]).unwrap();
+        assert!(!tab.is_discarded());
+        let engine = tab.engine.clone().unwrap();
+        assert_eq!(engine.lock().unwrap().current_url(), Some("https://example.com/next"));
+        assert!(tab.go_back().unwrap());
+    }
+
+    fn visits(manager: &mut TabManager) -> Vec<(String, PageTransition)> {
+        manager.process_engine_events();
+        manager
+            .take_history_updates()
+            .into_iter()
+            .filter_map(|update| match update {
+                HistoryUpdate::Visit { url, transition, .. } => Some((url, transition)),
+                HistoryUpdate::Title { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn committed_navigations_are_recorded_with_their_transition() {
+        let (mut manager, ids) = manager_with_tabs(2);
+        assert_eq!(visits(&mut manager).len(), 2);
+
+        let mut recorded = Vec::new();
+        let steps: [fn(&mut BrowserTab); 3] = [
+            |tab| tab.navigate_with_transition("https://example.com/typed", PageTransition::Typed).unwrap(),
+            |tab| tab.reload().unwrap(),
+            |tab| assert!(tab.go_back().unwrap()),
+        ];
+        for step in steps {
+            step(manager.get_tab(&ids[1]).unwrap());
+            recorded.extend(visits(&mut manager));
+        }
+        // Superseded before it commits, so never a visit
+        let tab = manager.get_tab(&ids[1]).unwrap();
+        tab.navigate("https://example.com/abandoned").unwrap();
+        tab.go_back().unwrap();
+        recorded.extend(visits(&mut manager));
+
+        let expected = vec![
+            ("https://example.com/typed".to_string(), PageTransition::Typed),
+            ("https://example.com/typed".to_string(), PageTransition::Reload),
+            ("https://example.com/1".to_string(), PageTransition::BackForward),
+            ("https://example.com/1".to_string(), PageTransition::BackForward),
+        ];
+        assert_eq!(recorded, expected);
+
+        manager.discard_tab(&ids[1]).unwrap();
+        manager.set_active_tab(&ids[1]).unwrap();
+        assert!(visits(&mut manager).is_empty());
+
+        // A blank tab has nothing to reload, so its next load is not one
+        let blank = manager.create_tab(None, None).unwrap();
+        let tab = manager.get_tab(&blank).unwrap();
+        tab.reload().unwrap();
+        tab.navigate_with_transition("https://example.com/typed", PageTransition::Typed).unwrap();
+        let expected = vec![("https://example.com/typed".to_string(), PageTransition::Typed)];
+        assert_eq!(visits(&mut manager), expected);
+    }
+
+    #[test]
+    fn active_tab_cannot_be_discarded() {
+        let (mut manager, ids) = manager_with_tabs(1);
+
+        assert!(manager.discard_tab(&ids[0]).is_err());
+    }
+
+    #[test]
+    fn engine_limit_discards_least_recently_used_tabs() {
+        let (mut manager, ids) = manager_with_tabs(4);
+        manager.set_discard_policy(TabDiscardPolicy {
+            max_live_engines: Some(2),
+            ..no_automatic_discards()
+        });
+        for id in [&ids[2], &ids[1], &ids[3]] {
+            manager.set_active_tab(id).unwrap();
+        }
+
+        let discarded = manager.apply_discard_policy();
+
+        assert_eq!(discarded, vec![ids[0].clone(), ids[2].clone()]);
+        assert!(!manager.get_tab_info(&ids[1]).unwrap().is_discarded);
+        assert!(!manager.get_tab_info(&ids[3]).unwrap().is_discarded);
+    }
+
+    #[test]
+    fn idle_timeout_spares_exempt_tabs() {
+        let (mut manager, ids) = manager_with_tabs(3);
+        manager.set_discard_policy(TabDiscardPolicy {
+            idle_timeout_secs: Some(0),
+            ..no_automatic_discards()
+        });
+        manager.pin_tab(&ids[1]).unwrap();
+        manager.get_tab(&ids[2]).unwrap().info.is_audible = true;
+        manager.set_active_tab(&ids[1]).unwrap();
+        manager.set_active_tab(&ids[0]).unwrap();
+
+        assert!(manager.apply_discard_policy().is_empty());
+
+        manager.get_tab(&ids[2]).unwrap().info.is_audible = false;
+        assert_eq!(manager.apply_discard_policy(), vec![ids[2].clone()]);
+    }
+}
@@ -0,0 +1,220 @@
+// Browser Window Registry
+use super::discard::TabDiscardPolicy;
+use super::session::WindowSnapshot;
+use super::tabs::{SharedTabManager, TabManager};
+use anyhow::Result;
+use log::info;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// Label of the window declared in `tauri.conf.json`
+pub const MAIN_WINDOW_ID: &str = "main";
+
+/// Tracks every open browser window and the tab strip it owns.
+///
+/// Window ids double as Tauri window labels.
+pub struct WindowRegistry {
+    windows: HashMap<String, SharedTabManager>,
+    window_order: Vec<String>,
+    // Shared by every window
+    discard_policy: TabDiscardPolicy,
+}
+
+impl WindowRegistry {
+    pub fn new() -> Self {
+        Self {
+            windows: HashMap::new(),
+            window_order: Vec::new(),
+            discard_policy: TabDiscardPolicy::default(),
+        }
+    }
+
+    /// Registers a new window with an empty tab strip and returns its id.
+    pub fn create_window(&mut self) -> String {
+        let window_id = format!("browser-{}", Uuid::new_v4());
+        self.insert_window(window_id.clone(), TabManager::new());
+        window_id
+    }
+
+    pub fn insert_window(&mut self, window_id: String, mut tab_manager: TabManager) -> SharedTabManager {
+        info!("Registering window: {}", window_id);
+
+        tab_manager.set_discard_policy(self.discard_policy.clone());
+        let tab_manager = Arc::new(Mutex::new(tab_manager));
+        if self.windows.insert(window_id.clone(), tab_manager.clone()).is_none() {
+            self.window_order.push(window_id);
+        }
+        tab_manager
+    }
+
+    pub fn remove_window(&mut self, window_id: &str) -> Option<SharedTabManager> {
+        info!("Unregistering window: {}", window_id);
+
+        self.window_order.retain(|id| id != window_id);
+        self.windows.remove(window_id)
+    }
+
+    pub fn get(&self, window_id: &str) -> Option<SharedTabManager> {
+        self.windows.get(window_id).cloned()
+    }
+
+    /// Windows in the order they were opened
+    pub fn windows(&self) -> Vec<(String, SharedTabManager)> {
+        self.window_order
+            .iter()
+            .filter_map(|id| self.windows.get(id).map(|manager| (id.clone(), manager.clone())))
+            .collect()
+    }
+
+    pub fn window_count(&self) -> usize {
+        self.windows.len()
+    }
+
+    pub fn discard_policy(&self) -> &TabDiscardPolicy {
+        &self.discard_policy
+    }
+
+    pub fn set_discard_policy(&mut self, policy: TabDiscardPolicy) {
+        for tab_manager in self.windows.values() {
+            tab_manager.lock().unwrap().set_discard_policy(policy.clone());
+        }
+        self.discard_policy = policy;
+    }
+
+    /// Moves a tab, with its engine and history, into another window.
+    pub fn move_tab(&self, from_window_id: &str, tab_id: &str, to_window_id: &str, index: Option<usize>) -> Result<()> {
+        let source = self
+            .get(from_window_id)
+            .ok_or_else(|| anyhow::anyhow!("Window not found: {}", from_window_id))?;
+        let target = self
+            .get(to_window_id)
+            .ok_or_else(|| anyhow::anyhow!("Window not found: {}", to_window_id))?;
+        if Arc::ptr_eq(&source, &target) {
+            return match index {
+                Some(index) => source.lock().unwrap().move_tab(tab_id, index),
+                None => Ok(()),
+            };
+        }
+
+        // Refuse before taking the tab, so a failed move cannot lose it
+        let mut target = target.lock().unwrap();
+        if target.get_tab_info(tab_id).is_some() {
+            return Err(anyhow::anyhow!("Tab already exists: {}", tab_id));
+        }
+        let tab = source.lock().unwrap().take_tab(tab_id)?;
+        target.insert_tab(tab, index)?;
+
+        info!("Moved tab {} from window {} to {}", tab_id, from_window_id, to_window_id);
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> Vec<WindowSnapshot> {
+        self.windows()
+            .into_iter()
+            .filter_map(|(id, manager)| manager.lock().ok().map(|manager| manager.window_snapshot(id)))
+            .collect()
+    }
+}
+
+impl Default for WindowRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Thread-safe wrapper for the window registry
+pub type SharedWindowRegistry = Arc<Mutex<WindowRegistry>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry_with_windows(tab_counts: &[usize]) -> (WindowRegistry, Vec<String>) {
+        let mut registry = WindowRegistry::new();
+        let window_ids = tab_counts
+            .iter()
+            .map(|&count| {
+                let window_id = registry.create_window();
+                let manager = registry.get(&window_id).unwrap();
+                let mut manager = manager.lock().unwrap();
+                for i in 0..count {
+                    manager.create_tab(Some(format!("https://example.com/{}", i)), None).unwrap();
+                }
+                window_id
+            })
+            .collect();
+        (registry, window_ids)
+    }
+
+    fn tab_ids(registry: &WindowRegistry, window_id: &str) -> Vec<String> {
+        let manager = registry.get(window_id).unwrap();
+        let manager = manager.lock().unwrap();
+        manager.get_all_tab_info().into_iter().map(|info| info.id).collect()
+    }
+
+    #[test]
+    fn moved_tab_keeps_its_engine_and_history() {
+        let (registry, windows) = registry_with_windows(&[2, 1]);
+        let source_tabs = tab_ids(&registry, &windows[0]);
+        let moved = source_tabs[1].clone();
+        let engine = {
+            let source = registry.get(&windows[0]).unwrap();
+            let mut source = source.lock().unwrap();
+            let tab = source.get_tab(&moved).unwrap();
+            tab.navigate("https://example.com/next").unwrap();
+            tab.engine.clone().unwrap()
+        };
+
+        registry.move_tab(&windows[0], &moved, &windows[1], Some(0)).unwrap();
+
+        assert_eq!(tab_ids(&registry, &windows[0]), vec![source_tabs[0].clone()]);
+        let target_tabs = tab_ids(&registry, &windows[1]);
+        assert_eq!(target_tabs.len(), 2);
+        assert_eq!(target_tabs[0], moved);
+        let target = registry.get(&windows[1]).unwrap();
+        let mut target = target.lock().unwrap();
+        assert_eq!(target.get_active_tab_id(), Some(moved.as_str()));
+        let tab = target.get_tab(&moved).unwrap();
+        assert!(Arc::ptr_eq(tab.engine.as_ref().unwrap(), &engine));
+        assert!(tab.can_go_back());
+    }
+
+    #[test]
+    fn moving_within_a_window_reorders_it() {
+        let (registry, windows) = registry_with_windows(&[3]);
+        let tabs = tab_ids(&registry, &windows[0]);
+
+        registry.move_tab(&windows[0], &tabs[0], &windows[0], Some(2)).unwrap();
+
+        assert_eq!(tab_ids(&registry, &windows[0]), vec![tabs[1].clone(), tabs[2].clone(), tabs[0].clone()]);
+    }
+
+    #[test]
+    fn moving_to_or_from_an_unknown_window_fails() {
+        let (registry, windows) = registry_with_windows(&[1]);
+        let tabs = tab_ids(&registry, &windows[0]);
+
+        assert!(registry.move_tab(&windows[0], &tabs[0], "missing", None).is_err());
+        assert!(registry.move_tab("missing", &tabs[0], &windows[0], None).is_err());
+        assert_eq!(tab_ids(&registry, &windows[0]), tabs);
+    }
+
+    #[test]
+    fn failed_move_leaves_the_source_window_unchanged() {
+        let (mut registry, windows) = registry_with_windows(&[3]);
+        let tabs = tab_ids(&registry, &windows[0]);
+        // A window restored from the same snapshot holds tabs with the same ids
+        let snapshot = registry.get(&windows[0]).unwrap().lock().unwrap().window_snapshot("copy".to_string());
+        let mut copy = TabManager::new();
+        copy.restore_window(snapshot, None).unwrap();
+        registry.insert_window("copy".to_string(), copy);
+
+        assert!(registry.move_tab(&windows[0], &tabs[1], "copy", None).is_err());
+
+        assert_eq!(tab_ids(&registry, &windows[0]), tabs);
+        let source = registry.get(&windows[0]).unwrap();
+        let mut source = source.lock().unwrap();
+        assert!(!source.get_tab(&tabs[1]).unwrap().is_discarded());
+    }
+}
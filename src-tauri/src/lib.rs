@@ -1,106 +1,317 @@
 // Vishwakarma Express Browser - Main Library
 mod browser;
 
-use browser::{TabManager, SharedTabManager};
-use log::info;
-use std::sync::Mutex;
+use browser::{
+    TabInfo, TabManager, SharedTabManager, SessionSaver, SessionStore, TabGroupColor, TabSelectionPolicy,
+    TabDiscardPolicy, WindowRegistry, SharedWindowRegistry, MAIN_WINDOW_ID, DownloadManager,
+    SharedDownloadManager, DownloadHistoryStore, DownloadItem, DownloadPriority, DownloadProgress,
+    DownloadQueueState, ProgressThrottle, HistoryStore, SharedHistoryStore, HistoryQuery, HistoryUpdate,
+    HistoryVisit, HISTORY_FILE_NAME, HistorySearch, HistorySearchResult, SearchEngine, Checksum,
+    IntegrityStatus, DownloadSchedule, DownloadSettings,
+};
+use log::{error, info, warn};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
+
+// How often engine events are collected for backends that load asynchronously
+const ENGINE_EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// How often background tabs are checked against the discard policy
+const TAB_DISCARD_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// Quiet period before pending tab changes are written to disk
+const SESSION_SAVE_DEBOUNCE: Duration = Duration::from_secs(2);
+// How often download workers are drained and the schedule checked
+const DOWNLOAD_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+// Minimum gap between progress events for the same download
+const DOWNLOAD_PROGRESS_EVENT_INTERVAL: Duration = Duration::from_millis(500);
 
 // Application state
 pub struct AppState {
-    tab_manager: SharedTabManager,
+    windows: SharedWindowRegistry,
+    session_saver: SessionSaver,
+    downloads: SharedDownloadManager,
+    history: SharedHistoryStore,
+    // Where address bar input that is not an address is searched for
+    search_engine: Mutex<SearchEngine>,
+}
+
+fn tab_manager_for(state: &AppState, window_id: &str) -> Result<SharedTabManager, String> {
+    state
+        .windows
+        .lock()
+        .unwrap()
+        .get(window_id)
+        .ok_or_else(|| format!("Window not found: {}", window_id))
+}
+
+// Forwards pending engine events to the owning window as `tab-event`.
+// Returns true if any event was emitted.
+fn emit_tab_events(app: &AppHandle, window_id: &str, tab_manager: &mut TabManager) -> bool {
+    let events = tab_manager.process_engine_events();
+    for event in &events {
+        if let Err(e) = app.emit_to(window_id, "tab-event", event) {
+            warn!("Failed to emit tab event: {}", e);
+        }
+    }
+    record_history(app, &tab_manager.take_history_updates());
+    !events.is_empty()
+}
+
+// Writes visits and titles the tabs saw to the global history
+fn record_history(app: &AppHandle, updates: &[HistoryUpdate]) {
+    if updates.is_empty() {
+        return;
+    }
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let result = state.history.lock().unwrap().apply(updates);
+    if let Err(e) = result {
+        warn!("Failed to record history: {}", e);
+    }
+}
+
+fn emit_discarded_tabs(app: &AppHandle, window_id: &str, tab_ids: &[String]) {
+    if tab_ids.is_empty() {
+        return;
+    }
+    if let Err(e) = app.emit_to(window_id, "tabs-discarded", tab_ids) {
+        warn!("Failed to emit discarded tabs: {}", e);
+    }
+}
+
+// Drains download worker updates and returns progress for the downloads
+// that changed status or are due another progress event
+fn collect_download_progress(
+    downloads: &mut DownloadManager,
+    throttle: &mut ProgressThrottle,
+) -> Vec<DownloadProgress> {
+    downloads.process_transfer_updates();
+    downloads.apply_schedule();
+
+    let now = Instant::now();
+    let due: Vec<String> = downloads
+        .get_all_downloads()
+        .into_iter()
+        .filter(|download| throttle.should_emit(download, now))
+        .map(|download| download.id.clone())
+        .collect();
+    throttle.retain(|id| downloads.get_download(id).is_some());
+    due.iter().filter_map(|id| downloads.download_progress(id)).collect()
+}
+
+fn tab_info_json(tab_info: &TabInfo) -> serde_json::Value {
+    serde_json::json!({
+        "id": tab_info.id,
+        "url": tab_info.url,
+        "title": tab_info.title,
+        "is_loading": tab_info.is_loading,
+        "load_progress": tab_info.load_progress,
+        "can_go_back": tab_info.can_go_back,
+        "can_go_forward": tab_info.can_go_forward,
+        "is_active": tab_info.is_active,
+        "favicon_url": tab_info.favicon_url,
+        "opener_id": tab_info.opener_id,
+        "is_pinned": tab_info.is_pinned,
+        "group_id": tab_info.group_id,
+        "is_discarded": tab_info.is_discarded,
+        "is_audible": tab_info.is_audible,
+    })
 }
 
 // Tauri commands
 #[tauri::command]
 async fn create_new_tab(
+    app: AppHandle,
     state: tauri::State<'_, AppState>,
+    window_id: String,
     url: Option<String>,
+    opener_tab_id: Option<String>,
 ) -> Result<String, String> {
-    let mut tab_manager = state.tab_manager.lock().unwrap();
-    let tab_id = tab_manager.create_tab(url, None).map_err(|e| e.to_string())?;
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    let tab_id = tab_manager
+        .create_tab_with_opener(url, opener_tab_id, None)
+        .map_err(|e| e.to_string())?;
+    emit_tab_events(&app, &window_id, &mut tab_manager);
+    state.session_saver.schedule_save();
     Ok(tab_id)
 }
 
 #[tauri::command]
 async fn close_tab(
     state: tauri::State<'_, AppState>,
+    window_id: String,
+    tab_id: String,
+    force: Option<bool>,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    if force.unwrap_or(false) {
+        tab_manager.force_close_tab(&tab_id).map_err(|e| e.to_string())?;
+    } else {
+        tab_manager.close_tab(&tab_id).map_err(|e| e.to_string())?;
+    }
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn pin_tab(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
     tab_id: String,
 ) -> Result<(), String> {
-    let mut tab_manager = state.tab_manager.lock().unwrap();
-    tab_manager.close_tab(&tab_id).map_err(|e| e.to_string())
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.pin_tab(&tab_id).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn unpin_tab(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    tab_id: String,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.unpin_tab(&tab_id).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn reopen_closed_tab(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    tab_id: Option<String>,
+) -> Result<String, String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    let tab_id = tab_manager.reopen_closed_tab(tab_id.as_deref()).map_err(|e| e.to_string())?;
+    emit_tab_events(&app, &window_id, &mut tab_manager);
+    state.session_saver.schedule_save();
+    Ok(tab_id)
+}
+
+#[tauri::command]
+async fn get_recently_closed_tabs(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+) -> Result<Vec<serde_json::Value>, String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let tab_manager = tab_manager.lock().unwrap();
+    let closed_tabs = tab_manager.get_closed_tabs()
+        .iter()
+        .map(|closed| serde_json::json!({
+            "id": closed.snapshot.info.id,
+            "url": closed.snapshot.info.url,
+            "title": closed.snapshot.info.title,
+            "favicon_url": closed.snapshot.info.favicon_url,
+            "index": closed.index,
+            "closed_at": closed.closed_at,
+            "history_length": closed.snapshot.navigation.entries.len(),
+        }))
+        .collect();
+    Ok(closed_tabs)
 }
 
 #[tauri::command]
 async fn navigate_to_url(
+    app: AppHandle,
     state: tauri::State<'_, AppState>,
+    window_id: String,
     tab_id: String,
     url: String,
-) -> Result<(), String> {
-    let mut tab_manager = state.tab_manager.lock().unwrap();
-    if let Some(tab) = tab_manager.get_tab(&tab_id) {
-        tab.navigate(&url).map_err(|e| e.to_string())
+) -> Result<String, String> {
+    let target = {
+        let search_engine = state.search_engine.lock().unwrap();
+        browser::resolve_input(&url, &search_engine).map_err(|e| e.to_string())?
+    };
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    let result = if let Some(tab) = tab_manager.get_tab(&tab_id) {
+        tab.navigate_with_transition(&target.url, target.transition)
+            .map(|_| target.url)
+            .map_err(|e| e.to_string())
     } else {
         Err("Tab not found".to_string())
-    }
+    };
+    emit_tab_events(&app, &window_id, &mut tab_manager);
+    state.session_saver.schedule_save();
+    result
 }
 
 #[tauri::command]
 async fn reload_tab(
+    app: AppHandle,
     state: tauri::State<'_, AppState>,
+    window_id: String,
     tab_id: String,
 ) -> Result<(), String> {
-    let mut tab_manager = state.tab_manager.lock().unwrap();
-    if let Some(tab) = tab_manager.get_tab(&tab_id) {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    let result = if let Some(tab) = tab_manager.get_tab(&tab_id) {
         tab.reload().map_err(|e| e.to_string())
     } else {
         Err("Tab not found".to_string())
-    }
+    };
+    emit_tab_events(&app, &window_id, &mut tab_manager);
+    result
 }
 
 #[tauri::command]
 async fn go_back(
+    app: AppHandle,
     state: tauri::State<'_, AppState>,
+    window_id: String,
     tab_id: String,
 ) -> Result<(), String> {
-    let mut tab_manager = state.tab_manager.lock().unwrap();
-    if let Some(tab) = tab_manager.get_tab(&tab_id) {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    let result = if let Some(tab) = tab_manager.get_tab(&tab_id) {
         tab.go_back().map(|_| ()).map_err(|e| e.to_string())
     } else {
         Err("Tab not found".to_string())
-    }
+    };
+    emit_tab_events(&app, &window_id, &mut tab_manager);
+    state.session_saver.schedule_save();
+    result
 }
 
 #[tauri::command]
 async fn go_forward(
+    app: AppHandle,
     state: tauri::State<'_, AppState>,
+    window_id: String,
     tab_id: String,
 ) -> Result<(), String> {
-    let mut tab_manager = state.tab_manager.lock().unwrap();
-    if let Some(tab) = tab_manager.get_tab(&tab_id) {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    let result = if let Some(tab) = tab_manager.get_tab(&tab_id) {
         tab.go_forward().map(|_| ()).map_err(|e| e.to_string())
     } else {
         Err("Tab not found".to_string())
-    }
+    };
+    emit_tab_events(&app, &window_id, &mut tab_manager);
+    state.session_saver.schedule_save();
+    result
 }
 
 #[tauri::command]
 async fn get_all_tabs(
     state: tauri::State<'_, AppState>,
+    window_id: String,
 ) -> Result<Vec<serde_json::Value>, String> {
-    let tab_manager = state.tab_manager.lock().unwrap();
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let tab_manager = tab_manager.lock().unwrap();
     let tabs = tab_manager.get_all_tab_info()
         .iter()
-        .map(|tab_info| serde_json::json!({
-            "id": tab_info.id,
-            "url": tab_info.url,
-            "title": tab_info.title,
-            "is_loading": tab_info.is_loading,
-            "can_go_back": false,
-            "can_go_forward": false,
-            "is_active": tab_info.is_active,
-            "favicon_url": tab_info.favicon_url,
-            "is_pinned": false,
-        }))
+        .map(tab_info_json)
         .collect();
     Ok(tabs)
 }
@@ -108,39 +319,704 @@ async fn get_all_tabs(
 #[tauri::command]
 async fn get_active_tab_id(
     state: tauri::State<'_, AppState>,
+    window_id: String,
 ) -> Result<Option<String>, String> {
-    let tab_manager = state.tab_manager.lock().unwrap();
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let tab_manager = tab_manager.lock().unwrap();
     Ok(tab_manager.get_active_tab_id().map(|s| s.to_string()))
 }
 
 #[tauri::command]
 async fn set_active_tab(
     state: tauri::State<'_, AppState>,
+    window_id: String,
+    tab_id: String,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.set_active_tab(&tab_id).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_tab_selection_policy(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+) -> Result<TabSelectionPolicy, String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let tab_manager = tab_manager.lock().unwrap();
+    Ok(tab_manager.selection_policy())
+}
+
+#[tauri::command]
+async fn set_tab_selection_policy(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    policy: TabSelectionPolicy,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.set_selection_policy(policy);
+    Ok(())
+}
+
+#[tauri::command]
+async fn move_tab(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    tab_id: String,
+    new_index: usize,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.move_tab(&tab_id, new_index).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn create_tab_group(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    tab_ids: Vec<String>,
+    name: String,
+    color: Option<TabGroupColor>,
+) -> Result<String, String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    let group_id = tab_manager
+        .create_tab_group(&tab_ids, name, color.unwrap_or_default())
+        .map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(group_id)
+}
+
+#[tauri::command]
+async fn rename_tab_group(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    group_id: String,
+    name: String,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.rename_tab_group(&group_id, name).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_tab_group_color(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    group_id: String,
+    color: TabGroupColor,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.set_tab_group_color(&group_id, color).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_tab_group_collapsed(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    group_id: String,
+    collapsed: bool,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.set_tab_group_collapsed(&group_id, collapsed).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn add_tab_to_group(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    tab_id: String,
+    group_id: String,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.add_tab_to_group(&tab_id, &group_id).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn remove_tab_from_group(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    tab_id: String,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.remove_tab_from_group(&tab_id).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn ungroup_tab_group(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    group_id: String,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.ungroup_tab_group(&group_id).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn close_tab_group(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    group_id: String,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.close_tab_group(&group_id).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn move_tab_group(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    group_id: String,
+    new_index: usize,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.move_tab_group(&group_id, new_index).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_tab_groups(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+) -> Result<Vec<serde_json::Value>, String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let tab_manager = tab_manager.lock().unwrap();
+    let groups = tab_manager.get_tab_groups()
+        .iter()
+        .map(|group| serde_json::json!({
+            "id": group.id,
+            "name": group.name,
+            "color": group.color,
+            "collapsed": group.collapsed,
+            "tab_ids": tab_manager.get_group_tab_ids(&group.id),
+        }))
+        .collect();
+    Ok(groups)
+}
+
+#[tauri::command]
+async fn discard_tab(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    tab_id: String,
+) -> Result<(), String> {
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let mut tab_manager = tab_manager.lock().unwrap();
+    tab_manager.discard_tab(&tab_id).map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+// Frees memory across all windows, e.g. when the system reports pressure
+#[tauri::command]
+async fn discard_background_tabs(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<usize, String> {
+    let windows = state.windows.lock().unwrap();
+    let mut discarded_count = 0;
+    for (window_id, tab_manager) in windows.windows() {
+        let discarded = tab_manager.lock().unwrap().discard_background_tabs();
+        discarded_count += discarded.len();
+        emit_discarded_tabs(&app, &window_id, &discarded);
+    }
+    state.session_saver.schedule_save();
+    Ok(discarded_count)
+}
+
+#[tauri::command]
+async fn get_tab_discard_policy(
+    state: tauri::State<'_, AppState>,
+) -> Result<TabDiscardPolicy, String> {
+    let windows = state.windows.lock().unwrap();
+    Ok(windows.discard_policy().clone())
+}
+
+#[tauri::command]
+async fn set_tab_discard_policy(
+    state: tauri::State<'_, AppState>,
+    policy: TabDiscardPolicy,
+) -> Result<(), String> {
+    let mut windows = state.windows.lock().unwrap();
+    windows.set_discard_policy(policy);
+    Ok(())
+}
+
+// Opens a browser window for an already registered window id
+fn build_browser_window(app: &AppHandle, window_id: &str) -> Result<(), String> {
+    WebviewWindowBuilder::new(app, window_id, WebviewUrl::App("index.html".into()))
+        .title("Vishwakarma Express")
+        .inner_size(800.0, 600.0)
+        .resizable(true)
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn open_window(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    url: Option<String>,
+) -> Result<String, String> {
+    let window_id = state.windows.lock().unwrap().create_window();
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    {
+        let mut tab_manager = tab_manager.lock().unwrap();
+        tab_manager.create_tab(url, None).map_err(|e| e.to_string())?;
+        emit_tab_events(&app, &window_id, &mut tab_manager);
+    }
+    if let Err(e) = build_browser_window(&app, &window_id) {
+        state.windows.lock().unwrap().remove_window(&window_id);
+        return Err(e);
+    }
+    state.session_saver.schedule_save();
+    Ok(window_id)
+}
+
+#[tauri::command]
+async fn detach_tab(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    window_id: String,
+    tab_id: String,
+) -> Result<String, String> {
+    let new_window_id = {
+        let mut windows = state.windows.lock().unwrap();
+        let new_window_id = windows.create_window();
+        if let Err(e) = windows.move_tab(&window_id, &tab_id, &new_window_id, None) {
+            windows.remove_window(&new_window_id);
+            return Err(e.to_string());
+        }
+        new_window_id
+    };
+    if let Err(e) = build_browser_window(&app, &new_window_id) {
+        // Put the tab back where the user can still reach it
+        let mut windows = state.windows.lock().unwrap();
+        if let Err(e) = windows.move_tab(&new_window_id, &tab_id, &window_id, None) {
+            warn!("Failed to return tab {} to window {}: {}", tab_id, window_id, e);
+        }
+        windows.remove_window(&new_window_id);
+        return Err(e);
+    }
+    state.session_saver.schedule_save();
+    Ok(new_window_id)
+}
+
+#[tauri::command]
+async fn move_tab_to_window(
+    state: tauri::State<'_, AppState>,
+    window_id: String,
     tab_id: String,
+    target_window_id: String,
+    index: Option<usize>,
 ) -> Result<(), String> {
-    let mut tab_manager = state.tab_manager.lock().unwrap();
-    tab_manager.set_active_tab(&tab_id).map_err(|e| e.to_string())
+    let windows = state.windows.lock().unwrap();
+    windows
+        .move_tab(&window_id, &tab_id, &target_window_id, index)
+        .map_err(|e| e.to_string())?;
+    state.session_saver.schedule_save();
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_windows(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let windows = state.windows.lock().unwrap();
+    let windows = windows
+        .windows()
+        .into_iter()
+        .map(|(id, tab_manager)| {
+            let tab_manager = tab_manager.lock().unwrap();
+            serde_json::json!({
+                "id": id,
+                "tab_count": tab_manager.tab_count(),
+                "active_tab_id": tab_manager.get_active_tab_id(),
+            })
+        })
+        .collect();
+    Ok(windows)
 }
 
 #[tauri::command]
 async fn get_tab_info(
     state: tauri::State<'_, AppState>,
+    window_id: String,
     tab_id: String,
 ) -> Result<serde_json::Value, String> {
-    let tab_manager = state.tab_manager.lock().unwrap();
+    let tab_manager = tab_manager_for(&state, &window_id)?;
+    let tab_manager = tab_manager.lock().unwrap();
     let tab_info = tab_manager.get_tab_info(&tab_id).ok_or("Tab not found")?;
     
-    Ok(serde_json::json!({
-        "id": tab_info.id,
-        "url": tab_info.url,
-        "title": tab_info.title,
-        "is_loading": tab_info.is_loading,
-        "can_go_back": false,
-        "can_go_forward": false,
-        "is_active": tab_info.is_active,
-        "favicon_url": tab_info.favicon_url,
-        "is_pinned": false,
-    }))
+    Ok(tab_info_json(tab_info))
+}
+
+#[tauri::command]
+async fn start_download(
+    state: tauri::State<'_, AppState>,
+    url: String,
+    filename: Option<String>,
+    checksum: Option<Checksum>,
+) -> Result<String, String> {
+    let checksum = validate_checksum(checksum)?;
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads
+        .start_download_with_checksum(url, filename, checksum)
+        .map_err(|e| e.to_string())
+}
+
+// Digests from the UI are normalized and checked for the right length
+fn validate_checksum(checksum: Option<Checksum>) -> Result<Option<Checksum>, String> {
+    checksum
+        .map(|checksum| Checksum::new(checksum.algorithm, &checksum.value))
+        .transpose()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_download_checksum(
+    state: tauri::State<'_, AppState>,
+    download_id: String,
+    checksum: Option<Checksum>,
+) -> Result<IntegrityStatus, String> {
+    let checksum = validate_checksum(checksum)?;
+    browser::set_download_checksum(&state.downloads, &download_id, checksum)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn pause_download(
+    state: tauri::State<'_, AppState>,
+    download_id: String,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.pause_download(&download_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn resume_download(
+    state: tauri::State<'_, AppState>,
+    download_id: String,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.resume_download(&download_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn retry_download(
+    state: tauri::State<'_, AppState>,
+    download_id: String,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.retry_download(&download_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn cancel_download(
+    state: tauri::State<'_, AppState>,
+    download_id: String,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.cancel_download(&download_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn confirm_download(
+    state: tauri::State<'_, AppState>,
+    download_id: String,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.confirm_download(&download_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn discard_download(
+    state: tauri::State<'_, AppState>,
+    download_id: String,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.discard_download(&download_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn remove_download(
+    state: tauri::State<'_, AppState>,
+    download_id: String,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.remove_download(&download_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_downloads(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<DownloadItem>, String> {
+    let downloads = state.downloads.lock().unwrap();
+    Ok(downloads.get_all_downloads().into_iter().cloned().collect())
+}
+
+#[tauri::command]
+async fn clear_completed_downloads(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.clear_completed();
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_download_directory(
+    state: tauri::State<'_, AppState>,
+) -> Result<PathBuf, String> {
+    let downloads = state.downloads.lock().unwrap();
+    Ok(downloads.get_download_directory().clone())
+}
+
+#[tauri::command]
+async fn set_download_directory(
+    state: tauri::State<'_, AppState>,
+    path: PathBuf,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.set_download_directory(path).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_download_queue(
+    state: tauri::State<'_, AppState>,
+) -> Result<DownloadQueueState, String> {
+    let downloads = state.downloads.lock().unwrap();
+    Ok(downloads.queue_state())
+}
+
+#[tauri::command]
+async fn set_download_priority(
+    state: tauri::State<'_, AppState>,
+    download_id: String,
+    priority: DownloadPriority,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads
+        .set_download_priority(&download_id, priority)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn move_download_to_top(
+    state: tauri::State<'_, AppState>,
+    download_id: String,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.move_download_to_top(&download_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_download_limits(
+    state: tauri::State<'_, AppState>,
+    max_concurrent_downloads: usize,
+    max_downloads_per_host: Option<usize>,
+) -> Result<DownloadQueueState, String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.set_max_concurrent_downloads(max_concurrent_downloads);
+    downloads.set_max_downloads_per_host(max_downloads_per_host);
+    Ok(downloads.queue_state())
+}
+
+#[tauri::command]
+async fn get_download_settings(state: tauri::State<'_, AppState>) -> Result<DownloadSettings, String> {
+    let downloads = state.downloads.lock().unwrap();
+    Ok(downloads.settings())
+}
+
+#[tauri::command]
+async fn set_global_download_rate_limit(
+    state: tauri::State<'_, AppState>,
+    bytes_per_second: Option<u64>,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.set_global_rate_limit(bytes_per_second);
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_download_rate_limit(
+    state: tauri::State<'_, AppState>,
+    download_id: String,
+    bytes_per_second: Option<u64>,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads
+        .set_download_rate_limit(&download_id, bytes_per_second)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_download_schedule(
+    state: tauri::State<'_, AppState>,
+    schedule: Option<DownloadSchedule>,
+) -> Result<DownloadQueueState, String> {
+    let schedule = schedule
+        .map(|schedule| DownloadSchedule::new(schedule.start_minute, schedule.end_minute))
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    let mut downloads = state.downloads.lock().unwrap();
+    downloads.set_schedule(schedule);
+    Ok(downloads.queue_state())
+}
+
+#[tauri::command]
+async fn get_history(
+    state: tauri::State<'_, AppState>,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<HistoryVisit>, String> {
+    let defaults = HistoryQuery::default();
+    let query = HistoryQuery {
+        start_time,
+        end_time,
+        limit: limit.unwrap_or(defaults.limit),
+        offset: offset.unwrap_or(defaults.offset),
+    };
+    let history = state.history.lock().unwrap();
+    history.visits(&query).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_history_visit(
+    state: tauri::State<'_, AppState>,
+    visit_id: i64,
+) -> Result<bool, String> {
+    let mut history = state.history.lock().unwrap();
+    history.delete_visit(visit_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_history_url(
+    state: tauri::State<'_, AppState>,
+    url: String,
+) -> Result<bool, String> {
+    let mut history = state.history.lock().unwrap();
+    history.delete_url(&url).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_history_range(
+    state: tauri::State<'_, AppState>,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+) -> Result<usize, String> {
+    let mut history = state.history.lock().unwrap();
+    history.delete_range(start_time, end_time).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn search_history(
+    state: tauri::State<'_, AppState>,
+    query: String,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<HistorySearchResult>, String> {
+    let defaults = HistorySearch::default();
+    let search = HistorySearch {
+        query,
+        start_time,
+        end_time,
+        limit: limit.unwrap_or(defaults.limit),
+        offset: offset.unwrap_or(defaults.offset),
+    };
+    let history = state.history.lock().unwrap();
+    history.search(&search).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn index_page_text(
+    state: tauri::State<'_, AppState>,
+    url: String,
+    text: String,
+) -> Result<bool, String> {
+    let mut history = state.history.lock().unwrap();
+    history.index_page_text(&url, &text).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_page_text_indexing(
+    state: tauri::State<'_, AppState>,
+) -> Result<bool, String> {
+    let history = state.history.lock().unwrap();
+    Ok(history.page_text_indexing())
+}
+
+#[tauri::command]
+async fn set_page_text_indexing(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut history = state.history.lock().unwrap();
+    history.set_page_text_indexing(enabled).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn clear_history(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    let mut history = state.history.lock().unwrap();
+    history.clear().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_search_engine(
+    state: tauri::State<'_, AppState>,
+) -> Result<SearchEngine, String> {
+    Ok(state.search_engine.lock().unwrap().clone())
+}
+
+// A built-in engine by id, or a custom one when a template is given
+#[tauri::command]
+async fn set_search_engine(
+    state: tauri::State<'_, AppState>,
+    engine: String,
+    template: Option<String>,
+) -> Result<SearchEngine, String> {
+    let search_engine = match template {
+        Some(template) => SearchEngine::new(engine, template).map_err(|e| e.to_string())?,
+        None => SearchEngine::builtin(&engine).ok_or_else(|| format!("Unknown search engine: {}", engine))?,
+    };
+    info!("Search engine set to {}", search_engine.name);
+    *state.search_engine.lock().unwrap() = search_engine.clone();
+    Ok(search_engine)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -157,14 +1033,159 @@ pub fn run() {
             }
 
             info!("Vishwakarma Express browser starting up...");
+
+            // Restore the previous session, falling back to the last good snapshot.
+            // The window from tauri.conf.json is always registered as the main window.
+            let windows = SharedWindowRegistry::new(Mutex::new(WindowRegistry::new()));
+            windows.lock().unwrap().insert_window(MAIN_WINDOW_ID.to_string(), TabManager::new());
+            let app_data_dir = app.path().app_data_dir()?;
+            let session_store = Arc::new(SessionStore::new(app_data_dir.clone()));
+            match session_store.load() {
+                Ok(Some(mut snapshot)) => {
+                    // The main window may have been closed before another one
+                    if !snapshot.windows.iter().any(|window| window.id == MAIN_WINDOW_ID) {
+                        if let Some(first) = snapshot.windows.first_mut() {
+                            first.id = MAIN_WINDOW_ID.to_string();
+                        }
+                    }
+                    for window_snapshot in snapshot.windows {
+                        let window_id = window_snapshot.id.clone();
+                        let mut tab_manager = TabManager::new();
+                        if let Err(e) = tab_manager.restore_window(window_snapshot, None) {
+                            warn!("Failed to restore window {}: {}", window_id, e);
+                            continue;
+                        }
+                        if window_id != MAIN_WINDOW_ID {
+                            if let Err(e) = build_browser_window(app.handle(), &window_id) {
+                                warn!("Failed to reopen window {}: {}", window_id, e);
+                                continue;
+                            }
+                        }
+                        windows.lock().unwrap().insert_window(window_id, tab_manager);
+                    }
+                }
+                Ok(None) => info!("No previous session to restore"),
+                Err(e) => warn!("Failed to load session: {}", e),
+            }
+
+            let (session_saver, save_task) =
+                SessionSaver::new(session_store, windows.clone(), SESSION_SAVE_DEBOUNCE);
+            tauri::async_runtime::spawn(save_task);
+
+            // Downloads from the last run come back paused where they left off
+            let download_directory = app
+                .path()
+                .download_dir()
+                .unwrap_or_else(|_| app_data_dir.join("downloads"));
+            let mut download_manager = DownloadManager::new(download_directory);
+            if let Err(e) = download_manager.open_history(DownloadHistoryStore::new(app_data_dir.clone())) {
+                warn!("Failed to load download history: {}", e);
+            }
+            let downloads = SharedDownloadManager::new(Mutex::new(download_manager));
+
+            // Without the history file, visits are kept for this run only
+            let history_store = HistoryStore::open(&app_data_dir.join(HISTORY_FILE_NAME)).or_else(|e| {
+                warn!("Failed to open browsing history, keeping it in memory: {}", e);
+                HistoryStore::open_in_memory()
+            })?;
+            let history = SharedHistoryStore::new(Mutex::new(history_store));
+
+            app.manage(AppState {
+                windows: windows.clone(),
+                session_saver: session_saver.clone(),
+                downloads: downloads.clone(),
+                history,
+                search_engine: Mutex::new(SearchEngine::default()),
+            });
+
+            // Keep forwarding events from engines that finish loads later
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut interval = tokio::time::interval(ENGINE_EVENT_POLL_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let open_windows = match windows.lock() {
+                        Ok(windows) => windows.windows(),
+                        Err(_) => continue,
+                    };
+                    let mut emitted = false;
+                    for (window_id, tab_manager) in open_windows {
+                        if let Ok(mut tab_manager) = tab_manager.lock() {
+                            emitted |= emit_tab_events(&app_handle, &window_id, &mut tab_manager);
+                        }
+                    }
+                    if emitted {
+                        session_saver.schedule_save();
+                    }
+                }
+            });
+
+            // Discard background tabs that went idle or exceed the engine limit
+            let app_handle = app.handle().clone();
+            let windows = app.state::<AppState>().windows.clone();
+            let session_saver = app.state::<AppState>().session_saver.clone();
+            tauri::async_runtime::spawn(async move {
+                let mut interval = tokio::time::interval(TAB_DISCARD_CHECK_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let open_windows = match windows.lock() {
+                        Ok(windows) => windows.windows(),
+                        Err(_) => continue,
+                    };
+                    let mut any_discarded = false;
+                    for (window_id, tab_manager) in open_windows {
+                        if let Ok(mut tab_manager) = tab_manager.lock() {
+                            let discarded = tab_manager.apply_discard_policy();
+                            any_discarded |= !discarded.is_empty();
+                            emit_discarded_tabs(&app_handle, &window_id, &discarded);
+                        }
+                    }
+                    if any_discarded {
+                        session_saver.schedule_save();
+                    }
+                }
+            });
+
+            // Move download bytes along and report progress to every window
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut interval = tokio::time::interval(DOWNLOAD_UPDATE_INTERVAL);
+                let mut throttle = ProgressThrottle::new(DOWNLOAD_PROGRESS_EVENT_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let progress = match downloads.lock() {
+                        Ok(mut downloads) => collect_download_progress(&mut downloads, &mut throttle),
+                        Err(_) => continue,
+                    };
+                    if progress.is_empty() {
+                        continue;
+                    }
+                    if let Err(e) = app_handle.emit("download-progress", &progress) {
+                        warn!("Failed to emit download progress: {}", e);
+                    }
+                }
+            });
+
             Ok(())
         })
-        .manage(AppState {
-            tab_manager: SharedTabManager::new(Mutex::new(TabManager::new())),
+        .on_window_event(|window, event| {
+            // Closing a window discards its tabs, except for the last window
+            // whose tabs are kept for the next launch
+            if let tauri::WindowEvent::Destroyed = event {
+                let state = window.state::<AppState>();
+                let mut windows = state.windows.lock().unwrap();
+                if windows.window_count() > 1 && windows.remove_window(window.label()).is_some() {
+                    state.session_saver.schedule_save();
+                }
+            }
         })
         .invoke_handler(tauri::generate_handler![
             create_new_tab,
             close_tab,
+            pin_tab,
+            unpin_tab,
+            reopen_closed_tab,
+            get_recently_closed_tabs,
             navigate_to_url,
             reload_tab,
             go_back,
@@ -172,8 +1193,70 @@ pub fn run() {
             get_all_tabs,
             get_active_tab_id,
             set_active_tab,
+            get_tab_selection_policy,
+            set_tab_selection_policy,
+            move_tab,
+            create_tab_group,
+            rename_tab_group,
+            set_tab_group_color,
+            set_tab_group_collapsed,
+            add_tab_to_group,
+            remove_tab_from_group,
+            ungroup_tab_group,
+            close_tab_group,
+            move_tab_group,
+            get_tab_groups,
+            open_window,
+            detach_tab,
+            move_tab_to_window,
+            get_windows,
+            discard_tab,
+            discard_background_tabs,
+            get_tab_discard_policy,
+            set_tab_discard_policy,
+            start_download,
+            set_download_checksum,
+            pause_download,
+            resume_download,
+            retry_download,
+            cancel_download,
+            confirm_download,
+            discard_download,
+            remove_download,
+            get_downloads,
+            clear_completed_downloads,
+            get_download_directory,
+            set_download_directory,
+            get_download_queue,
+            set_download_priority,
+            move_download_to_top,
+            set_download_limits,
+            get_download_settings,
+            set_global_download_rate_limit,
+            set_download_rate_limit,
+            set_download_schedule,
+            get_history,
+            delete_history_visit,
+            delete_history_url,
+            delete_history_range,
+            clear_history,
+            search_history,
+            index_page_text,
+            get_page_text_indexing,
+            set_page_text_indexing,
+            get_search_engine,
+            set_search_engine,
             get_tab_info
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                let state = app.state::<AppState>();
+                if let Err(e) = state.session_saver.save_now() {
+                    error!("Failed to save session on exit: {}", e);
+                }
+                state.downloads.lock().unwrap().save_history();
+            }
+        });
 }
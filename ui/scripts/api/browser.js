@@ -1,11 +1,15 @@
 // Vishwakarma Express - Browser API
 const { invoke } = window.__TAURI__.tauri;
 
+// Every tab command is scoped to the window that issues it
+const currentWindow = window.__TAURI__.webviewWindow.getCurrentWebviewWindow();
+const windowId = currentWindow.label;
+
 export class BrowserAPI {
     // Tab Management
-    static async createNewTab(url = null) {
+    static async createNewTab(url = null, openerTabId = null) {
         try {
-            const tabId = await invoke('create_new_tab', { url });
+            const tabId = await invoke('create_new_tab', { windowId, url, openerTabId });
             console.log('New tab created:', tabId);
             return tabId;
         } catch (error) {
@@ -14,9 +18,9 @@ export class BrowserAPI {
         }
     }
 
-    static async closeTab(tabId) {
+    static async closeTab(tabId, force = false) {
         try {
-            await invoke('close_tab', { tabId });
+            await invoke('close_tab', { windowId, tabId, force });
             console.log('Tab closed:', tabId);
             return true;
         } catch (error) {
@@ -25,9 +29,46 @@ export class BrowserAPI {
         }
     }
 
+    static async pinTab(tabId) {
+        try {
+            return await invoke('pin_tab', { windowId, tabId });
+        } catch (error) {
+            console.error('Failed to pin tab:', error);
+            throw error;
+        }
+    }
+
+    static async unpinTab(tabId) {
+        try {
+            return await invoke('unpin_tab', { windowId, tabId });
+        } catch (error) {
+            console.error('Failed to unpin tab:', error);
+            throw error;
+        }
+    }
+
+    static async reopenClosedTab(tabId = null) {
+        try {
+            return await invoke('reopen_closed_tab', { windowId, tabId });
+        } catch (error) {
+            console.error('Failed to reopen closed tab:', error);
+            throw error;
+        }
+    }
+
+    static async getRecentlyClosedTabs() {
+        try {
+            return await invoke('get_recently_closed_tabs', { windowId });
+        } catch (error) {
+            console.error('Failed to get recently closed tabs:', error);
+            throw error;
+        }
+    }
+
+    // `url` is raw address bar input; resolves to the URL actually loaded
     static async navigateToUrl(tabId, url) {
         try {
-            return await invoke('navigate_to_url', { tabId, url });
+            return await invoke('navigate_to_url', { windowId, tabId, url });
         } catch (error) {
             console.error('Failed to navigate:', error);
             throw error;
@@ -36,7 +77,7 @@ export class BrowserAPI {
 
     static async reloadTab(tabId) {
         try {
-            return await invoke('reload_tab', { tabId });
+            return await invoke('reload_tab', { windowId, tabId });
         } catch (error) {
             console.error('Failed to reload tab:', error);
             throw error;
@@ -45,7 +86,7 @@ export class BrowserAPI {
 
     static async goBack(tabId) {
         try {
-            return await invoke('go_back', { tabId });
+            return await invoke('go_back', { windowId, tabId });
         } catch (error) {
             console.error('Failed to go back:', error);
             throw error;
@@ -54,7 +95,7 @@ export class BrowserAPI {
 
     static async goForward(tabId) {
         try {
-            return await invoke('go_forward', { tabId });
+            return await invoke('go_forward', { windowId, tabId });
         } catch (error) {
             console.error('Failed to go forward:', error);
             throw error;
@@ -63,7 +104,7 @@ export class BrowserAPI {
 
     static async getTabInfo(tabId) {
         try {
-            return await invoke('get_tab_info', { tabId });
+            return await invoke('get_tab_info', { windowId, tabId });
         } catch (error) {
             console.error('Failed to get tab info:', error);
             throw error;
@@ -72,7 +113,7 @@ export class BrowserAPI {
 
     static async getAllTabs() {
         try {
-            return await invoke('get_all_tabs');
+            return await invoke('get_all_tabs', { windowId });
         } catch (error) {
             console.error('Failed to get all tabs:', error);
             throw error;
@@ -81,7 +122,7 @@ export class BrowserAPI {
 
     static async setActiveTab(tabId) {
         try {
-            return await invoke('set_active_tab', { tabId });
+            return await invoke('set_active_tab', { windowId, tabId });
         } catch (error) {
             console.error('Failed to set active tab:', error);
             throw error;
@@ -90,13 +131,544 @@ export class BrowserAPI {
 
     static async getActiveTabId() {
         try {
-            return await invoke('get_active_tab_id');
+            return await invoke('get_active_tab_id', { windowId });
         } catch (error) {
             console.error('Failed to get active tab ID:', error);
             throw error;
         }
     }
 
+    // Tab groups
+    static async moveTab(tabId, newIndex) {
+        try {
+            return await invoke('move_tab', { windowId, tabId, newIndex });
+        } catch (error) {
+            console.error('Failed to move tab:', error);
+            throw error;
+        }
+    }
+
+    static async createTabGroup(tabIds, name, color = null) {
+        try {
+            return await invoke('create_tab_group', { windowId, tabIds, name, color });
+        } catch (error) {
+            console.error('Failed to create tab group:', error);
+            throw error;
+        }
+    }
+
+    static async renameTabGroup(groupId, name) {
+        try {
+            return await invoke('rename_tab_group', { windowId, groupId, name });
+        } catch (error) {
+            console.error('Failed to rename tab group:', error);
+            throw error;
+        }
+    }
+
+    static async setTabGroupColor(groupId, color) {
+        try {
+            return await invoke('set_tab_group_color', { windowId, groupId, color });
+        } catch (error) {
+            console.error('Failed to set tab group color:', error);
+            throw error;
+        }
+    }
+
+    static async setTabGroupCollapsed(groupId, collapsed) {
+        try {
+            return await invoke('set_tab_group_collapsed', { windowId, groupId, collapsed });
+        } catch (error) {
+            console.error('Failed to collapse tab group:', error);
+            throw error;
+        }
+    }
+
+    static async addTabToGroup(tabId, groupId) {
+        try {
+            return await invoke('add_tab_to_group', { windowId, tabId, groupId });
+        } catch (error) {
+            console.error('Failed to add tab to group:', error);
+            throw error;
+        }
+    }
+
+    static async removeTabFromGroup(tabId) {
+        try {
+            return await invoke('remove_tab_from_group', { windowId, tabId });
+        } catch (error) {
+            console.error('Failed to remove tab from group:', error);
+            throw error;
+        }
+    }
+
+    static async ungroupTabGroup(groupId) {
+        try {
+            return await invoke('ungroup_tab_group', { windowId, groupId });
+        } catch (error) {
+            console.error('Failed to ungroup tabs:', error);
+            throw error;
+        }
+    }
+
+    static async closeTabGroup(groupId) {
+        try {
+            return await invoke('close_tab_group', { windowId, groupId });
+        } catch (error) {
+            console.error('Failed to close tab group:', error);
+            throw error;
+        }
+    }
+
+    static async moveTabGroup(groupId, newIndex) {
+        try {
+            return await invoke('move_tab_group', { windowId, groupId, newIndex });
+        } catch (error) {
+            console.error('Failed to move tab group:', error);
+            throw error;
+        }
+    }
+
+    static async getTabGroups() {
+        try {
+            return await invoke('get_tab_groups', { windowId });
+        } catch (error) {
+            console.error('Failed to get tab groups:', error);
+            throw error;
+        }
+    }
+
+    // Which tab is activated after closing the active one:
+    // 'next', 'previous', 'opener' or 'most_recently_used'
+    static async getTabSelectionPolicy() {
+        try {
+            return await invoke('get_tab_selection_policy', { windowId });
+        } catch (error) {
+            console.error('Failed to get tab selection policy:', error);
+            throw error;
+        }
+    }
+
+    static async setTabSelectionPolicy(policy) {
+        try {
+            return await invoke('set_tab_selection_policy', { windowId, policy });
+        } catch (error) {
+            console.error('Failed to set tab selection policy:', error);
+            throw error;
+        }
+    }
+
+    // Engine lifecycle events (load_started, committed, title_changed, ...)
+    static async onTabEvent(handler) {
+        try {
+            return await currentWindow.listen('tab-event', (event) => handler(event.payload));
+        } catch (error) {
+            console.error('Failed to listen for tab events:', error);
+            throw error;
+        }
+    }
+
+    // Tab discarding
+    static async discardTab(tabId) {
+        try {
+            return await invoke('discard_tab', { windowId, tabId });
+        } catch (error) {
+            console.error('Failed to discard tab:', error);
+            throw error;
+        }
+    }
+
+    static async discardBackgroundTabs() {
+        try {
+            return await invoke('discard_background_tabs');
+        } catch (error) {
+            console.error('Failed to discard background tabs:', error);
+            throw error;
+        }
+    }
+
+    static async getTabDiscardPolicy() {
+        try {
+            return await invoke('get_tab_discard_policy');
+        } catch (error) {
+            console.error('Failed to get tab discard policy:', error);
+            throw error;
+        }
+    }
+
+    static async setTabDiscardPolicy(policy) {
+        try {
+            return await invoke('set_tab_discard_policy', { policy });
+        } catch (error) {
+            console.error('Failed to set tab discard policy:', error);
+            throw error;
+        }
+    }
+
+    static async onTabsDiscarded(handler) {
+        try {
+            return await currentWindow.listen('tabs-discarded', (event) => handler(event.payload));
+        } catch (error) {
+            console.error('Failed to listen for discarded tabs:', error);
+            throw error;
+        }
+    }
+
+    // Windows
+    static getWindowId() {
+        return windowId;
+    }
+
+    static async openWindow(url = null) {
+        try {
+            return await invoke('open_window', { url });
+        } catch (error) {
+            console.error('Failed to open window:', error);
+            throw error;
+        }
+    }
+
+    static async detachTab(tabId) {
+        try {
+            return await invoke('detach_tab', { windowId, tabId });
+        } catch (error) {
+            console.error('Failed to detach tab:', error);
+            throw error;
+        }
+    }
+
+    static async moveTabToWindow(tabId, targetWindowId, index = null) {
+        try {
+            return await invoke('move_tab_to_window', { windowId, tabId, targetWindowId, index });
+        } catch (error) {
+            console.error('Failed to move tab to window:', error);
+            throw error;
+        }
+    }
+
+    static async getWindows() {
+        try {
+            return await invoke('get_windows');
+        } catch (error) {
+            console.error('Failed to get windows:', error);
+            throw error;
+        }
+    }
+
+    // Downloads
+    // checksum: { algorithm: 'sha256' | 'sha512' | 'md5', value: hex } or null
+    static async startDownload(url, filename = null, checksum = null) {
+        try {
+            return await invoke('start_download', { url, filename, checksum });
+        } catch (error) {
+            console.error('Failed to start download:', error);
+            throw error;
+        }
+    }
+
+    // Resolves to 'verified', 'mismatch' or 'unverified'; null clears the checksum
+    static async setDownloadChecksum(downloadId, checksum) {
+        try {
+            return await invoke('set_download_checksum', { downloadId, checksum });
+        } catch (error) {
+            console.error('Failed to set download checksum:', error);
+            throw error;
+        }
+    }
+
+    static async pauseDownload(downloadId) {
+        try {
+            return await invoke('pause_download', { downloadId });
+        } catch (error) {
+            console.error('Failed to pause download:', error);
+            throw error;
+        }
+    }
+
+    static async resumeDownload(downloadId) {
+        try {
+            return await invoke('resume_download', { downloadId });
+        } catch (error) {
+            console.error('Failed to resume download:', error);
+            throw error;
+        }
+    }
+
+    static async retryDownload(downloadId) {
+        try {
+            return await invoke('retry_download', { downloadId });
+        } catch (error) {
+            console.error('Failed to retry download:', error);
+            throw error;
+        }
+    }
+
+    static async cancelDownload(downloadId) {
+        try {
+            return await invoke('cancel_download', { downloadId });
+        } catch (error) {
+            console.error('Failed to cancel download:', error);
+            throw error;
+        }
+    }
+
+    // Keep or delete a download held back as dangerous
+    static async confirmDownload(downloadId) {
+        try {
+            return await invoke('confirm_download', { downloadId });
+        } catch (error) {
+            console.error('Failed to confirm download:', error);
+            throw error;
+        }
+    }
+
+    static async discardDownload(downloadId) {
+        try {
+            return await invoke('discard_download', { downloadId });
+        } catch (error) {
+            console.error('Failed to discard download:', error);
+            throw error;
+        }
+    }
+
+    static async removeDownload(downloadId) {
+        try {
+            return await invoke('remove_download', { downloadId });
+        } catch (error) {
+            console.error('Failed to remove download:', error);
+            throw error;
+        }
+    }
+
+    static async getDownloads() {
+        try {
+            return await invoke('get_downloads');
+        } catch (error) {
+            console.error('Failed to get downloads:', error);
+            throw error;
+        }
+    }
+
+    static async clearCompletedDownloads() {
+        try {
+            return await invoke('clear_completed_downloads');
+        } catch (error) {
+            console.error('Failed to clear completed downloads:', error);
+            throw error;
+        }
+    }
+
+    static async getDownloadDirectory() {
+        try {
+            return await invoke('get_download_directory');
+        } catch (error) {
+            console.error('Failed to get download directory:', error);
+            throw error;
+        }
+    }
+
+    static async setDownloadDirectory(path) {
+        try {
+            return await invoke('set_download_directory', { path });
+        } catch (error) {
+            console.error('Failed to set download directory:', error);
+            throw error;
+        }
+    }
+
+    static async getDownloadQueue() {
+        try {
+            return await invoke('get_download_queue');
+        } catch (error) {
+            console.error('Failed to get download queue:', error);
+            throw error;
+        }
+    }
+
+    // priority: 'low', 'normal' or 'high'
+    static async setDownloadPriority(downloadId, priority) {
+        try {
+            return await invoke('set_download_priority', { downloadId, priority });
+        } catch (error) {
+            console.error('Failed to set download priority:', error);
+            throw error;
+        }
+    }
+
+    static async moveDownloadToTop(downloadId) {
+        try {
+            return await invoke('move_download_to_top', { downloadId });
+        } catch (error) {
+            console.error('Failed to move download to top:', error);
+            throw error;
+        }
+    }
+
+    static async setDownloadLimits(maxConcurrentDownloads, maxDownloadsPerHost = null) {
+        try {
+            return await invoke('set_download_limits', { maxConcurrentDownloads, maxDownloadsPerHost });
+        } catch (error) {
+            console.error('Failed to set download limits:', error);
+            throw error;
+        }
+    }
+
+    // { global_rate_limit, schedule: { start_minute, end_minute } | null }
+    static async getDownloadSettings() {
+        try {
+            return await invoke('get_download_settings');
+        } catch (error) {
+            console.error('Failed to get download settings:', error);
+            throw error;
+        }
+    }
+
+    // Bytes per second across all downloads; null removes the limit
+    static async setGlobalDownloadRateLimit(bytesPerSecond) {
+        try {
+            return await invoke('set_global_download_rate_limit', { bytesPerSecond });
+        } catch (error) {
+            console.error('Failed to set global download rate limit:', error);
+            throw error;
+        }
+    }
+
+    static async setDownloadRateLimit(downloadId, bytesPerSecond) {
+        try {
+            return await invoke('set_download_rate_limit', { downloadId, bytesPerSecond });
+        } catch (error) {
+            console.error('Failed to set download rate limit:', error);
+            throw error;
+        }
+    }
+
+    // Minutes after midnight, local time; null lets downloads run at any time
+    static async setDownloadSchedule(schedule) {
+        try {
+            return await invoke('set_download_schedule', { schedule });
+        } catch (error) {
+            console.error('Failed to set download schedule:', error);
+            throw error;
+        }
+    }
+
+    // Batches of { id, status, downloaded_bytes, total_bytes, percentage,
+    // bytes_per_second, eta_secs }, sent to every window
+    static async onDownloadProgress(handler) {
+        try {
+            return await window.__TAURI__.event.listen('download-progress', (event) => handler(event.payload));
+        } catch (error) {
+            console.error('Failed to listen for download progress:', error);
+            throw error;
+        }
+    }
+
+    // History
+    // Times are Unix seconds; visits come back newest first
+    static async getHistory({ startTime = null, endTime = null, limit = null, offset = null } = {}) {
+        try {
+            return await invoke('get_history', { startTime, endTime, limit, offset });
+        } catch (error) {
+            console.error('Failed to get history:', error);
+            throw error;
+        }
+    }
+
+    static async deleteHistoryVisit(visitId) {
+        try {
+            return await invoke('delete_history_visit', { visitId });
+        } catch (error) {
+            console.error('Failed to delete history visit:', error);
+            throw error;
+        }
+    }
+
+    static async deleteHistoryUrl(url) {
+        try {
+            return await invoke('delete_history_url', { url });
+        } catch (error) {
+            console.error('Failed to delete history URL:', error);
+            throw error;
+        }
+    }
+
+    static async deleteHistoryRange(startTime = null, endTime = null) {
+        try {
+            return await invoke('delete_history_range', { startTime, endTime });
+        } catch (error) {
+            console.error('Failed to delete history range:', error);
+            throw error;
+        }
+    }
+
+    // Ranked matches on URL, title and indexed page text
+    static async searchHistory(query, { startTime = null, endTime = null, limit = null, offset = null } = {}) {
+        try {
+            return await invoke('search_history', { query, startTime, endTime, limit, offset });
+        } catch (error) {
+            console.error('Failed to search history:', error);
+            throw error;
+        }
+    }
+
+    // Ignored unless page text indexing is enabled
+    static async indexPageText(url, text) {
+        try {
+            return await invoke('index_page_text', { url, text });
+        } catch (error) {
+            console.error('Failed to index page text:', error);
+            throw error;
+        }
+    }
+
+    static async getPageTextIndexing() {
+        try {
+            return await invoke('get_page_text_indexing');
+        } catch (error) {
+            console.error('Failed to get page text indexing:', error);
+            throw error;
+        }
+    }
+
+    static async setPageTextIndexing(enabled) {
+        try {
+            return await invoke('set_page_text_indexing', { enabled });
+        } catch (error) {
+            console.error('Failed to set page text indexing:', error);
+            throw error;
+        }
+    }
+
+    static async clearHistory() {
+        try {
+            return await invoke('clear_history');
+        } catch (error) {
+            console.error('Failed to clear history:', error);
+            throw error;
+        }
+    }
+
+    // Search engine
+    // A built-in engine ('google', 'bing' or 'duckduckgo'), or a custom one
+    // when a template containing {searchTerms} is given
+    static async getSearchEngine() {
+        try {
+            return await invoke('get_search_engine');
+        } catch (error) {
+            console.error('Failed to get search engine:', error);
+            throw error;
+        }
+    }
+
+    static async setSearchEngine(engine, template = null) {
+        try {
+            return await invoke('set_search_engine', { engine, template });
+        } catch (error) {
+            console.error('Failed to set search engine:', error);
+            throw error;
+        }
+    }
+
     // Utility functions
     static isValidUrl(string) {
         try {
@@ -1,25 +1,44 @@
 // Download Manager Component
+import { BrowserAPI } from '../api/browser.js';
+
+// Backend download statuses and the names the panel uses for them
+const STATUS_KEYS = {
+    Pending: 'queued',
+    InProgress: 'downloading',
+    Paused: 'paused',
+    Completed: 'completed',
+    Failed: 'failed',
+    Cancelled: 'cancelled',
+    Blocked: 'blocked',
+    AwaitingConfirmation: 'dangerous'
+};
+
 export class DownloadManager {
     constructor() {
         this.downloads = [];
-        this.storageKey = 'vishwakarma_downloads';
-        this.maxDownloads = 100;
         this.isVisible = false;
-        
+        this.unlistenProgress = null;
+
         this.init();
     }
 
     async init() {
-        this.loadDownloads();
-        this.setupEventListeners();
         this.createDownloadPanel();
+        this.setupEventListeners();
+        await this.refreshDownloads();
+
+        try {
+            this.unlistenProgress = await BrowserAPI.onDownloadProgress((batch) => this.handleProgress(batch));
+        } catch (error) {
+            console.error('Download progress unavailable:', error);
+        }
     }
 
     createDownloadPanel() {
         const panel = document.createElement('div');
         panel.id = 'download-panel';
         panel.className = 'download-panel';
-        
+
         panel.innerHTML = `
             <div class="download-header">
                 <div class="download-title">
@@ -28,11 +47,11 @@ export class DownloadManager {
                     <span class="download-count" id="download-count">0</span>
                 </div>
                 <div class="download-controls">
-                    <button class="btn btn-secondary" id="clear-downloads">Clear All</button>
+                    <button class="btn btn-secondary" id="clear-downloads">Clear Finished</button>
                     <button class="btn btn-secondary" id="close-downloads">&times;</button>
                 </div>
             </div>
-            
+
             <div class="download-content" id="download-content">
                 <div class="download-empty" id="download-empty">
                     <div class="download-empty-icon">📥</div>
@@ -41,7 +60,7 @@ export class DownloadManager {
                 </div>
             </div>
         `;
-        
+
         document.body.appendChild(panel);
         this.setupPanelEvents(panel);
     }
@@ -53,21 +72,9 @@ export class DownloadManager {
             downloadBtn.addEventListener('click', () => this.togglePanel());
         }
 
-        // Listen for download events from browser
-        document.addEventListener('download-started', (e) => {
-            this.addDownload(e.detail);
-        });
-
-        document.addEventListener('download-progress', (e) => {
-            this.updateDownloadProgress(e.detail.id, e.detail.progress);
-        });
-
-        document.addEventListener('download-completed', (e) => {
-            this.completeDownload(e.detail.id);
-        });
-
-        document.addEventListener('download-failed', (e) => {
-            this.failDownload(e.detail.id, e.detail.error);
+        // Other components ask for downloads through this event
+        document.addEventListener('download-request', (e) => {
+            this.startDownload(e.detail.url, e.detail.filename);
         });
     }
 
@@ -77,9 +84,9 @@ export class DownloadManager {
             this.hidePanel();
         });
 
-        // Clear all button
+        // Clear finished button
         panel.querySelector('#clear-downloads').addEventListener('click', () => {
-            this.clearAllDownloads();
+            this.clearFinishedDownloads();
         });
 
         // Click outside to close
@@ -111,139 +118,149 @@ export class DownloadManager {
         this.isVisible = false;
     }
 
-    addDownload(downloadInfo) {
-        const download = {
-            id: downloadInfo.id || 'dl_' + Date.now() + '_' + Math.random().toString(36).substr(2, 9),
-            filename: downloadInfo.filename || 'Unknown File',
-            url: downloadInfo.url || '',
-            size: downloadInfo.size || 0,
-            progress: 0,
-            status: 'downloading', // downloading, completed, failed, paused
-            startTime: Date.now(),
-            endTime: null,
-            error: null,
-            path: downloadInfo.path || null
-        };
-
-        this.downloads.unshift(download);
-        
-        // Limit downloads history
-        if (this.downloads.length > this.maxDownloads) {
-            this.downloads = this.downloads.slice(0, this.maxDownloads);
+    async refreshDownloads() {
+        try {
+            const downloads = await BrowserAPI.getDownloads();
+            // Newest first
+            this.downloads = downloads.reverse().map(download => this.fromBackend(download));
+        } catch (error) {
+            this.downloads = [];
         }
-
         this.renderDownloads();
-        this.saveDownloads();
         this.updateDownloadCount();
-        
-        // Show notification
-        this.showToast(`Download started: ${download.filename}`, 'info');
-        
-        return download.id;
     }
 
-    updateDownloadProgress(downloadId, progress) {
-        const download = this.downloads.find(d => d.id === downloadId);
-        if (download) {
-            download.progress = Math.min(100, Math.max(0, progress));
+    fromBackend(download) {
+        return {
+            id: download.id,
+            filename: download.filename,
+            url: download.url,
+            path: download.file_path,
+            size: download.total_bytes,
+            downloadedBytes: download.downloaded_bytes,
+            progress: this.percentage(download),
+            status: STATUS_KEYS[download.status] || 'failed',
+            startTime: download.start_time,
+            endTime: download.end_time,
+            error: download.danger_reason || download.error_message || download.last_error,
+            bytesPerSecond: download.bytes_per_second,
+            etaSecs: download.eta_secs,
+            elapsedSecs: Math.floor(download.elapsed_ms / 1000)
+        };
+    }
+
+    handleProgress(batch) {
+        let needsRefresh = false;
+
+        batch.forEach(update => {
+            const download = this.downloads.find(d => d.id === update.id);
+            if (!download) {
+                needsRefresh = true;
+                return;
+            }
+
+            const status = STATUS_KEYS[update.status] || 'failed';
+            if (status !== download.status) {
+                // Filenames, errors and end times only come with the full item
+                needsRefresh = true;
+                if (status === 'completed') {
+                    this.showToast(`Download completed: ${download.filename}`, 'success');
+                } else if (status === 'failed') {
+                    this.showToast(`Download failed: ${download.filename}`, 'error');
+                } else if (status === 'dangerous') {
+                    this.showToast(`${download.filename} may be dangerous. Keep it?`, 'warning');
+                } else if (status === 'blocked') {
+                    this.showToast(`Download blocked: ${download.filename}`, 'error');
+                }
+            }
+
+            download.status = status;
+            download.downloadedBytes = update.downloaded_bytes;
+            download.size = update.total_bytes;
+            download.progress = update.percentage;
+            download.bytesPerSecond = update.bytes_per_second;
+            download.etaSecs = update.eta_secs;
+            download.elapsedSecs = update.elapsed_secs;
             this.updateDownloadElement(download);
+        });
+
+        if (needsRefresh) {
+            this.refreshDownloads();
+        } else {
+            this.updateDownloadCount();
         }
     }
 
-    completeDownload(downloadId) {
-        const download = this.downloads.find(d => d.id === downloadId);
-        if (download) {
-            download.status = 'completed';
-            download.progress = 100;
-            download.endTime = Date.now();
-            
-            this.updateDownloadElement(download);
-            this.saveDownloads();
-            
-            this.showToast(`Download completed: ${download.filename}`, 'success');
+    async startDownload(url, filename = null) {
+        try {
+            const downloadId = await BrowserAPI.startDownload(url, filename);
+            await this.refreshDownloads();
+
+            const download = this.downloads.find(d => d.id === downloadId);
+            this.showToast(`Download started: ${download ? download.filename : url}`, 'info');
+            return downloadId;
+        } catch (error) {
+            this.showToast(`Download failed: ${error}`, 'error');
+            return null;
         }
     }
 
-    failDownload(downloadId, error) {
-        const download = this.downloads.find(d => d.id === downloadId);
-        if (download) {
-            download.status = 'failed';
-            download.error = error;
-            download.endTime = Date.now();
-            
-            this.updateDownloadElement(download);
-            this.saveDownloads();
-            
-            this.showToast(`Download failed: ${download.filename}`, 'error');
+    async runAction(action, downloadId, failureMessage) {
+        try {
+            await action(downloadId);
+        } catch (error) {
+            this.showToast(`${failureMessage}: ${error}`, 'error');
         }
+        await this.refreshDownloads();
     }
 
     pauseDownload(downloadId) {
-        const download = this.downloads.find(d => d.id === downloadId);
-        if (download && download.status === 'downloading') {
-            download.status = 'paused';
-            this.updateDownloadElement(download);
-            this.saveDownloads();
-            
-            // Emit pause event
-            document.dispatchEvent(new CustomEvent('download-pause', {
-                detail: { id: downloadId }
-            }));
-        }
+        return this.runAction((id) => BrowserAPI.pauseDownload(id), downloadId, 'Could not pause download');
     }
 
     resumeDownload(downloadId) {
-        const download = this.downloads.find(d => d.id === downloadId);
-        if (download && download.status === 'paused') {
-            download.status = 'downloading';
-            this.updateDownloadElement(download);
-            this.saveDownloads();
-            
-            // Emit resume event
-            document.dispatchEvent(new CustomEvent('download-resume', {
-                detail: { id: downloadId }
-            }));
-        }
+        return this.runAction((id) => BrowserAPI.resumeDownload(id), downloadId, 'Could not resume download');
     }
 
     cancelDownload(downloadId) {
-        const download = this.downloads.find(d => d.id === downloadId);
-        if (download) {
-            download.status = 'failed';
-            download.error = 'Cancelled by user';
-            download.endTime = Date.now();
-            
-            this.updateDownloadElement(download);
-            this.saveDownloads();
-            
-            // Emit cancel event
-            document.dispatchEvent(new CustomEvent('download-cancel', {
-                detail: { id: downloadId }
-            }));
-        }
+        return this.runAction((id) => BrowserAPI.cancelDownload(id), downloadId, 'Could not cancel download');
+    }
+
+    confirmDownload(downloadId) {
+        return this.runAction((id) => BrowserAPI.confirmDownload(id), downloadId, 'Could not keep download');
+    }
+
+    discardDownload(downloadId) {
+        return this.runAction((id) => BrowserAPI.discardDownload(id), downloadId, 'Could not discard download');
     }
 
     removeDownload(downloadId) {
-        this.downloads = this.downloads.filter(d => d.id !== downloadId);
-        this.renderDownloads();
-        this.saveDownloads();
-        this.updateDownloadCount();
+        return this.runAction((id) => BrowserAPI.removeDownload(id), downloadId, 'Could not remove download');
+    }
+
+    retryDownload(download) {
+        this.showToast(`Retrying download: ${download.filename}`, 'info');
+        return this.runAction((id) => BrowserAPI.retryDownload(id), download.id, 'Could not retry download');
+    }
+
+    moveDownloadToTop(downloadId) {
+        return this.runAction((id) => BrowserAPI.moveDownloadToTop(id), downloadId, 'Could not move download');
     }
 
     renderDownloads() {
         const content = document.getElementById('download-content');
         const emptyState = document.getElementById('download-empty');
-        
+
+        // Remove existing download items
+        content.querySelectorAll('.download-item').forEach(item => item.remove());
+
         if (this.downloads.length === 0) {
             emptyState.style.display = 'block';
             return;
         }
-        
+
         emptyState.style.display = 'none';
-        
-        // Remove existing download items
-        content.querySelectorAll('.download-item').forEach(item => item.remove());
-        
+
         // Add download items
         this.downloads.forEach(download => {
             const downloadElement = this.createDownloadElement(download);
@@ -255,11 +272,10 @@ export class DownloadManager {
         const element = document.createElement('div');
         element.className = `download-item ${download.status}`;
         element.setAttribute('data-download-id', download.id);
-        
+
         const statusIcon = this.getStatusIcon(download.status);
-        const fileSize = this.formatFileSize(download.size);
         const duration = this.getDownloadDuration(download);
-        
+
         element.innerHTML = `
             <div class="download-icon">${statusIcon}</div>
             <div class="download-info">
@@ -267,16 +283,17 @@ export class DownloadManager {
                     ${this.escapeHtml(download.filename)}
                 </div>
                 <div class="download-details">
-                    <span class="download-size">${fileSize}</span>
+                    <span class="download-size">${this.getSizeText(download)}</span>
+                    <span class="download-speed">${this.getSpeedText(download)}</span>
                     <span class="download-duration">${duration}</span>
                     ${download.error ? `<span class="download-error">${this.escapeHtml(download.error)}</span>` : ''}
                 </div>
-                ${download.status === 'downloading' || download.status === 'paused' ? `
-                    <div class="download-progress">
+                ${this.isActive(download) ? `
+                    <div class="download-progress${download.progress === null ? ' indeterminate' : ''}">
                         <div class="download-progress-bar">
-                            <div class="download-progress-fill" style="width: ${download.progress}%"></div>
+                            <div class="download-progress-fill" style="width: ${this.getProgressWidth(download)}"></div>
                         </div>
-                        <span class="download-percentage">${Math.round(download.progress)}%</span>
+                        <span class="download-percentage">${this.getPercentageText(download)}</span>
                     </div>
                 ` : ''}
             </div>
@@ -284,16 +301,20 @@ export class DownloadManager {
                 ${this.getDownloadActions(download)}
             </div>
         `;
-        
+
         this.addDownloadEventListeners(element, download);
         return element;
     }
 
     getStatusIcon(status) {
         const icons = {
+            queued: '🕒',
             downloading: '⬇️',
             completed: '✅',
             failed: '❌',
+            cancelled: '🚫',
+            blocked: '⛔',
+            dangerous: '⚠️',
             paused: '⏸️'
         };
         return icons[status] || '📄';
@@ -301,6 +322,12 @@ export class DownloadManager {
 
     getDownloadActions(download) {
         switch (download.status) {
+            case 'queued':
+                return `
+                    <button class="download-action-btn top-btn" title="Start next">⏫</button>
+                    <button class="download-action-btn pause-btn" title="Pause">⏸️</button>
+                    <button class="download-action-btn cancel-btn" title="Cancel">❌</button>
+                `;
             case 'downloading':
                 return `
                     <button class="download-action-btn pause-btn" title="Pause">⏸️</button>
@@ -321,12 +348,31 @@ export class DownloadManager {
                     <button class="download-action-btn retry-btn" title="Retry">🔄</button>
                     <button class="download-action-btn remove-btn" title="Remove">🗑️</button>
                 `;
+            case 'cancelled':
+                return `
+                    <button class="download-action-btn remove-btn" title="Remove">🗑️</button>
+                `;
+            case 'dangerous':
+                return `
+                    <button class="download-action-btn keep-btn" title="Keep">✔️</button>
+                    <button class="download-action-btn discard-btn" title="Discard">🗑️</button>
+                `;
+            case 'blocked':
+                return `
+                    <button class="download-action-btn discard-btn" title="Discard">🗑️</button>
+                `;
             default:
                 return '';
         }
     }
 
     addDownloadEventListeners(element, download) {
+        // Start next button
+        const topBtn = element.querySelector('.top-btn');
+        if (topBtn) {
+            topBtn.addEventListener('click', () => this.moveDownloadToTop(download.id));
+        }
+
         // Pause button
         const pauseBtn = element.querySelector('.pause-btn');
         if (pauseBtn) {
@@ -345,6 +391,17 @@ export class DownloadManager {
             cancelBtn.addEventListener('click', () => this.cancelDownload(download.id));
         }
 
+        // Keep and discard buttons for dangerous downloads
+        const keepBtn = element.querySelector('.keep-btn');
+        if (keepBtn) {
+            keepBtn.addEventListener('click', () => this.confirmDownload(download.id));
+        }
+
+        const discardBtn = element.querySelector('.discard-btn');
+        if (discardBtn) {
+            discardBtn.addEventListener('click', () => this.discardDownload(download.id));
+        }
+
         // Remove button
         const removeBtn = element.querySelector('.remove-btn');
         if (removeBtn) {
@@ -377,18 +434,35 @@ export class DownloadManager {
         const element = document.querySelector(`[data-download-id="${download.id}"]`);
         if (element) {
             // Update progress if downloading
-            if (download.status === 'downloading' || download.status === 'paused') {
+            if (this.isActive(download)) {
+                const progress = element.querySelector('.download-progress');
                 const progressFill = element.querySelector('.download-progress-fill');
                 const percentage = element.querySelector('.download-percentage');
-                
+
+                if (progress) {
+                    progress.classList.toggle('indeterminate', download.progress === null);
+                }
                 if (progressFill) {
-                    progressFill.style.width = `${download.progress}%`;
+                    progressFill.style.width = this.getProgressWidth(download);
                 }
                 if (percentage) {
-                    percentage.textContent = `${Math.round(download.progress)}%`;
+                    percentage.textContent = this.getPercentageText(download);
                 }
             }
-            
+
+            const size = element.querySelector('.download-size');
+            if (size) {
+                size.textContent = this.getSizeText(download);
+            }
+            const speed = element.querySelector('.download-speed');
+            if (speed) {
+                speed.textContent = this.getSpeedText(download);
+            }
+            const duration = element.querySelector('.download-duration');
+            if (duration) {
+                duration.textContent = this.getDownloadDuration(download);
+            }
+
             // Update status class and actions
             element.className = `download-item ${download.status}`;
             const actionsContainer = element.querySelector('.download-actions');
@@ -396,7 +470,7 @@ export class DownloadManager {
                 actionsContainer.innerHTML = this.getDownloadActions(download);
                 this.addDownloadEventListeners(element, download);
             }
-            
+
             // Update icon
             const iconElement = element.querySelector('.download-icon');
             if (iconElement) {
@@ -414,60 +488,35 @@ export class DownloadManager {
         }
     }
 
-    retryDownload(download) {
-        // Reset download state
-        download.status = 'downloading';
-        download.progress = 0;
-        download.error = null;
-        download.startTime = Date.now();
-        download.endTime = null;
-        
-        this.updateDownloadElement(download);
-        this.saveDownloads();
-        
-        // Emit retry event
-        document.dispatchEvent(new CustomEvent('download-retry', {
-            detail: { 
-                id: download.id,
-                url: download.url,
-                filename: download.filename
-            }
-        }));
-        
-        this.showToast(`Retrying download: ${download.filename}`, 'info');
+    async clearFinishedDownloads() {
+        try {
+            await BrowserAPI.clearCompletedDownloads();
+            this.showToast('Finished downloads cleared', 'success');
+        } catch (error) {
+            this.showToast(`Could not clear downloads: ${error}`, 'error');
+        }
+        await this.refreshDownloads();
     }
 
-    clearAllDownloads() {
-        if (confirm('Are you sure you want to clear all downloads?')) {
-            this.downloads = [];
-            this.renderDownloads();
-            this.saveDownloads();
-            this.updateDownloadCount();
-            this.showToast('All downloads cleared', 'success');
-        }
+    isActive(download) {
+        return download.status === 'downloading' || download.status === 'paused' || download.status === 'queued';
     }
 
     updateDownloadCount() {
+        const activeCount = this.getActiveDownloads().length;
+
         const countElement = document.getElementById('download-count');
         if (countElement) {
-            const activeDownloads = this.downloads.filter(d => 
-                d.status === 'downloading' || d.status === 'paused'
-            ).length;
-            
-            countElement.textContent = activeDownloads;
-            countElement.style.display = activeDownloads > 0 ? 'inline' : 'none';
+            countElement.textContent = activeCount;
+            countElement.style.display = activeCount > 0 ? 'inline' : 'none';
         }
-        
+
         // Update toolbar button badge
         const downloadBtn = document.getElementById('download-btn');
         if (downloadBtn) {
-            const badge = downloadBtn.querySelector('.download-badge') || 
+            const badge = downloadBtn.querySelector('.download-badge') ||
                          this.createDownloadBadge(downloadBtn);
-            
-            const activeCount = this.downloads.filter(d => 
-                d.status === 'downloading' || d.status === 'paused'
-            ).length;
-            
+
             if (activeCount > 0) {
                 badge.textContent = activeCount;
                 badge.style.display = 'block';
@@ -499,11 +548,12 @@ export class DownloadManager {
         return badge;
     }
 
+    // Time spent transferring, not counting the queue or pauses
     getDownloadDuration(download) {
-        const startTime = new Date(download.startTime);
-        const endTime = download.endTime ? new Date(download.endTime) : new Date();
-        const duration = Math.floor((endTime - startTime) / 1000);
-        
+        return download.elapsedSecs ? this.formatDuration(download.elapsedSecs) : '';
+    }
+
+    formatDuration(duration) {
         if (duration < 60) {
             return `${duration}s`;
         } else if (duration < 3600) {
@@ -515,41 +565,47 @@ export class DownloadManager {
         }
     }
 
-    formatFileSize(bytes) {
-        if (bytes === 0) return '0 B';
-        
-        const k = 1024;
-        const sizes = ['B', 'KB', 'MB', 'GB', 'TB'];
-        const i = Math.floor(Math.log(bytes) / Math.log(k));
-        
-        return parseFloat((bytes / Math.pow(k, i)).toFixed(2)) + ' ' + sizes[i];
+    getSizeText(download) {
+        if (download.status === 'completed' || !download.size) {
+            return this.formatFileSize(download.size || download.downloadedBytes);
+        }
+        return `${this.formatFileSize(download.downloadedBytes)} of ${this.formatFileSize(download.size)}`;
     }
 
-    loadDownloads() {
-        try {
-            const saved = localStorage.getItem(this.storageKey);
-            if (saved) {
-                this.downloads = JSON.parse(saved);
-                // Reset any downloading/paused downloads to failed on startup
-                this.downloads.forEach(download => {
-                    if (download.status === 'downloading' || download.status === 'paused') {
-                        download.status = 'failed';
-                        download.error = 'Browser restart';
-                    }
-                });
-            }
-        } catch (error) {
-            console.error('Failed to load downloads:', error);
-            this.downloads = [];
+    getSpeedText(download) {
+        if (download.status !== 'downloading' || !download.bytesPerSecond) {
+            return '';
         }
+        const speed = `${this.formatFileSize(download.bytesPerSecond)}/s`;
+        if (download.etaSecs === null || download.etaSecs === undefined) {
+            return speed;
+        }
+        return `${speed} · ${this.formatDuration(download.etaSecs)} left`;
     }
 
-    saveDownloads() {
-        try {
-            localStorage.setItem(this.storageKey, JSON.stringify(this.downloads));
-        } catch (error) {
-            console.error('Failed to save downloads:', error);
-        }
+    // null while the size is unknown
+    percentage(download) {
+        if (download.status === 'Completed') return 100;
+        if (download.total_bytes === null || download.total_bytes === undefined) return null;
+        return download.total_bytes ? Math.min(100, (download.downloaded_bytes / download.total_bytes) * 100) : 0;
+    }
+
+    getProgressWidth(download) {
+        return download.progress === null ? '100%' : `${download.progress}%`;
+    }
+
+    getPercentageText(download) {
+        return download.progress === null ? '' : `${Math.round(download.progress)}%`;
+    }
+
+    formatFileSize(bytes) {
+        if (!bytes) return '0 B';
+
+        const k = 1024;
+        const sizes = ['B', 'KB', 'MB', 'GB', 'TB'];
+        const i = Math.floor(Math.log(bytes) / Math.log(k));
+
+        return parseFloat((bytes / Math.pow(k, i)).toFixed(2)) + ' ' + sizes[i];
     }
 
     escapeHtml(text) {
@@ -559,19 +615,7 @@ export class DownloadManager {
     }
 
     showToast(message, type = 'info') {
-        const toast = document.createElement('div');
-        toast.className = `toast ${type} show`;
-        toast.innerHTML = `
-            <div class="toast-content">
-                <div class="toast-message">${message}</div>
-            </div>
-        `;
-        
-        document.body.appendChild(toast);
-        setTimeout(() => {
-            toast.classList.remove('show');
-            setTimeout(() => toast.remove(), 300);
-        }, 3000);
+        BrowserAPI.showToast(this.escapeHtml(String(message)), type);
     }
 
     // Public API
@@ -580,36 +624,10 @@ export class DownloadManager {
     }
 
     getActiveDownloads() {
-        return this.downloads.filter(d => 
-            d.status === 'downloading' || d.status === 'paused'
-        );
+        return this.downloads.filter(d => this.isActive(d));
     }
 
     getCompletedDownloads() {
         return this.downloads.filter(d => d.status === 'completed');
     }
-
-    // Simulate download for testing
-    simulateDownload(filename, size = 1024 * 1024) {
-        const downloadId = this.addDownload({
-            filename: filename,
-            url: 'https://example.com/' + filename,
-            size: size
-        });
-        
-        // Simulate progress
-        let progress = 0;
-        const interval = setInterval(() => {
-            progress += Math.random() * 10;
-            if (progress >= 100) {
-                progress = 100;
-                this.completeDownload(downloadId);
-                clearInterval(interval);
-            } else {
-                this.updateDownloadProgress(downloadId, progress);
-            }
-        }, 500);
-        
-        return downloadId;
-    }
 }
@@ -1,9 +1,13 @@
 // History Manager Component
+import { BrowserAPI } from '../api/browser.js';
+
 export class HistoryManager {
     constructor() {
+        // Most recent visits, newest first. The backend records them.
         this.history = [];
-        this.storageKey = 'vishwakarma_history';
-        this.maxHistoryItems = 1000;
+        this.pageSize = 200;
+        this.searchDelay = 150;
+        this.searchTimer = null;
         
         this.init();
     }
@@ -15,20 +19,26 @@ export class HistoryManager {
 
     async loadHistory() {
         try {
-            const historyData = localStorage.getItem(this.storageKey);
-            this.history = historyData ? JSON.parse(historyData) : [];
-            
-            // Clean old entries if exceeding limit
-            if (this.history.length > this.maxHistoryItems) {
-                this.history = this.history.slice(-this.maxHistoryItems);
-                await this.saveHistory();
-            }
+            const visits = await BrowserAPI.getHistory({ limit: this.pageSize });
+            this.history = visits.map(visit => this.fromBackend(visit));
         } catch (error) {
             console.error('Failed to load history:', error);
             this.history = [];
         }
     }
 
+    fromBackend(visit) {
+        return {
+            id: visit.id,
+            url: visit.url,
+            title: visit.title || this.extractTitleFromUrl(visit.url),
+            timestamp: new Date(visit.visited_at * 1000).toISOString(),
+            transition: visit.transition,
+            favicon_url: this.getFaviconUrl(visit.url),
+            visit_count: visit.visit_count
+        };
+    }
+
     setupEventListeners() {
         // History button in toolbar
         const historyBtn = document.getElementById('history-btn');
@@ -37,67 +47,12 @@ export class HistoryManager {
         }
     }
 
-    async addHistoryEntry(url, title, tabId) {
-        // Don't add certain URLs to history
-        if (this.shouldSkipUrl(url)) return;
-
-        // Check if this URL was just visited (avoid duplicates)
-        const lastEntry = this.history[this.history.length - 1];
-        if (lastEntry && lastEntry.url === url && 
-            Date.now() - new Date(lastEntry.timestamp).getTime() < 5000) {
-            return;
-        }
-
-        const entry = {
-            id: 'hist_' + Date.now() + '_' + Math.random().toString(36).substr(2, 9),
-            url: url,
-            title: title || this.extractTitleFromUrl(url),
-            timestamp: new Date().toISOString(),
-            tab_id: tabId,
-            favicon_url: await this.getFaviconUrl(url),
-            visit_count: 1
-        };
-
-        // Check if URL exists in recent history and update visit count
-        const existingIndex = this.history.findIndex(h => h.url === url);
-        if (existingIndex !== -1) {
-            const existing = this.history[existingIndex];
-            existing.visit_count++;
-            existing.timestamp = entry.timestamp;
-            // Move to end
-            this.history.splice(existingIndex, 1);
-            this.history.push(existing);
-        } else {
-            this.history.push(entry);
-        }
-
-        // Limit history size
-        if (this.history.length > this.maxHistoryItems) {
-            this.history.shift();
-        }
-
-        await this.saveHistory();
-    }
-
-    shouldSkipUrl(url) {
-        const skipPatterns = [
-            'about:blank',
-            'chrome://',
-            'moz-extension://',
-            'data:',
-            'blob:',
-            'javascript:'
-        ];
-
-        return skipPatterns.some(pattern => url.startsWith(pattern));
-    }
-
-    showHistoryPanel() {
+    async showHistoryPanel() {
+        await this.loadHistory();
         const panel = document.createElement('div');
         panel.className = 'history-panel show';
         
         const recentHistory = this.getRecentHistory(50);
-        const groupedHistory = this.groupHistoryByDate(recentHistory);
         
         panel.innerHTML = `
             <div class="history-header">
@@ -109,14 +64,7 @@ export class HistoryManager {
                 </div>
             </div>
             <div class="history-content">
-                ${Object.entries(groupedHistory).map(([date, entries]) => `
-                    <div class="history-group">
-                        <div class="history-date">${this.formatDate(date)}</div>
-                        <div class="history-entries">
-                            ${entries.map(entry => this.createHistoryEntryHTML(entry)).join('')}
-                        </div>
-                    </div>
-                `).join('')}
+                ${this.renderHistoryGroups(recentHistory)}
                 ${recentHistory.length === 0 ? '<div class="history-empty">No history yet</div>' : ''}
             </div>
         `;
@@ -142,11 +90,27 @@ export class HistoryManager {
         // Search functionality
         const searchInput = panel.querySelector('#history-search');
         searchInput.addEventListener('input', (e) => {
-            this.filterHistory(panel, e.target.value);
+            clearTimeout(this.searchTimer);
+            this.searchTimer = setTimeout(() => this.filterHistory(panel, e.target.value), this.searchDelay);
         });
 
-        // History entry clicks
+        this.setupHistoryEntryEvents(panel);
+
+        // Close on outside click
+        panel.addEventListener('click', (e) => {
+            if (e.target === panel) {
+                panel.remove();
+            }
+        });
+    }
+
+    setupHistoryEntryEvents(panel) {
         panel.querySelectorAll('.history-entry').forEach(entry => {
+            entry.querySelector('.history-delete').addEventListener('click', async (e) => {
+                e.stopPropagation();
+                await this.removeHistoryEntry(entry.dataset);
+            });
+
             entry.addEventListener('click', async () => {
                 const url = entry.dataset.url;
                 await this.openHistoryEntry(url);
@@ -158,13 +122,18 @@ export class HistoryManager {
                 this.showHistoryContextMenu(e, entry.dataset);
             });
         });
+    }
 
-        // Close on outside click
-        panel.addEventListener('click', (e) => {
-            if (e.target === panel) {
-                panel.remove();
-            }
-        });
+    renderHistoryGroups(entries) {
+        const groupedHistory = this.groupHistoryByDate(entries);
+        return Object.entries(groupedHistory).map(([date, group]) => `
+            <div class="history-group">
+                <div class="history-date">${this.formatDate(date)}</div>
+                <div class="history-entries">
+                    ${group.map(entry => this.createHistoryEntryHTML(entry)).join('')}
+                </div>
+            </div>
+        `).join('');
     }
 
     createHistoryEntryHTML(entry) {
@@ -174,17 +143,18 @@ export class HistoryManager {
             : '<div class="history-favicon">🌐</div>';
         
         return `
-            <div class="history-entry" data-url="${this.escapeHtml(entry.url)}" data-id="${entry.id}">
+            <div class="history-entry" data-url="${this.escapeHtml(entry.url)}" data-id="${entry.id ?? ''}">
                 ${favicon}
                 <div class="history-info">
                     <div class="history-title">${this.escapeHtml(entry.title)}</div>
                     <div class="history-url">${this.escapeHtml(entry.url)}</div>
+                    ${entry.snippet ? `<div class="history-snippet">${this.escapeHtml(entry.snippet)}</div>` : ''}
                     <div class="history-meta">
                         <span class="history-time">${timeAgo}</span>
                         ${entry.visit_count > 1 ? `<span class="history-visits">${entry.visit_count} visits</span>` : ''}
                     </div>
                 </div>
-                <button class="history-delete" data-id="${entry.id}" title="Remove from history">
+                <button class="history-delete" title="Remove from history">
                     <svg width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2">
                         <path d="M18 6L6 18M6 6l12 12"/>
                     </svg>
@@ -243,36 +213,21 @@ export class HistoryManager {
         return time.toLocaleDateString();
     }
 
-    filterHistory(panel, query) {
-        const entries = panel.querySelectorAll('.history-entry');
-        const groups = panel.querySelectorAll('.history-group');
-        
-        if (!query.trim()) {
-            entries.forEach(entry => entry.style.display = '');
-            groups.forEach(group => group.style.display = '');
-            return;
-        }
-        
-        const lowerQuery = query.toLowerCase();
-        
-        groups.forEach(group => {
-            const groupEntries = group.querySelectorAll('.history-entry');
-            let hasVisibleEntries = false;
-            
-            groupEntries.forEach(entry => {
-                const title = entry.querySelector('.history-title').textContent.toLowerCase();
-                const url = entry.querySelector('.history-url').textContent.toLowerCase();
-                
-                if (title.includes(lowerQuery) || url.includes(lowerQuery)) {
-                    entry.style.display = '';
-                    hasVisibleEntries = true;
-                } else {
-                    entry.style.display = 'none';
-                }
-            });
-            
-            group.style.display = hasVisibleEntries ? '' : 'none';
-        });
+    async filterHistory(panel, query) {
+        const content = panel.querySelector('.history-content');
+        const entries = query.trim()
+            ? await this.searchHistory(query, 50)
+            : this.getRecentHistory(50);
+        // A newer search may have finished first
+        if (panel.querySelector('#history-search').value !== query) return;
+
+        const emptyMessage = query.trim() ? 'No matching pages' : 'No history yet';
+        content.innerHTML = entries.length > 0
+            ? (query.trim()
+                ? `<div class="history-entries">${entries.map(entry => this.createHistoryEntryHTML(entry)).join('')}</div>`
+                : this.renderHistoryGroups(entries))
+            : `<div class="history-empty">${emptyMessage}</div>`;
+        this.setupHistoryEntryEvents(panel);
     }
 
     async openHistoryEntry(url) {
@@ -343,63 +298,84 @@ export class HistoryManager {
                 this.showToast('URL copied to clipboard', 'success');
                 break;
             case 'delete':
-                await this.removeHistoryEntry(entryData.id);
-                this.showToast('Removed from history', 'success');
+                if (await this.removeHistoryEntry(entryData)) {
+                    this.showToast('Removed from history', 'success');
+                }
                 break;
         }
     }
 
-    async removeHistoryEntry(entryId) {
-        this.history = this.history.filter(h => h.id !== entryId);
-        await this.saveHistory();
+    // Removes a single visit, or every visit to a page found by search
+    async removeHistoryEntry({ id, url }) {
+        try {
+            if (id) {
+                await BrowserAPI.deleteHistoryVisit(Number(id));
+            } else {
+                await BrowserAPI.deleteHistoryUrl(url);
+            }
+        } catch (error) {
+            this.showToast('Failed to remove from history', 'error');
+            return false;
+        }
+        this.history = this.history.filter(h => id ? h.id !== Number(id) : h.url !== url);
         
         // Update UI if history panel is open
         const panel = document.querySelector('.history-panel');
         if (panel) {
-            const entry = panel.querySelector(`[data-id="${entryId}"]`);
-            if (entry) {
-                entry.remove();
-            }
+            panel.querySelectorAll('.history-entry').forEach(entry => {
+                if (id ? entry.dataset.id === id : entry.dataset.url === url) {
+                    entry.remove();
+                }
+            });
         }
+        return true;
     }
 
     async clearHistory() {
-        this.history = [];
-        await this.saveHistory();
+        try {
+            await BrowserAPI.clearHistory();
+            this.history = [];
+        } catch (error) {
+            this.showToast('Failed to clear history', 'error');
+        }
     }
 
     getRecentHistory(limit = 50) {
-        return this.history
-            .slice(-limit)
-            .reverse();
+        return this.history.slice(0, limit);
     }
 
-    searchHistory(query, limit = 20) {
-        const lowerQuery = query.toLowerCase();
-        return this.history
-            .filter(entry => 
-                entry.title.toLowerCase().includes(lowerQuery) ||
-                entry.url.toLowerCase().includes(lowerQuery)
-            )
-            .slice(-limit)
-            .reverse();
+    // Best matches first, one entry per page
+    async searchHistory(query, limit = 20) {
+        try {
+            const results = await BrowserAPI.searchHistory(query, { limit });
+            return results.map(result => ({
+                id: null,
+                url: result.url,
+                title: result.title || this.extractTitleFromUrl(result.url),
+                timestamp: new Date(result.last_visit_at * 1000).toISOString(),
+                favicon_url: this.getFaviconUrl(result.url),
+                visit_count: result.visit_count,
+                snippet: result.snippet
+            }));
+        } catch (error) {
+            return [];
+        }
     }
 
     getMostVisited(limit = 10) {
-        return this.history
+        // Each URL once, at its latest visit
+        const pages = new Map();
+        this.history.forEach(entry => {
+            if (!pages.has(entry.url)) {
+                pages.set(entry.url, entry);
+            }
+        });
+        return [...pages.values()]
             .filter(entry => entry.visit_count > 1)
             .sort((a, b) => b.visit_count - a.visit_count)
             .slice(0, limit);
     }
 
-    async saveHistory() {
-        try {
-            localStorage.setItem(this.storageKey, JSON.stringify(this.history));
-        } catch (error) {
-            console.error('Failed to save history:', error);
-        }
-    }
-
     extractTitleFromUrl(url) {
         try {
             const domain = new URL(url).hostname;
@@ -409,7 +385,7 @@ export class HistoryManager {
         }
     }
 
-    async getFaviconUrl(url) {
+    getFaviconUrl(url) {
         try {
             const domain = new URL(url).origin;
             return `${domain}/favicon.ico`;
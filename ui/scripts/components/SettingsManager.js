@@ -1,4 +1,6 @@
 // Settings Manager Component
+import { BrowserAPI } from '../api/browser.js';
+
 export class SettingsManager {
     constructor() {
         this.settings = {
@@ -421,6 +423,9 @@ export class SettingsManager {
         // Apply theme
         this.applyTheme(this.settings.general.theme);
         
+        // Address bar searches go through the backend
+        BrowserAPI.setSearchEngine(this.settings.general.searchEngine).catch(() => {});
+        
         // Emit settings change event
         document.dispatchEvent(new CustomEvent('settings-changed', {
             detail: this.settings
@@ -18,6 +18,9 @@ export class TabManager {
         // Setup tab container for drag and drop
         this.setupDragAndDrop();
         
+        // Keep tabs in sync with engine lifecycle events
+        this.unlistenTabEvents = await BrowserAPI.onTabEvent((event) => this.handleTabEvent(event));
+        
         // Load existing tabs
         await this.loadTabs();
         
@@ -93,6 +96,17 @@ export class TabManager {
         }
     }
 
+    async reopenClosedTab(tabId = null) {
+        try {
+            const reopenedId = await BrowserAPI.reopenClosedTab(tabId);
+            await this.loadTabs();
+            await this.setActiveTab(reopenedId);
+            return reopenedId;
+        } catch (error) {
+            console.error('Failed to reopen closed tab:', error);
+        }
+    }
+
     async setActiveTab(tabId) {
         try {
             await BrowserAPI.setActiveTab(tabId);
@@ -114,6 +128,54 @@ export class TabManager {
         }
     }
 
+    handleTabEvent(event) {
+        const tab = this.tabs.get(event.tab_id);
+        if (!tab) return;
+        
+        switch (event.type) {
+            case 'load_started':
+                tab.is_loading = true;
+                tab.load_progress = 0;
+                break;
+            case 'committed':
+                tab.url = event.url;
+                if (event.tab_id === this.activeTabId) {
+                    document.getElementById('address-input').value = event.url;
+                }
+                break;
+            case 'title_changed':
+                tab.title = event.title;
+                break;
+            case 'favicon_changed':
+                tab.favicon_url = event.url;
+                break;
+            case 'progress':
+                tab.load_progress = event.progress;
+                return; // Nothing in the tab strip shows progress yet
+            case 'load_finished':
+                tab.is_loading = false;
+                tab.load_progress = 1;
+                break;
+            case 'load_failed':
+                tab.is_loading = false;
+                tab.load_progress = 0;
+                console.warn(`Tab ${event.tab_id} failed to load ${event.url}:`, event.error);
+                break;
+        }
+        
+        this.updateTabElement(tab);
+    }
+
+    updateTabElement(tab) {
+        const oldElement = this.tabsContainer.querySelector(`[data-tab-id="${tab.id}"]`);
+        if (!oldElement) return;
+        
+        this.renderTab(tab);
+        const newElement = this.tabsContainer.lastElementChild;
+        oldElement.replaceWith(newElement);
+        this.updateActiveTab();
+    }
+
     renderTab(tab) {
         const tabElement = document.createElement('div');
         tabElement.className = `tab ${tab.is_active ? 'active' : ''}`;
@@ -257,6 +319,12 @@ export class TabManager {
                 this.switchToPreviousTab();
             }
             
+            // Ctrl+Shift+T - Reopen last closed tab
+            if (e.ctrlKey && e.shiftKey && (e.key === 'T' || e.key === 't')) {
+                e.preventDefault();
+                this.reopenClosedTab();
+            }
+            
             // Ctrl+1-9 - Switch to tab by number
             if (e.ctrlKey && e.key >= '1' && e.key <= '9') {
                 e.preventDefault();
@@ -341,8 +409,12 @@ export class TabManager {
                 await this.createTab(tab.url);
                 break;
             case 'pin':
-                // TODO: Implement pin/unpin functionality
-                console.log('Pin/unpin not implemented yet');
+                if (tab.is_pinned) {
+                    await BrowserAPI.unpinTab(tab.id);
+                } else {
+                    await BrowserAPI.pinTab(tab.id);
+                }
+                await this.loadTabs();
                 break;
             case 'close':
                 await this.closeTab(tab.id);
@@ -258,7 +258,7 @@ class VishwakarmaExpress {
     setupKeyboardShortcuts() {
         document.addEventListener('keydown', (e) => {
             // Ctrl/Cmd + T - New tab
-            if ((e.ctrlKey || e.metaKey) && e.key === 't') {
+            if ((e.ctrlKey || e.metaKey) && !e.shiftKey && e.key === 't') {
                 e.preventDefault();
                 this.createNewTab();
             }
@@ -432,33 +432,25 @@ class VishwakarmaExpress {
         if (!input.trim()) return;
         
         try {
-            let url;
-            
-            if (BrowserAPI.isValidUrl(input)) {
-                url = BrowserAPI.formatUrl(input);
-            } else {
-                // Treat as search query
-                url = BrowserAPI.createSearchUrl(input);
-            }
-            
             // Create new tab if no active tab
             if (!this.activeTabId) {
-                await this.createNewTab(url);
-            } else {
-                await BrowserAPI.navigateTo(url, this.activeTabId);
-                
-                // Update address bar
-                document.getElementById('address-input').value = url;
-                
-                // Show loading state
-                this.showLoadingIndicator();
-                
-                // Hide loading after delay (simulate page load)
-                setTimeout(() => {
-                    this.hideLoadingIndicator();
-                }, 2000);
+                await this.createNewTab();
             }
             
+            // The backend adds a scheme or turns the input into a search
+            const url = await BrowserAPI.navigateToUrl(this.activeTabId, input);
+            
+            // Update address bar
+            document.getElementById('address-input').value = url;
+            
+            // Show loading state
+            this.showLoadingIndicator();
+            
+            // Hide loading after delay (simulate page load)
+            setTimeout(() => {
+                this.hideLoadingIndicator();
+            }, 2000);
+            
             // Hide welcome screen
             this.hideWelcomeScreen();
             